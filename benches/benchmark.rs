@@ -1,7 +1,6 @@
 use std::sync::Arc;
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use dashmap::DashMap;
 // TODO: Re-export this under lunatic_runtime
 use lunatic_process::{
     env::LunaticEnvironment,
@@ -26,14 +25,12 @@ fn criterion_benchmark(c: &mut Criterion) {
     let env = Arc::new(LunaticEnvironment::new(0));
     c.bench_function("spawn process", |b| {
         b.to_async(&rt).iter(|| async {
-            let registry = Arc::new(DashMap::new());
             let state = DefaultProcessState::new(
                 env.clone(),
                 None,
                 runtime.clone(),
                 module.clone(),
                 config.clone(),
-                registry,
             )
             .unwrap();
             lunatic_process::wasm::spawn_wasm(
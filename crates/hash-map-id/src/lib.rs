@@ -35,6 +35,26 @@ where
     pub fn get(&self, id: u64) -> Option<&T> {
         self.store.get(&id)
     }
+
+    /// Inserts an item under a specific ID, replacing any existing item with the same ID.
+    ///
+    /// IDs returned by `add` will always be greater than `id` afterwards.
+    pub fn insert(&mut self, id: u64, item: T) -> Option<T> {
+        self.id_seed = self.id_seed.max(id + 1);
+        self.store.insert(id, item)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &T)> {
+        self.store.iter().map(|(id, item)| (*id, item))
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
 }
 
 impl<T> Default for HashMapId<T>
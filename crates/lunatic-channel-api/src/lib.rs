@@ -0,0 +1,239 @@
+use std::{future::Future, sync::Arc};
+
+use anyhow::Result;
+use hash_map_id::HashMapId;
+use lunatic_common_api::{get_memory, IntoTrap};
+use lunatic_process::{
+    channel::{channel, ChannelReceiver, ChannelSender},
+    message::Message,
+    state::ProcessState,
+};
+use lunatic_process_api::ProcessCtx;
+use tokio::time::{timeout, Duration};
+use wasmtime::{Caller, Linker, Trap};
+
+pub type ChannelSenderResources = HashMapId<Arc<ChannelSender>>;
+pub type ChannelReceiverResources = HashMapId<Arc<ChannelReceiver>>;
+
+pub trait ChannelCtx {
+    fn channel_sender_resources(&self) -> &ChannelSenderResources;
+    fn channel_sender_resources_mut(&mut self) -> &mut ChannelSenderResources;
+    fn channel_receiver_resources(&self) -> &ChannelReceiverResources;
+    fn channel_receiver_resources_mut(&mut self) -> &mut ChannelReceiverResources;
+}
+
+// Register the channel APIs to the linker
+pub fn register<T: ProcessState + ProcessCtx<T> + ChannelCtx + Send + 'static>(
+    linker: &mut Linker<T>,
+) -> Result<()> {
+    linker.func_wrap("lunatic::channel", "create", create)?;
+    linker.func_wrap("lunatic::channel", "clone_sender", clone_sender)?;
+    linker.func_wrap("lunatic::channel", "drop_sender", drop_sender)?;
+    linker.func_wrap("lunatic::channel", "drop_receiver", drop_receiver)?;
+    linker.func_wrap2_async("lunatic::channel", "send", send)?;
+    linker.func_wrap2_async("lunatic::channel", "receive", receive)?;
+    Ok(())
+}
+
+// Creates a new channel with a bounded capacity and writes the IDs of the sender and receiver
+// into **sender_id_ptr** and **receiver_id_ptr**.
+//
+// Channels carry data messages and are an alternative to mailboxes when a process needs
+// backpressure. Once the channel holds `capacity` messages, senders wait until the receiver
+// catches up. Both halves can be handed to other processes inside of messages.
+//
+// If `multi_producer` is 0, the sender can't be cloned.
+//
+// Traps:
+// * If the capacity is 0.
+// * If any memory outside the guest heap space is referenced.
+fn create<T: ChannelCtx>(
+    mut caller: Caller<T>,
+    capacity: u32,
+    multi_producer: u32,
+    sender_id_ptr: u32,
+    receiver_id_ptr: u32,
+) -> Result<(), Trap> {
+    if capacity == 0 {
+        return Err(Trap::new(
+            "lunatic::channel::create: capacity must be greater than 0",
+        ));
+    }
+    let (sender, receiver) = channel(capacity as usize, multi_producer != 0);
+    let sender_id = caller
+        .data_mut()
+        .channel_sender_resources_mut()
+        .add(Arc::new(sender));
+    let receiver_id = caller
+        .data_mut()
+        .channel_receiver_resources_mut()
+        .add(Arc::new(receiver));
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(
+            &mut caller,
+            sender_id_ptr as usize,
+            &sender_id.to_le_bytes(),
+        )
+        .or_trap("lunatic::channel::create")?;
+    memory
+        .write(
+            &mut caller,
+            receiver_id_ptr as usize,
+            &receiver_id.to_le_bytes(),
+        )
+        .or_trap("lunatic::channel::create")?;
+    Ok(())
+}
+
+// Clones the sender of a multi-producer channel and returns the ID of the new sender.
+//
+// Traps:
+// * If the sender ID doesn't exist.
+// * If the channel is single-producer.
+fn clone_sender<T: ChannelCtx>(mut caller: Caller<T>, sender_id: u64) -> Result<u64, Trap> {
+    let sender = caller
+        .data()
+        .channel_sender_resources()
+        .get(sender_id)
+        .or_trap("lunatic::channel::clone_sender")?
+        .try_clone()
+        .or_trap("lunatic::channel::clone_sender: channel is single-producer")?;
+    Ok(caller
+        .data_mut()
+        .channel_sender_resources_mut()
+        .add(Arc::new(sender)))
+}
+
+// Drops the sender resource. Once all senders are dropped, the receiver gets notified that
+// the channel is closed.
+//
+// Traps:
+// * If the sender ID doesn't exist.
+fn drop_sender<T: ChannelCtx>(mut caller: Caller<T>, sender_id: u64) -> Result<(), Trap> {
+    caller
+        .data_mut()
+        .channel_sender_resources_mut()
+        .remove(sender_id)
+        .or_trap("lunatic::channel::drop_sender")?;
+    Ok(())
+}
+
+// Drops the receiver resource. All following sends on the channel will fail.
+//
+// Traps:
+// * If the receiver ID doesn't exist.
+fn drop_receiver<T: ChannelCtx>(mut caller: Caller<T>, receiver_id: u64) -> Result<(), Trap> {
+    caller
+        .data_mut()
+        .channel_receiver_resources_mut()
+        .remove(receiver_id)
+        .or_trap("lunatic::channel::drop_receiver")?;
+    Ok(())
+}
+
+// Sends the data message from the scratch area through the channel.
+//
+// If the channel is full, the function waits until there is space in it. If timeout is
+// specified (value different from u64::MAX), the function will return on timeout expiration
+// with value 9027 and the message stays in the scratch area.
+//
+// Returns:
+// * 0    If the message was sent.
+// * 1    If the receiver was dropped. The message is dropped too.
+// * 9027 If call timed out.
+//
+// Traps:
+// * If the sender ID doesn't exist.
+// * If no data message is in the scratch area.
+fn send<T: ProcessState + ProcessCtx<T> + ChannelCtx + Send>(
+    mut caller: Caller<T>,
+    sender_id: u64,
+    timeout_duration: u64,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        let sender = caller
+            .data()
+            .channel_sender_resources()
+            .get(sender_id)
+            .or_trap("lunatic::channel::send")?
+            .clone();
+        if !matches!(
+            caller.data_mut().message_scratch_area(),
+            Some(Message::Data(_))
+        ) {
+            return Err(Trap::new(
+                "lunatic::channel::send: no data message in scratch area",
+            ));
+        }
+
+        let reserve = sender.reserve();
+        let permit = match timeout_duration {
+            // Without timeout
+            u64::MAX => Ok(reserve.await),
+            // With timeout
+            t => timeout(Duration::from_millis(t), reserve).await,
+        };
+        match permit {
+            Ok(Ok(permit)) => {
+                if let Some(Message::Data(message)) =
+                    caller.data_mut().message_scratch_area().take()
+                {
+                    permit.send(message);
+                }
+                Ok(0)
+            }
+            Ok(Err(_)) => {
+                caller.data_mut().message_scratch_area().take();
+                Ok(1)
+            }
+            Err(_) => Ok(9027),
+        }
+    })
+}
+
+// Waits on the next message in the channel and puts it into the scratch area.
+//
+// If timeout is specified (value different from u64::MAX), the function will return on timeout
+// expiration with value 9027.
+//
+// Returns:
+// * 0    If a message was received.
+// * 1    If all senders were dropped and there are no messages left in the channel.
+// * 9027 If call timed out.
+//
+// Traps:
+// * If the receiver ID doesn't exist.
+fn receive<T: ProcessState + ProcessCtx<T> + ChannelCtx + Send>(
+    mut caller: Caller<T>,
+    receiver_id: u64,
+    timeout_duration: u64,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        let receiver = caller
+            .data()
+            .channel_receiver_resources()
+            .get(receiver_id)
+            .or_trap("lunatic::channel::receive")?
+            .clone();
+
+        let recv = receiver.recv();
+        let message = match timeout_duration {
+            // Without timeout
+            u64::MAX => Ok(recv.await),
+            // With timeout
+            t => timeout(Duration::from_millis(t), recv).await,
+        };
+        match message {
+            Ok(Some(message)) => {
+                caller
+                    .data_mut()
+                    .message_scratch_area()
+                    .replace(Message::Data(message));
+                Ok(0)
+            }
+            Ok(None) => Ok(1),
+            Err(_) => Ok(9027),
+        }
+    })
+}
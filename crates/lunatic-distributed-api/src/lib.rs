@@ -3,13 +3,13 @@ use std::{future::Future, sync::Arc, time::Duration};
 use anyhow::{anyhow, Result};
 use lunatic_common_api::{get_memory, IntoTrap};
 use lunatic_distributed::{
-    distributed::message::{ClientError, Spawn, Val},
+    distributed::message::{serialize_resources, ClientError, Spawn, Val},
     DistributedCtx,
 };
 use lunatic_error_api::ErrorCtx;
 use lunatic_process::{
     env::Environment,
-    message::{DataMessage, Message},
+    message::{DataMessage, Message, MessageSender},
 };
 use lunatic_process_api::ProcessCtx;
 use tokio::time::timeout;
@@ -325,15 +325,20 @@ where
 //
 // There are no guarantees that the message will be received.
 //
+// Only module resources can be sent to remote nodes, they are looked up by the receiving node
+// through the control server.
+//
 // Returns:
 // * 0      If message sent
 // * 1      If process_id does not exist
 // * 2      If node_id does not exist
+// * 3      If the message contains resources that can't be sent to remote nodes
+// * 4      If a module in the message is not known to the control server
+// * 5      If the message is larger than the receiving node accepts
 // * 9027   If node connection error occurred
 //
 // Traps:
 // * If it's called before creating the next message.
-// * If the message contains resources
 fn send<T, E>(
     mut caller: Caller<T>,
     node_id: u64,
@@ -358,15 +363,27 @@ where
             ..
         }) = message
         {
-            if !resources.is_empty() {
-                return Err(Trap::new("Cannot send resources to remote nodes."));
-            }
+            let resources = match serialize_resources::<T>(resources) {
+                Ok(resources) => resources,
+                Err(_) => return Ok(3),
+            };
 
             let state = caller.data();
             match state
                 .distributed()?
                 .node_client
-                .message_process(node_id, state.environment_id(), process_id, tag, buffer)
+                .message_process(
+                    node_id,
+                    state.environment_id(),
+                    process_id,
+                    tag,
+                    buffer,
+                    resources,
+                    MessageSender {
+                        node_id: state.distributed()?.node_id(),
+                        process_id: state.id(),
+                    },
+                )
                 .await
             {
                 Ok(_) => Ok(0),
@@ -374,8 +391,10 @@ where
                     ClientError::Unexpected(cause) => Err(Trap::new(cause)),
                     ClientError::ProcessNotFound => Ok(1),
                     ClientError::NodeNotFound => Ok(2),
+                    ClientError::UnsupportedResource(_) => Ok(3),
+                    ClientError::ModuleNotFound => Ok(4),
+                    ClientError::MessageTooLarge => Ok(5),
                     ClientError::Connection(_) => Ok(9027),
-                    _ => Err(Trap::new("unreachable")),
                 },
             }
         } else {
@@ -400,11 +419,13 @@ where
 // * 0    If message arrived.
 // * 1    If process_id does not exist
 // * 2    If node_id does not exist
-// * 9027 If call timed out.
+// * 3    If the message contains resources that can't be sent to remote nodes
+// * 4    If a module in the message is not known to the control server
+// * 5    If the message is larger than the receiving node accepts
+// * 9027 If call timed out or a node connection error occurred.
 //
 // Traps:
 // * If it's called with wrong data in the scratch area.
-// * If the message contains resources
 fn send_receive_skip_search<T, E>(
     mut caller: Caller<T>,
     node_id: u64,
@@ -438,23 +459,38 @@ where
             ..
         }) = message
         {
-            if !resources.is_empty() {
-                return Err(Trap::new("Cannot send resources to remote nodes."));
-            }
+            let resources = match serialize_resources::<T>(resources) {
+                Ok(resources) => resources,
+                Err(_) => return Ok(3),
+            };
 
             let state = caller.data();
             let code = match state
                 .distributed()?
                 .node_client
-                .message_process(node_id, state.environment_id(), process_id, tag, buffer)
+                .message_process(
+                    node_id,
+                    state.environment_id(),
+                    process_id,
+                    tag,
+                    buffer,
+                    resources,
+                    MessageSender {
+                        node_id: state.distributed()?.node_id(),
+                        process_id: state.id(),
+                    },
+                )
                 .await
             {
                 Ok(_) => Ok(0),
                 Err(error) => match error {
                     ClientError::ProcessNotFound => Ok(1),
                     ClientError::NodeNotFound => Ok(2),
+                    ClientError::UnsupportedResource(_) => Ok(3),
+                    ClientError::ModuleNotFound => Ok(4),
+                    ClientError::MessageTooLarge => Ok(5),
+                    ClientError::Connection(_) => Ok(9027),
                     ClientError::Unexpected(cause) => Err(Trap::new(cause)),
-                    _ => Err(Trap::new("unreachable")),
                 },
             }?;
 
@@ -36,6 +36,11 @@ pub struct InnerClient {
     nodes: DashMap<u64, NodeInfo>,
     node_ids: RwLock<Vec<u64>>,
     attributes: HashMap<String, String>,
+    // Cluster-wide registry lookups by (environment ID, name), invalidated by notifications from
+    // the control server.
+    registry: DashMap<(u64, String), Option<(u64, u64)>>,
+    // Incremented on each invalidation, so that lookups racing with it are not cached.
+    registry_generation: AtomicU64,
 }
 
 impl Client {
@@ -62,6 +67,8 @@ impl Client {
                 nodes: Default::default(),
                 node_ids: Default::default(),
                 attributes,
+                registry: DashMap::new(),
+                registry_generation: AtomicU64::new(0),
             }),
         };
         // Spawn reader task before register
@@ -124,6 +131,13 @@ impl Client {
     }
 
     fn process_response(&self, id: u64, resp: Response) {
+        // Message IDs start at 1, the ID 0 is used for notifications.
+        if id == 0 {
+            if let Response::RegistryChanged(environment_id, name) = resp {
+                self.invalidate_registry(Some((environment_id, name)));
+            }
+            return;
+        }
         if let Some(e) = self.inner.pending_requests.get(&id) {
             e.set(resp);
         };
@@ -196,6 +210,168 @@ impl Client {
             Err(anyhow::anyhow!("Invalid response type on add_module."))
         }
     }
+
+    /// Registers the process under `name` in the cluster-wide registry of the environment.
+    pub async fn registry_put(
+        &self,
+        environment_id: u64,
+        name: String,
+        node_id: u64,
+        process_id: u64,
+    ) -> Result<()> {
+        match self
+            .send(Request::RegistryPut(
+                environment_id,
+                name,
+                node_id,
+                process_id,
+            ))
+            .await?
+        {
+            Response::None => Ok(()),
+            Response::Error(message) => Err(anyhow!(message)),
+            _ => Err(anyhow!("Invalid response type on registry_put.")),
+        }
+    }
+
+    /// Looks up the (node ID, process ID) registered under `name` in the cluster-wide registry of
+    /// the environment.
+    ///
+    /// Results are cached until the control server notifies the node that the name changed.
+    pub async fn registry_get(
+        &self,
+        environment_id: u64,
+        name: &str,
+    ) -> Result<Option<(u64, u64)>> {
+        let key = (environment_id, name.to_string());
+        if let Some(registration) = self.inner.registry.get(&key) {
+            return Ok(*registration);
+        }
+        let generation = self
+            .inner
+            .registry_generation
+            .load(atomic::Ordering::Acquire);
+        match self
+            .send(Request::RegistryGet(environment_id, name.to_string()))
+            .await?
+        {
+            Response::ProcessRegistration(registration) => {
+                self.inner.registry.insert(key.clone(), registration);
+                // Don't keep the result if the name could have changed after it was looked up.
+                if self
+                    .inner
+                    .registry_generation
+                    .load(atomic::Ordering::Acquire)
+                    != generation
+                {
+                    self.inner.registry.remove(&key);
+                }
+                Ok(registration)
+            }
+            Response::Error(message) => Err(anyhow!(message)),
+            _ => Err(anyhow!("Invalid response type on registry_get.")),
+        }
+    }
+
+    /// Removes the name from the cluster-wide registry of the environment.
+    pub async fn registry_remove(&self, environment_id: u64, name: String) -> Result<()> {
+        match self
+            .send(Request::RegistryRemove(environment_id, name))
+            .await?
+        {
+            Response::None => Ok(()),
+            Response::Error(message) => Err(anyhow!(message)),
+            _ => Err(anyhow!("Invalid response type on registry_remove.")),
+        }
+    }
+
+    /// Removes the name from the cluster-wide registry of the environment, but only if it's still
+    /// registered to the process (node ID, process ID).
+    pub async fn registry_remove_if(
+        &self,
+        environment_id: u64,
+        name: String,
+        node_id: u64,
+        process_id: u64,
+    ) -> Result<()> {
+        match self
+            .send(Request::RegistryRemoveIf(
+                environment_id,
+                name,
+                node_id,
+                process_id,
+            ))
+            .await?
+        {
+            Response::None => Ok(()),
+            Response::Error(message) => Err(anyhow!(message)),
+            _ => Err(anyhow!("Invalid response type on registry_remove_if.")),
+        }
+    }
+
+    /// Registers the process under `name` in the cluster-wide registry of the environment, but
+    /// only if no process is registered under it yet. Returns true if the process was registered.
+    pub async fn registry_put_if_absent(
+        &self,
+        environment_id: u64,
+        name: String,
+        node_id: u64,
+        process_id: u64,
+    ) -> Result<bool> {
+        match self
+            .send(Request::RegistryPutIfAbsent(
+                environment_id,
+                name,
+                node_id,
+                process_id,
+            ))
+            .await?
+        {
+            Response::RegistryUpdated(updated) => Ok(updated),
+            Response::Error(message) => Err(anyhow!(message)),
+            _ => Err(anyhow!("Invalid response type on registry_put_if_absent.")),
+        }
+    }
+
+    /// Registers the process under `name` in the cluster-wide registry of the environment, but
+    /// only if the `expected` (node ID, process ID) is registered under it. Returns true if the
+    /// registration was replaced.
+    pub async fn registry_compare_and_swap(
+        &self,
+        environment_id: u64,
+        name: String,
+        expected: (u64, u64),
+        registration: (u64, u64),
+    ) -> Result<bool> {
+        match self
+            .send(Request::RegistryCas(
+                environment_id,
+                name,
+                expected,
+                registration,
+            ))
+            .await?
+        {
+            Response::RegistryUpdated(updated) => Ok(updated),
+            Response::Error(message) => Err(anyhow!(message)),
+            _ => Err(anyhow!(
+                "Invalid response type on registry_compare_and_swap."
+            )),
+        }
+    }
+
+    // Removes the (environment ID, name) from the registry cache, or all names if `key` is `None`.
+    fn invalidate_registry(&self, key: Option<(u64, String)>) {
+        self.inner
+            .registry_generation
+            .fetch_add(1, atomic::Ordering::AcqRel);
+        match key {
+            Some(key) => {
+                self.inner.registry.remove(&key);
+            }
+            None => self.inner.registry.clear(),
+        }
+    }
 }
 
 async fn reader_task(client: Client, mut recv: RecvStream) -> Result<()> {
@@ -240,6 +416,8 @@ async fn connection_task(
                 log::debug!("Cannot send data to control node: {e}, reconnecting...");
                 let (new_send, new_recv) =
                     quic::try_connect_forever(&quic_client, addr, &name).await;
+                // Notifications could have been missed while disconnected.
+                client.invalidate_registry(None);
                 tokio::spawn(reader_task(client.clone(), new_recv));
                 send = new_send;
             }
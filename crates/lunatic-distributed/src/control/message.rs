@@ -14,6 +14,19 @@ pub enum Request {
     LookupNodes(String),
     AddModule(Vec<u8>),
     GetModule(u64),
+    // Registers the process (node ID, process ID) under the name in the cluster-wide registry of
+    // the environment. Environments don't share names.
+    RegistryPut(u64, String, u64, u64),
+    RegistryGet(u64, String),
+    RegistryRemove(u64, String),
+    // Removes the name only if it's still registered to the process (node ID, process ID).
+    RegistryRemoveIf(u64, String, u64, u64),
+    // Registers the process (node ID, process ID) under the name if no process is registered
+    // under it yet.
+    RegistryPutIfAbsent(u64, String, u64, u64),
+    // Registers the process (node ID, process ID) under the name if the process registered under
+    // it is the expected (node ID, process ID).
+    RegistryCas(u64, String, (u64, u64), (u64, u64)),
 }
 
 impl Request {
@@ -25,6 +38,12 @@ impl Request {
             Request::LookupNodes(_) => "LookupNodes",
             Request::AddModule(_) => "AddModule",
             Request::GetModule(_) => "GetModule",
+            Request::RegistryPut(..) => "RegistryPut",
+            Request::RegistryGet(..) => "RegistryGet",
+            Request::RegistryRemove(..) => "RegistryRemove",
+            Request::RegistryRemoveIf(..) => "RegistryRemoveIf",
+            Request::RegistryPutIfAbsent(..) => "RegistryPutIfAbsent",
+            Request::RegistryCas(..) => "RegistryCas",
         }
     }
 }
@@ -35,6 +54,12 @@ pub enum Response {
     Nodes(Vec<NodeInfo>),
     Module(Option<Vec<u8>>),
     ModuleId(u64),
+    ProcessRegistration(Option<(u64, u64)>),
+    // If a conditional registry update was applied.
+    RegistryUpdated(bool),
+    // Pushed to all nodes with the message ID 0 when a name in the cluster-wide registry of an
+    // environment changes.
+    RegistryChanged(u64, String),
     Error(String),
     None,
 }
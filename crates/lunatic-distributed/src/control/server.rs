@@ -14,8 +14,9 @@ use crate::{
 };
 use anyhow::Result;
 use bytes::Bytes;
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
 use rcgen::*;
+use tokio::sync::mpsc::UnboundedSender;
 
 use super::parser::Parser;
 
@@ -30,6 +31,11 @@ struct InnerServer {
     addr_to_node: DashMap<SocketAddr, u64>,
     next_module_id: AtomicU64,
     modules: DashMap<u64, Vec<u8>>,
+    // Cluster-wide registry, (environment ID, name) maps to (node ID, process ID).
+    registry: DashMap<(u64, String), (u64, u64)>,
+    next_subscriber_id: AtomicU64,
+    // Connections of nodes that are notified about registry changes.
+    subscribers: DashMap<u64, UnboundedSender<Response>>,
     ca_cert: Certificate,
 }
 
@@ -42,6 +48,9 @@ impl Server {
                 nodes: DashMap::new(),
                 addr_to_node: DashMap::new(),
                 modules: DashMap::new(),
+                registry: DashMap::new(),
+                next_subscriber_id: AtomicU64::new(1),
+                subscribers: DashMap::new(),
                 ca_cert,
             }),
         }
@@ -85,6 +94,19 @@ impl Server {
 
     pub fn deregister(&self, node_id: u64) -> Response {
         self.inner.nodes.remove(&node_id);
+        // Names registered to processes on the node would point to processes that don't exist.
+        let mut removed = Vec::new();
+        self.inner.registry.retain(|key, (registered_node_id, _)| {
+            if *registered_node_id == node_id {
+                removed.push(key.clone());
+                false
+            } else {
+                true
+            }
+        });
+        for (environment_id, name) in removed {
+            self.notify_registry_change(environment_id, name);
+        }
         Response::None
     }
 
@@ -130,6 +152,120 @@ impl Server {
     pub fn get_module(&self, id: u64) -> Response {
         Response::Module(self.inner.modules.get(&id).map(|e| e.clone()))
     }
+
+    pub fn registry_put(
+        &self,
+        environment_id: u64,
+        name: String,
+        node_id: u64,
+        process_id: u64,
+    ) -> Response {
+        self.inner
+            .registry
+            .insert((environment_id, name.clone()), (node_id, process_id));
+        self.notify_registry_change(environment_id, name);
+        Response::None
+    }
+
+    pub fn registry_get(&self, environment_id: u64, name: String) -> Response {
+        Response::ProcessRegistration(self.inner.registry.get(&(environment_id, name)).map(|e| *e))
+    }
+
+    pub fn registry_remove(&self, environment_id: u64, name: String) -> Response {
+        if self
+            .inner
+            .registry
+            .remove(&(environment_id, name.clone()))
+            .is_some()
+        {
+            self.notify_registry_change(environment_id, name);
+        }
+        Response::None
+    }
+
+    pub fn registry_remove_if(
+        &self,
+        environment_id: u64,
+        name: String,
+        node_id: u64,
+        process_id: u64,
+    ) -> Response {
+        if self
+            .inner
+            .registry
+            .remove_if(&(environment_id, name.clone()), |_, registration| {
+                *registration == (node_id, process_id)
+            })
+            .is_some()
+        {
+            self.notify_registry_change(environment_id, name);
+        }
+        Response::None
+    }
+
+    pub fn registry_put_if_absent(
+        &self,
+        environment_id: u64,
+        name: String,
+        node_id: u64,
+        process_id: u64,
+    ) -> Response {
+        let updated = match self.inner.registry.entry((environment_id, name.clone())) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert((node_id, process_id));
+                true
+            }
+        };
+        if updated {
+            self.notify_registry_change(environment_id, name);
+        }
+        Response::RegistryUpdated(updated)
+    }
+
+    pub fn registry_compare_and_swap(
+        &self,
+        environment_id: u64,
+        name: String,
+        expected: (u64, u64),
+        registration: (u64, u64),
+    ) -> Response {
+        let updated = match self.inner.registry.get_mut(&(environment_id, name.clone())) {
+            Some(mut current) if *current == expected => {
+                *current = registration;
+                true
+            }
+            _ => false,
+        };
+        if updated {
+            self.notify_registry_change(environment_id, name);
+        }
+        Response::RegistryUpdated(updated)
+    }
+
+    /// Subscribes a node connection to registry change notifications and returns the ID of the
+    /// subscription.
+    pub fn subscribe(&self, sender: UnboundedSender<Response>) -> u64 {
+        let id = self
+            .inner
+            .next_subscriber_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        self.inner.subscribers.insert(id, sender);
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u64) {
+        self.inner.subscribers.remove(&id);
+    }
+
+    fn notify_registry_change(&self, environment_id: u64, name: String) {
+        // Closed connections are removed when they are noticed.
+        self.inner.subscribers.retain(|_, subscriber| {
+            subscriber
+                .send(Response::RegistryChanged(environment_id, name.clone()))
+                .is_ok()
+        });
+    }
 }
 
 pub static CTRL_SERVER_NAME: &str = "ctrl.lunatic.cloud";
@@ -218,6 +354,20 @@ pub async fn handle_request(
         AddModule(bytes) => server.add_module(bytes),
         GetModule(id) => server.get_module(id),
         LookupNodes(query) => server.lookup_nodes(query),
+        RegistryPut(environment_id, name, node_id, process_id) => {
+            server.registry_put(environment_id, name, node_id, process_id)
+        }
+        RegistryGet(environment_id, name) => server.registry_get(environment_id, name),
+        RegistryRemove(environment_id, name) => server.registry_remove(environment_id, name),
+        RegistryRemoveIf(environment_id, name, node_id, process_id) => {
+            server.registry_remove_if(environment_id, name, node_id, process_id)
+        }
+        RegistryPutIfAbsent(environment_id, name, node_id, process_id) => {
+            server.registry_put_if_absent(environment_id, name, node_id, process_id)
+        }
+        RegistryCas(environment_id, name, expected, registration) => {
+            server.registry_compare_and_swap(environment_id, name, expected, registration)
+        }
     };
     let data = bincode::serialize(&(msg_id, response))?;
     let size = (data.len() as u32).to_le_bytes();
@@ -226,3 +376,92 @@ pub async fn handle_request(
     send.send(&mut [size, bytes]).await?;
     Ok(msg_id)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(response: Response) -> Option<(u64, u64)> {
+        match response {
+            Response::ProcessRegistration(registration) => registration,
+            _ => panic!("unexpected response"),
+        }
+    }
+
+    #[test]
+    fn registry_changes_are_pushed() {
+        let server = Server::new(root_cert(true, None, None).unwrap());
+        let (sender, mut notifications) = tokio::sync::mpsc::unbounded_channel();
+        let subscription = server.subscribe(sender);
+
+        server.registry_put(1, "worker".into(), 1, 10);
+        server.registry_put(1, "logger".into(), 2, 20);
+        assert_eq!(
+            registration(server.registry_get(1, "worker".into())),
+            Some((1, 10))
+        );
+        // Names are only visible inside their environment.
+        assert_eq!(registration(server.registry_get(2, "worker".into())), None);
+
+        server.registry_remove(1, "worker".into());
+        // Removing a name that isn't registered doesn't notify anyone.
+        server.registry_remove(1, "worker".into());
+        assert_eq!(registration(server.registry_get(1, "worker".into())), None);
+
+        // Conditional removes only apply to the expected process.
+        server.registry_remove_if(1, "logger".into(), 2, 21);
+        assert_eq!(
+            registration(server.registry_get(1, "logger".into())),
+            Some((2, 20))
+        );
+        server.registry_put(1, "owned".into(), 3, 30);
+        server.registry_remove_if(1, "owned".into(), 3, 30);
+        assert_eq!(registration(server.registry_get(1, "owned".into())), None);
+
+        // Conditional updates are applied atomically and only notify if they succeed.
+        let updated = |response| matches!(response, Response::RegistryUpdated(true));
+        assert!(updated(server.registry_put_if_absent(
+            1,
+            "leader".into(),
+            3,
+            30
+        )));
+        assert!(!updated(server.registry_put_if_absent(
+            1,
+            "leader".into(),
+            3,
+            31
+        )));
+        assert!(!updated(server.registry_compare_and_swap(
+            1,
+            "leader".into(),
+            (3, 31),
+            (3, 32)
+        )));
+        assert!(updated(server.registry_compare_and_swap(
+            1,
+            "leader".into(),
+            (3, 30),
+            (3, 32)
+        )));
+        assert_eq!(
+            registration(server.registry_get(1, "leader".into())),
+            Some((3, 32))
+        );
+
+        // Names registered to processes of a node are removed when the node leaves.
+        server.deregister(2);
+        assert_eq!(registration(server.registry_get(1, "logger".into())), None);
+
+        server.unsubscribe(subscription);
+        let mut changed = Vec::new();
+        while let Ok(Response::RegistryChanged(environment_id, name)) = notifications.try_recv() {
+            assert_eq!(environment_id, 1);
+            changed.push(name);
+        }
+        assert_eq!(
+            changed,
+            vec!["worker", "logger", "worker", "owned", "owned", "leader", "leader", "logger"]
+        );
+    }
+}
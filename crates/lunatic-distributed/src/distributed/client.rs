@@ -2,12 +2,13 @@ use anyhow::Result;
 use async_cell::sync::AsyncCell;
 use bytes::Bytes;
 use dashmap::DashMap;
+use lunatic_process::message::MessageSender;
 use std::sync::{atomic, atomic::AtomicU64, Arc};
 use tokio::sync::mpsc::{self, unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 use crate::{
     control,
-    distributed::message::{ClientError, Request, Response},
+    distributed::message::{ClientError, Request, Resource, Response},
     quic::{self, RecvStream},
     NodeInfo,
 };
@@ -78,6 +79,7 @@ impl Client {
         Ok(response)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn message_process(
         &self,
         node_id: u64,
@@ -85,6 +87,8 @@ impl Client {
         process_id: u64,
         tag: Option<i64>,
         data: Vec<u8>,
+        resources: Vec<Option<Resource>>,
+        sender: MessageSender,
     ) -> Result<(), ClientError> {
         match self
             .request(
@@ -94,6 +98,8 @@ impl Client {
                     process_id,
                     tag,
                     data,
+                    resources,
+                    sender,
                 },
             )
             .await
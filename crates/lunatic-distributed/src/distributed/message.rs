@@ -1,4 +1,10 @@
+use std::sync::Arc;
+
 use bytes::Bytes;
+use lunatic_process::{
+    message::{self, MessageSender},
+    runtimes::wasmtime::WasmtimeCompiledModule,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -9,6 +15,8 @@ pub enum Request {
         process_id: u64,
         tag: Option<i64>,
         data: Vec<u8>,
+        resources: Vec<Option<Resource>>,
+        sender: MessageSender,
     },
 }
 
@@ -30,6 +38,43 @@ pub struct Spawn {
     pub config: Vec<u8>,
 }
 
+/// A message resource in the form that can be sent to another node.
+///
+/// Slots that were already taken out of a message are sent as `None` so that the indexes of all
+/// other resources are preserved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Resource {
+    /// A module, referenced by the id that the control server assigned to it on `AddModule`.
+    Module(u64),
+}
+
+// Turns the resources of a message into their remote representation.
+//
+// Modules are sent as the id that the control server assigned to them. Modules compiled at
+// runtime were never registered with the control server and, as all other resources, can't be
+// sent to remote nodes.
+pub fn serialize_resources<T: 'static>(
+    resources: Vec<Option<Arc<message::Resource>>>,
+) -> Result<Vec<Option<Resource>>, ClientError> {
+    resources
+        .into_iter()
+        .map(|resource| match resource {
+            Some(resource) => match resource.downcast_ref::<WasmtimeCompiledModule<T>>() {
+                Some(module) => match module.source().id {
+                    Some(module_id) => Ok(Some(Resource::Module(module_id))),
+                    None => Err(ClientError::UnsupportedResource(
+                        "Module is not registered with the control server".to_string(),
+                    )),
+                },
+                None => Err(ClientError::UnsupportedResource(
+                    "Only modules can be sent to remote nodes".to_string(),
+                )),
+            },
+            None => Ok(None),
+        })
+        .collect()
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ClientError {
     Unexpected(String),
@@ -37,6 +82,10 @@ pub enum ClientError {
     NodeNotFound,
     ModuleNotFound,
     ProcessNotFound,
+    /// The message contains a resource that can't be sent to other nodes.
+    UnsupportedResource(String),
+    /// The message is larger than the receiving node accepts.
+    MessageTooLarge,
 }
 
 impl Default for ClientError {
@@ -4,8 +4,11 @@ use anyhow::{anyhow, Result};
 
 use lunatic_process::{
     env::{Environment, Environments},
-    message::{DataMessage, Message},
-    runtimes::{wasmtime::WasmtimeRuntime, Modules, RawWasm},
+    message::{DataMessage, Message, MessageSender},
+    runtimes::{
+        wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime},
+        Modules, RawWasm,
+    },
     state::ProcessState,
     Signal,
 };
@@ -18,13 +21,15 @@ use crate::{
     DistributedCtx, DistributedProcessState,
 };
 
-use super::message::{ClientError, Spawn};
+use super::message::{ClientError, Resource, Spawn};
 
 pub struct ServerCtx<T, E: Environment> {
     pub envs: Arc<dyn Environments<Env = E>>,
     pub modules: Modules<T>,
     pub distributed: DistributedProcessState,
     pub runtime: WasmtimeRuntime,
+    // Maximum size of data messages accepted from other nodes, unlimited if `None`
+    pub max_message_size: Option<usize>,
 }
 
 impl<T: 'static, E: Environment> Clone for ServerCtx<T, E> {
@@ -34,6 +39,7 @@ impl<T: 'static, E: Environment> Clone for ServerCtx<T, E> {
             modules: self.modules.clone(),
             distributed: self.distributed.clone(),
             runtime: self.runtime.clone(),
+            max_message_size: self.max_message_size,
         }
     }
 }
@@ -124,7 +130,19 @@ where
             process_id,
             tag,
             data,
-        } => match handle_process_message(ctx, environment_id, process_id, tag, data).await {
+            resources,
+            sender,
+        } => match handle_process_message(
+            ctx,
+            environment_id,
+            process_id,
+            tag,
+            data,
+            resources,
+            sender,
+        )
+        .await
+        {
             Ok(_) => {
                 let mut data = super::message::pack_response(msg_id, Response::Sent);
                 send.send(&mut data).await?;
@@ -154,16 +172,9 @@ where
     let config: T::Config = bincode::deserialize(&config[..])?;
     let config = Arc::new(config);
 
-    let module = match ctx.modules.get(module_id) {
+    let module = match get_module(&ctx, module_id).await? {
         Some(module) => module,
-        None => {
-            if let Some(bytes) = ctx.distributed.control.get_module(module_id).await {
-                let wasm = RawWasm::new(Some(module_id), bytes);
-                ctx.modules.compile(ctx.runtime.clone(), wasm).await??
-            } else {
-                return Ok(Err(ClientError::ModuleNotFound));
-            }
-        }
+        None => return Ok(Err(ClientError::ModuleNotFound)),
     };
 
     let env = ctx
@@ -187,23 +198,68 @@ where
     Ok(Ok(proc.id()))
 }
 
+// Returns the module with `module_id`, fetching it from the control server and compiling it if
+// this node didn't see it before.
+async fn get_module<T, E>(
+    ctx: &ServerCtx<T, E>,
+    module_id: u64,
+) -> Result<Option<Arc<WasmtimeCompiledModule<T>>>>
+where
+    T: ProcessState + 'static,
+    E: Environment,
+{
+    match ctx.modules.get(module_id) {
+        Some(module) => Ok(Some(module)),
+        None => {
+            if let Some(bytes) = ctx.distributed.control.get_module(module_id).await {
+                let wasm = RawWasm::new(Some(module_id), bytes);
+                Ok(Some(ctx.modules.compile(ctx.runtime.clone(), wasm).await??))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
 async fn handle_process_message<T, E>(
     ctx: ServerCtx<T, E>,
     environment_id: u64,
     process_id: u64,
     tag: Option<i64>,
     data: Vec<u8>,
+    resources: Vec<Option<Resource>>,
+    sender: MessageSender,
 ) -> std::result::Result<(), ClientError>
 where
     T: ProcessState + DistributedCtx<E> + ResourceLimiter + Send + 'static,
     E: Environment,
 {
+    if let Some(max_message_size) = ctx.max_message_size {
+        if data.len() > max_message_size {
+            #[cfg(feature = "metrics")]
+            metrics::increment_counter!("lunatic.process.messages.data.rejected");
+            return Err(ClientError::MessageTooLarge);
+        }
+    }
+
     let env = ctx.envs.get(environment_id);
     if let Some(env) = env {
         if let Some(proc) = env.get_process(process_id) {
-            proc.send(Signal::Message(Message::Data(DataMessage::new_from_vec(
-                tag, data,
-            ))));
+            let mut message = DataMessage::new_from_vec(tag, data);
+            message.sender = Some(sender);
+            for resource in resources {
+                match resource {
+                    Some(Resource::Module(module_id)) => {
+                        let module = get_module(&ctx, module_id)
+                            .await
+                            .map_err(|error| ClientError::Unexpected(error.to_string()))?
+                            .ok_or(ClientError::ModuleNotFound)?;
+                        message.add_resource(module);
+                    }
+                    None => message.resources.push(None),
+                }
+            }
+            proc.send(Signal::Message(Message::Data(message)));
         } else {
             return Err(ClientError::ProcessNotFound);
         }
@@ -9,7 +9,7 @@ use lunatic_process::{
     state::ProcessState,
 };
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, sync::Arc};
+use std::{future::Future, net::SocketAddr, sync::Arc};
 
 pub trait DistributedCtx<E: Environment>: ProcessState + Sized {
     fn new_dist_state(
@@ -26,6 +26,34 @@ pub trait DistributedCtx<E: Environment>: ProcessState + Sized {
     fn can_spawn(&self) -> bool;
 }
 
+/// Looks up the (node ID, process ID) registered under `name` in the environment of the process.
+///
+/// Names registered on this node are looked up first. If the node is part of a cluster and the
+/// name isn't registered locally, it's looked up in the cluster-wide registry of the environment.
+pub fn lookup_name<T, E>(state: &T, name: &str) -> impl Future<Output = Result<Option<(u64, u64)>>>
+where
+    T: DistributedCtx<E>,
+    E: Environment,
+{
+    let registration = state
+        .registry()
+        .get(name)
+        .map(|registration| (registration.node_id, registration.process_id));
+    let control = state
+        .distributed()
+        .ok()
+        .map(|distributed| distributed.control.clone());
+    let environment_id = state.environment_id();
+    let name = name.to_owned();
+    async move {
+        match (registration, control) {
+            (Some(registration), _) => Ok(Some(registration)),
+            (None, Some(control)) => control.registry_get(environment_id, &name).await,
+            (None, None) => Ok(None),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct DistributedProcessState {
     node_id: u64,
@@ -140,19 +140,41 @@ async fn handle_quic_stream(
 }
 
 async fn handle_quic_connection(
-    mut send: SendStream,
+    send: SendStream,
     mut recv: RecvStream,
     control_server: control::server::Server,
 ) {
+    // Registry changes are pushed to the node over the same stream as responses.
+    let send = Arc::new(tokio::sync::Mutex::new(send));
+    let (notifications_tx, mut notifications) = tokio::sync::mpsc::unbounded_channel();
+    let subscription = control_server.subscribe(notifications_tx);
+    let notifications_send = send.clone();
+    tokio::spawn(async move {
+        while let Some(notification) = notifications.recv().await {
+            let mut data = control::message::pack_response(0, notification);
+            if notifications_send
+                .lock()
+                .await
+                .send(&mut data)
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
     while let Ok(bytes) = recv.receive().await {
         if let Ok((msg_id, request)) =
             bincode::deserialize::<(u64, control::message::Request)>(&bytes)
         {
+            let mut send = send.lock().await;
             control::server::handle_request(control_server.clone(), &mut send, msg_id, request)
                 .await
                 .ok();
         }
     }
+    // Dropping the subscription also ends the notification task.
+    control_server.unsubscribe(subscription);
 }
 
 pub async fn handle_node_server<T, E>(
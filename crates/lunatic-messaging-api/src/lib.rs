@@ -2,44 +2,132 @@ use std::{
     convert::TryInto,
     future::Future,
     io::{Read, Write},
+    sync::Arc,
 };
 
 use anyhow::Result;
+use hash_map_id::HashMapId;
+use lunatic_channel_api::ChannelCtx;
 use lunatic_common_api::{get_memory, IntoTrap};
+use lunatic_distributed::{
+    distributed::message::{serialize_resources, ClientError},
+    lookup_name, DistributedCtx,
+};
 use lunatic_networking_api::NetworkingCtx;
 use lunatic_process_api::ProcessCtx;
 use tokio::time::{timeout, Duration};
 use wasmtime::{Caller, Linker, Trap};
 
 use lunatic_process::{
-    message::{DataMessage, Message},
+    config::ProcessConfig,
+    env::{broadcast as broadcast_to_group, Environment},
+    mailbox::Selector,
+    message::{DataMessage, Message, MessageSender, SharedBuffer},
     state::ProcessState,
-    Signal,
+    DeathReason, Signal,
 };
 
+pub type SharedBufferResources = HashMapId<Arc<SharedBuffer>>;
+
+pub trait MessageCtx {
+    fn shared_buffer_resources(&self) -> &SharedBufferResources;
+    fn shared_buffer_resources_mut(&mut self) -> &mut SharedBufferResources;
+}
+
 // Register the mailbox APIs to the linker
-pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + Send + 'static>(
-    linker: &mut Linker<T>,
-) -> Result<()> {
+pub fn register<T, E>(linker: &mut Linker<T>) -> Result<()>
+where
+    T: ProcessState
+        + ProcessCtx<T>
+        + DistributedCtx<E>
+        + NetworkingCtx
+        + MessageCtx
+        + ChannelCtx
+        + Send
+        + 'static,
+    E: Environment + 'static,
+{
     linker.func_wrap("lunatic::message", "create_data", create_data)?;
     linker.func_wrap("lunatic::message", "write_data", write_data)?;
     linker.func_wrap("lunatic::message", "read_data", read_data)?;
     linker.func_wrap("lunatic::message", "seek_data", seek_data)?;
     linker.func_wrap("lunatic::message", "get_tag", get_tag)?;
+    linker.func_wrap("lunatic::message", "get_sender", get_sender)?;
     linker.func_wrap("lunatic::message", "data_size", data_size)?;
+    linker.func_wrap(
+        "lunatic::message",
+        "get_process_died_id",
+        get_process_died_id,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "get_link_died_reason",
+        get_link_died_reason,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "get_link_died_exit_code",
+        get_link_died_exit_code,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "link_died_message_size",
+        link_died_message_size,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "get_link_died_message",
+        get_link_died_message,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "create_shared_buffer",
+        create_shared_buffer,
+    )?;
+    linker.func_wrap("lunatic::message", "shared_buffer_size", shared_buffer_size)?;
+    linker.func_wrap("lunatic::message", "read_shared_buffer", read_shared_buffer)?;
+    linker.func_wrap("lunatic::message", "drop_shared_buffer", drop_shared_buffer)?;
+    linker.func_wrap("lunatic::message", "push_shared_buffer", push_shared_buffer)?;
+    linker.func_wrap("lunatic::message", "take_shared_buffer", take_shared_buffer)?;
     linker.func_wrap("lunatic::message", "push_module", push_module)?;
     linker.func_wrap("lunatic::message", "take_module", take_module)?;
     linker.func_wrap("lunatic::message", "push_tcp_stream", push_tcp_stream)?;
     linker.func_wrap("lunatic::message", "take_tcp_stream", take_tcp_stream)?;
     linker.func_wrap("lunatic::message", "push_tls_stream", push_tls_stream)?;
     linker.func_wrap("lunatic::message", "take_tls_stream", take_tls_stream)?;
+    linker.func_wrap(
+        "lunatic::message",
+        "push_channel_sender",
+        push_channel_sender,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "take_channel_sender",
+        take_channel_sender,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "push_channel_receiver",
+        push_channel_receiver,
+    )?;
+    linker.func_wrap(
+        "lunatic::message",
+        "take_channel_receiver",
+        take_channel_receiver,
+    )?;
     linker.func_wrap("lunatic::message", "send", send)?;
+    linker.func_wrap("lunatic::message", "send_priority", send_priority)?;
+    linker.func_wrap2_async("lunatic::message", "send_to_name", send_to_name)?;
+    linker.func_wrap("lunatic::message", "broadcast", broadcast)?;
     linker.func_wrap2_async(
         "lunatic::message",
         "send_receive_skip_search",
         send_receive_skip_search,
     )?;
     linker.func_wrap3_async("lunatic::message", "receive", receive)?;
+    linker.func_wrap3_async("lunatic::message", "receive_except", receive_except)?;
+    linker.func_wrap3_async("lunatic::message", "receive_link_died", receive_link_died)?;
+    linker.func_wrap3_async("lunatic::message", "receive_from", receive_from)?;
     linker.func_wrap("lunatic::message", "push_udp_socket", push_udp_socket)?;
     linker.func_wrap("lunatic::message", "take_udp_socket", take_udp_socket)?;
 
@@ -51,7 +139,10 @@ pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + Send + 'static
 // 1. **Data message** that contains a buffer of raw `u8` data and host side resources.
 // 2. **LinkDied message**, representing a `LinkDied` signal that was turned into a message. The
 //    process can control if when a link dies the process should die too, or just receive a
-//    `LinkDied` message notifying it about the link's death.
+//    `LinkDied` message notifying it about the link's death. The reason of the link's death can
+//    be inspected with the `get_link_died_*` functions.
+// 3. **ProcessDied message**, received if a monitored process dies. Monitored processes never
+//    cause the monitoring process to die.
 //
 // All messages have a `tag` allowing for selective receives. If there are already messages in the
 // receiving queue, they will be first searched for a specific tag and the first match returned.
@@ -121,7 +212,8 @@ pub fn register<T: ProcessState + ProcessCtx<T> + NetworkingCtx + Send + 'static
 //
 // Arguments:
 // * tag - An identifier that can be used for selective receives. If value is 0, no tag is used.
-// * buffer_capacity - A hint to the message to pre-allocate a large enough buffer for writes.
+// * buffer_capacity - A hint to the message to pre-allocate a large enough buffer for writes. At
+//                     most the maximum message size of the process configuration is allocated.
 fn create_data<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     tag: i64,
@@ -131,7 +223,11 @@ fn create_data<T: ProcessState + ProcessCtx<T>>(
         0 => None,
         tag => Some(tag),
     };
-    let message = DataMessage::new(tag, buffer_capacity as usize);
+    let buffer_capacity = match caller.data().config().get_max_message_size() {
+        Some(max_message_size) => max_message_size.min(buffer_capacity as usize),
+        None => buffer_capacity as usize,
+    };
+    let message = DataMessage::new(tag, buffer_capacity);
     caller
         .data_mut()
         .message_scratch_area()
@@ -140,6 +236,10 @@ fn create_data<T: ProcessState + ProcessCtx<T>>(
 
 // Writes some data into the message buffer and returns how much data is written in bytes.
 //
+// If the write would grow the message over the maximum message size of the process
+// configuration, nothing is written and 0 is returned. Guests using `std::io::Write` will see
+// this as a `WriteZero` error.
+//
 // Traps:
 // * If any memory outside the guest heap space is referenced.
 // * If it's called without a data message being inside of the scratch area.
@@ -158,11 +258,27 @@ fn write_data<T: ProcessState + ProcessCtx<T>>(
         .data(&caller)
         .get(data_ptr as usize..(data_ptr as usize + data_len as usize))
         .or_trap("lunatic::message::write_data")?;
+    let max_message_size = caller.data().config().get_max_message_size();
     let bytes = match &mut message {
+        Message::Data(data)
+            if max_message_size.is_some_and(|max| data.size() + buffer.len() > max) =>
+        {
+            #[cfg(feature = "metrics")]
+            metrics::increment_counter!("lunatic.process.messages.data.rejected");
+            0
+        }
         Message::Data(data) => data.write(buffer).or_trap("lunatic::message::write_data")?,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     // Put message back after writing to it.
     caller.data_mut().message_scratch_area().replace(message);
@@ -192,9 +308,17 @@ fn read_data<T: ProcessState + ProcessCtx<T>>(
         .or_trap("lunatic::message::read_data")?;
     let bytes = match &mut message {
         Message::Data(data) => data.read(buffer).or_trap("lunatic::message::read_data")?,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     // Put message back after reading from it.
     caller.data_mut().message_scratch_area().replace(message);
@@ -219,9 +343,17 @@ fn seek_data<T: ProcessState + ProcessCtx<T>>(
         .or_trap("lunatic::message::seek_data")?;
     match &mut message {
         Message::Data(data) => data.seek(index as usize),
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(())
 }
@@ -242,6 +374,51 @@ fn get_tag<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<i64
     }
 }
 
+// Writes the node ID and process ID of the process that sent the message to **node_id_ptr** and
+// **process_id_ptr**. The node ID is 0 if the sender is not part of a cluster. Replies can be
+// sent to the sender without encoding its ID into the message itself.
+//
+// Returns:
+// * 0 if the sender is known.
+// * 1 if the message has no sender (it's not a data message or the sender is unknown).
+//
+// Traps:
+// * If it's called without a message being inside of the scratch area.
+// * If any memory outside the guest heap space is referenced.
+fn get_sender<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    node_id_ptr: u32,
+    process_id_ptr: u32,
+) -> Result<u32, Trap> {
+    let sender = caller
+        .data_mut()
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::get_sender")?
+        .sender();
+    let sender = match sender {
+        Some(sender) => sender,
+        None => return Ok(1),
+    };
+
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(
+            &mut caller,
+            node_id_ptr as usize,
+            &sender.node_id.to_le_bytes(),
+        )
+        .or_trap("lunatic::message::get_sender")?;
+    memory
+        .write(
+            &mut caller,
+            process_id_ptr as usize,
+            &sender.process_id.to_le_bytes(),
+        )
+        .or_trap("lunatic::message::get_sender")?;
+    Ok(0)
+}
+
 // Returns the size in bytes of the message buffer.
 //
 // Traps:
@@ -254,14 +431,440 @@ fn data_size<T: ProcessState + ProcessCtx<T>>(mut caller: Caller<T>) -> Result<u
         .or_trap("lunatic::message::data_size")?;
     let bytes = match message {
         Message::Data(data) => data.size(),
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
 
     Ok(bytes as u64)
 }
 
+// Returns the id of the monitored process that died. The process id is also used as the monitor
+// reference.
+//
+// Traps:
+// * If it's called without a `ProcessDied` message being inside of the scratch area.
+fn get_process_died_id<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+) -> Result<u64, Trap> {
+    let message = caller
+        .data_mut()
+        .message_scratch_area()
+        .as_ref()
+        .or_trap("lunatic::message::get_process_died_id")?;
+    match message {
+        Message::ProcessDied(process_id) => Ok(*process_id),
+        _ => Err(Trap::new("Expected `Message::ProcessDied` in scratch area")),
+    }
+}
+
+// Returns the reason of death contained in the `LinkDied` message:
+// * 1 - Failure (e.g. a native process failed, the message can be read with
+//       `get_link_died_message`)
+// * 2 - NoProcess (the process didn't exist when the link was established)
+// * 3 - Kill (the process was killed or died because of a link)
+// * 4 - Trap (the message can be read with `get_link_died_message`)
+// * 5 - OutOfFuel
+// * 6 - MemoryLimit
+// * 7 - ExitCode (the code can be read with `get_link_died_exit_code`)
+// * 8 - SpawnError (the message can be read with `get_link_died_message`)
+// * 9 - MailboxOverflow
+// * 10 - Exit (the reason can be read with `get_link_died_message`)
+//
+// Traps:
+// * If it's called without a `LinkDied` message being inside of the scratch area.
+fn get_link_died_reason<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+) -> Result<u32, Trap> {
+    let reason = link_died_reason(caller.data_mut(), "lunatic::message::get_link_died_reason")?;
+    let code = match reason {
+        DeathReason::Normal => 0,
+        DeathReason::Failure(_) => 1,
+        DeathReason::NoProcess => 2,
+        DeathReason::Kill => 3,
+        DeathReason::Trap(_) => 4,
+        DeathReason::OutOfFuel => 5,
+        DeathReason::MemoryLimit => 6,
+        DeathReason::ExitCode(_) => 7,
+        DeathReason::SpawnError(_) => 8,
+        DeathReason::MailboxOverflow => 9,
+        DeathReason::Exit(_) => 10,
+    };
+    Ok(code)
+}
+
+// Returns the exit code of the linked process if it exited with one, otherwise 0.
+//
+// Traps:
+// * If it's called without a `LinkDied` message being inside of the scratch area.
+fn get_link_died_exit_code<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+) -> Result<i32, Trap> {
+    let reason = link_died_reason(
+        caller.data_mut(),
+        "lunatic::message::get_link_died_exit_code",
+    )?;
+    match reason {
+        DeathReason::ExitCode(code) => Ok(*code),
+        _ => Ok(0),
+    }
+}
+
+// Returns the size in bytes of the message describing the reason of the linked process' death.
+// If the reason doesn't contain a message, 0 is returned.
+//
+// Traps:
+// * If it's called without a `LinkDied` message being inside of the scratch area.
+fn link_died_message_size<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+) -> Result<u32, Trap> {
+    let reason = link_died_reason(
+        caller.data_mut(),
+        "lunatic::message::link_died_message_size",
+    )?;
+    Ok(reason.message().map(|message| message.len()).unwrap_or(0) as u32)
+}
+
+// Writes the message describing the reason of the linked process' death to the guest memory.
+// `lunatic::message::link_died_message_size` can be used to get the message size.
+//
+// Traps:
+// * If it's called without a `LinkDied` message being inside of the scratch area.
+// * If any memory outside the guest heap space is referenced.
+fn get_link_died_message<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    message_ptr: u32,
+) -> Result<(), Trap> {
+    let memory = get_memory(&mut caller)?;
+    let message = link_died_reason(caller.data_mut(), "lunatic::message::get_link_died_message")?
+        .message()
+        .unwrap_or_default()
+        .to_owned();
+    memory
+        .write(&mut caller, message_ptr as usize, message.as_bytes())
+        .or_trap("lunatic::message::get_link_died_message")?;
+    Ok(())
+}
+
+fn link_died_reason<'a, T: ProcessState + ProcessCtx<T>>(
+    state: &'a mut T,
+    info: &str,
+) -> Result<&'a DeathReason, Trap> {
+    match state.message_scratch_area().as_ref().or_trap(info)? {
+        Message::LinkDied(_, reason) => Ok(reason),
+        _ => Err(Trap::new(format!(
+            "Expected `Message::LinkDied` in scratch area ({})",
+            info
+        ))),
+    }
+}
+
+// Copies the guest memory region into a new immutable shared buffer and returns its ID.
+//
+// Shared buffers are reference counted. Pushing one to a message doesn't copy the content,
+// so it's the preferred way of sending large payloads to other local processes. The data is
+// copied only once here, and then again only for the parts that receivers read out.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+fn create_shared_buffer<T: MessageCtx>(
+    mut caller: Caller<T>,
+    data_ptr: u32,
+    data_len: u32,
+) -> Result<u64, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let data = memory
+        .data(&caller)
+        .get(data_ptr as usize..(data_ptr as usize + data_len as usize))
+        .or_trap("lunatic::message::create_shared_buffer")?
+        .to_vec();
+    let buffer = Arc::new(SharedBuffer::new(data));
+    Ok(caller.data_mut().shared_buffer_resources_mut().add(buffer))
+}
+
+// Returns the size of the shared buffer in bytes.
+//
+// Traps:
+// * If the shared buffer ID doesn't exist.
+fn shared_buffer_size<T: MessageCtx>(caller: Caller<T>, buffer_id: u64) -> Result<u64, Trap> {
+    let buffer = caller
+        .data()
+        .shared_buffer_resources()
+        .get(buffer_id)
+        .or_trap("lunatic::message::shared_buffer_size")?;
+    Ok(buffer.len() as u64)
+}
+
+// Reads a part of the shared buffer, starting at `offset`, into guest memory and returns how
+// much data is read in bytes. Returns 0 if the offset is at or past the end of the buffer.
+//
+// Traps:
+// * If the shared buffer ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+fn read_shared_buffer<T: MessageCtx>(
+    mut caller: Caller<T>,
+    buffer_id: u64,
+    offset: u64,
+    data_ptr: u32,
+    data_len: u32,
+) -> Result<u32, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory, state) = memory.data_and_store_mut(&mut caller);
+    let buffer = state
+        .shared_buffer_resources()
+        .get(buffer_id)
+        .or_trap("lunatic::message::read_shared_buffer")?;
+    let target = memory
+        .get_mut(data_ptr as usize..(data_ptr as usize + data_len as usize))
+        .or_trap("lunatic::message::read_shared_buffer")?;
+    let source = buffer.get(offset as usize..).unwrap_or_default();
+    let bytes = source.len().min(target.len());
+    target[..bytes].copy_from_slice(&source[..bytes]);
+    Ok(bytes as u32)
+}
+
+// Drops the shared buffer resource. The content is freed once no other process or message
+// holds onto it anymore.
+//
+// Traps:
+// * If the shared buffer ID doesn't exist.
+fn drop_shared_buffer<T: MessageCtx>(mut caller: Caller<T>, buffer_id: u64) -> Result<(), Trap> {
+    caller
+        .data_mut()
+        .shared_buffer_resources_mut()
+        .remove(buffer_id)
+        .or_trap("lunatic::message::drop_shared_buffer")?;
+    Ok(())
+}
+
+// Adds a shared buffer resource to the message that is currently in the scratch area and
+// returns the new location of it. The buffer stays available to the current process.
+//
+// Shared buffers don't count towards the maximum message size of the process configuration.
+// Their content is never copied into the message and they can't be sent to other nodes, so they
+// don't add to the size of data that receivers need to accept.
+//
+// Traps:
+// * If shared buffer ID doesn't exist
+// * If no data message is in the scratch area.
+fn push_shared_buffer<T: ProcessState + ProcessCtx<T> + MessageCtx>(
+    mut caller: Caller<T>,
+    buffer_id: u64,
+) -> Result<u64, Trap> {
+    let data = caller.data_mut();
+    let buffer = data
+        .shared_buffer_resources()
+        .get(buffer_id)
+        .or_trap("lunatic::message::push_shared_buffer")?
+        .clone();
+    let message = data
+        .message_scratch_area()
+        .as_mut()
+        .or_trap("lunatic::message::push_shared_buffer")?;
+    let index = match message {
+        Message::Data(data) => data.add_resource(buffer) as u64,
+        Message::LinkDied(_, _) => {
+            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
+    };
+    Ok(index)
+}
+
+// Takes the shared buffer from the message that is currently in the scratch area by index,
+// puts it into the process' resources and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a shared buffer).
+// * If no data message is in the scratch area.
+fn take_shared_buffer<T: ProcessState + ProcessCtx<T> + MessageCtx>(
+    mut caller: Caller<T>,
+    index: u64,
+) -> Result<u64, Trap> {
+    let message = caller
+        .data_mut()
+        .message_scratch_area()
+        .as_mut()
+        .or_trap("lunatic::message::take_shared_buffer")?;
+    let buffer = match message {
+        Message::Data(data) => data
+            .take_shared_buffer(index as usize)
+            .or_trap("lunatic::message::take_shared_buffer")?,
+        Message::LinkDied(_, _) => {
+            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
+    };
+    Ok(caller.data_mut().shared_buffer_resources_mut().add(buffer))
+}
+
+// Adds a channel sender resource to the message that is currently in the scratch area and
+// returns the new location of it. This will remove the channel sender from the current
+// process' resources.
+//
+// Traps:
+// * If channel sender ID doesn't exist
+// * If no data message is in the scratch area.
+fn push_channel_sender<T: ProcessState + ProcessCtx<T> + ChannelCtx>(
+    mut caller: Caller<T>,
+    sender_id: u64,
+) -> Result<u64, Trap> {
+    let data = caller.data_mut();
+    let sender = data
+        .channel_sender_resources_mut()
+        .remove(sender_id)
+        .or_trap("lunatic::message::push_channel_sender")?;
+    let message = data
+        .message_scratch_area()
+        .as_mut()
+        .or_trap("lunatic::message::push_channel_sender")?;
+    let index = match message {
+        Message::Data(data) => data.add_resource(sender) as u64,
+        Message::LinkDied(_, _) => {
+            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
+    };
+    Ok(index)
+}
+
+// Takes the channel sender from the message that is currently in the scratch area by index,
+// puts it into the process' resources and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a channel sender).
+// * If no data message is in the scratch area.
+fn take_channel_sender<T: ProcessState + ProcessCtx<T> + ChannelCtx>(
+    mut caller: Caller<T>,
+    index: u64,
+) -> Result<u64, Trap> {
+    let message = caller
+        .data_mut()
+        .message_scratch_area()
+        .as_mut()
+        .or_trap("lunatic::message::take_channel_sender")?;
+    let sender = match message {
+        Message::Data(data) => data
+            .take_channel_sender(index as usize)
+            .or_trap("lunatic::message::take_channel_sender")?,
+        Message::LinkDied(_, _) => {
+            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
+    };
+    Ok(caller.data_mut().channel_sender_resources_mut().add(sender))
+}
+
+// Adds a channel receiver resource to the message that is currently in the scratch area and
+// returns the new location of it. This will remove the channel receiver from the current
+// process' resources.
+//
+// Traps:
+// * If channel receiver ID doesn't exist
+// * If no data message is in the scratch area.
+fn push_channel_receiver<T: ProcessState + ProcessCtx<T> + ChannelCtx>(
+    mut caller: Caller<T>,
+    receiver_id: u64,
+) -> Result<u64, Trap> {
+    let data = caller.data_mut();
+    let receiver = data
+        .channel_receiver_resources_mut()
+        .remove(receiver_id)
+        .or_trap("lunatic::message::push_channel_receiver")?;
+    let message = data
+        .message_scratch_area()
+        .as_mut()
+        .or_trap("lunatic::message::push_channel_receiver")?;
+    let index = match message {
+        Message::Data(data) => data.add_resource(receiver) as u64,
+        Message::LinkDied(_, _) => {
+            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
+    };
+    Ok(index)
+}
+
+// Takes the channel receiver from the message that is currently in the scratch area by index,
+// puts it into the process' resources and returns the resource ID.
+//
+// Traps:
+// * If index ID doesn't exist or matches the wrong resource (not a channel receiver).
+// * If no data message is in the scratch area.
+fn take_channel_receiver<T: ProcessState + ProcessCtx<T> + ChannelCtx>(
+    mut caller: Caller<T>,
+    index: u64,
+) -> Result<u64, Trap> {
+    let message = caller
+        .data_mut()
+        .message_scratch_area()
+        .as_mut()
+        .or_trap("lunatic::message::take_channel_receiver")?;
+    let receiver = match message {
+        Message::Data(data) => data
+            .take_channel_receiver(index as usize)
+            .or_trap("lunatic::message::take_channel_receiver")?,
+        Message::LinkDied(_, _) => {
+            return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
+        }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
+    };
+    Ok(caller
+        .data_mut()
+        .channel_receiver_resources_mut()
+        .add(receiver))
+}
+
 // Adds a module resource to the message that is currently in the scratch area and returns
 // the new location of it.
 //
@@ -285,9 +888,17 @@ fn push_module<T: ProcessState + ProcessCtx<T> + NetworkingCtx + 'static>(
         .or_trap("lunatic::message::push_module")?;
     let index = match message {
         Message::Data(data) => data.add_resource(module) as u64,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(index)
 }
@@ -311,9 +922,17 @@ fn take_module<T: ProcessState + ProcessCtx<T> + NetworkingCtx + 'static>(
         Message::Data(data) => data
             .take_module(index as usize)
             .or_trap("lunatic::message::take_module")?,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(caller.data_mut().module_resources_mut().add(module))
 }
@@ -340,9 +959,17 @@ fn push_tcp_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
         .or_trap("lunatic::message::push_tcp_stream")?;
     let index = match message {
         Message::Data(data) => data.add_resource(stream) as u64,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(index)
 }
@@ -366,9 +993,17 @@ fn take_tcp_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
         Message::Data(data) => data
             .take_tcp_stream(index as usize)
             .or_trap("lunatic::message::take_tcp_stream")?,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(caller.data_mut().tcp_stream_resources_mut().add(tcp_stream))
 }
@@ -396,9 +1031,17 @@ fn push_tls_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
         .or_trap("lunatic::message::push_tls_stream")?;
     let index = match message {
         Message::Data(data) => data.add_resource(stream) as u64,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(index)
 }
@@ -422,9 +1065,17 @@ fn take_tls_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
         Message::Data(data) => data
             .take_tls_stream(index as usize)
             .or_trap("lunatic::message::take_tls_stream")?,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(caller.data_mut().tls_stream_resources_mut().add(tls_stream))
 }
@@ -433,26 +1084,268 @@ fn take_tls_stream<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
 //
 // There are no guarantees that the message will be received.
 //
+// If the receiving mailbox is full and uses the backpressure overflow policy, the message is not
+// sent and stays in the scratch area, so that sending can be retried later.
+//
+// Returns:
+// * 0 if the message was sent.
+// * 1 if the receiving mailbox is full.
+//
 // Traps:
 // * If the process ID doesn't exist.
 // * If it's called before creating the next message.
-fn send<T: ProcessState + ProcessCtx<T>>(
+fn send<T: ProcessState + ProcessCtx<T>>(caller: Caller<T>, process_id: u64) -> Result<u32, Trap> {
+    send_message(caller, process_id, false)
+}
+
+// Sends the message to a process with high priority.
+//
+// Priority messages are delivered before all regular messages waiting in the receiving mailbox.
+// They are queued separately from regular messages, but their queue is bounded by the same
+// capacity and overflow policy. They should be reserved for system messages, like health checks
+// or supervisor pings.
+//
+// There are no guarantees that the message will be received.
+//
+// If the priority queue of the receiving mailbox is full and uses the backpressure overflow
+// policy, the message is not sent and stays in the scratch area, so that sending can be retried
+// later.
+//
+// Returns:
+// * 0 if the message was sent.
+// * 1 if the priority queue of the receiving mailbox is full.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If it's called before creating the next message.
+fn send_priority<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+) -> Result<u32, Trap> {
+    send_message(caller, process_id, true)
+}
+
+fn send_message<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     process_id: u64,
+    priority: bool,
 ) -> Result<u32, Trap> {
-    let message = caller
+    let mut message = caller
         .data_mut()
         .message_scratch_area()
         .take()
         .or_trap("lunatic::message::send::no_message")?;
+    if let Message::Data(data) = &mut message {
+        data.priority = priority;
+    }
+    let message = stamp_sender(message, caller.data());
 
     if let Some(process) = caller.data_mut().environment().get_process(process_id) {
+        if process.is_full(priority) {
+            *caller.data_mut().message_scratch_area() = Some(message);
+            return Ok(1);
+        }
         process.send(Signal::Message(message));
     }
 
     Ok(0)
 }
 
+// Sends the message to the process registered under `name`.
+//
+// The name is resolved and the message is delivered inside of one host call, so that the name
+// can't be registered to another process in between. Names are resolved like with
+// `lunatic::registry::get`, if the node is part of a cluster and the name isn't registered on
+// this node, it's looked up in the cluster-wide registry. If the name is registered to a process
+// on another node, the message is sent to that node.
+//
+// Returns:
+// * 0    If the message was sent.
+// * 1    If the receiving mailbox is full.
+// * 2    If no process is registered under the name or the registered process doesn't exist.
+// * 3    If the remote node doesn't accept the message, because it's too large or contains
+//        resources that can't be sent to other nodes.
+// * 9027 If a node connection error occurred.
+//
+// If 1 or 2 is returned for a process on this node, the message stays in the scratch area. A
+// message for a process on another node is serialized before the node answers and is consumed
+// by the call, regardless of the returned value.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+// * If the name is not a valid utf8 string.
+// * If it's called before creating the next message.
+// * If the control server can't be reached.
+fn send_to_name<T, E>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send + 'static,
+    E: Environment,
+{
+    Box::new(async move {
+        let memory = get_memory(&mut caller)?;
+        let name = memory
+            .data(&caller)
+            .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+            .or_trap("lunatic::message::send_to_name")?;
+        let name = std::str::from_utf8(name)
+            .or_trap("lunatic::message::send_to_name")?
+            .to_owned();
+        let message = caller
+            .data_mut()
+            .message_scratch_area()
+            .take()
+            .or_trap("lunatic::message::send_to_name::no_message")?;
+        let message = stamp_sender(message, caller.data());
+
+        let environment = caller.data().environment();
+        let registration = {
+            // The registry entry stays locked while the message is delivered locally.
+            let registration = environment.registry().get(&name);
+            match registration {
+                Some(registration) if registration.node_id == caller.data().node_id() => {
+                    return Ok(send_to_local_process(
+                        &mut caller,
+                        registration.process_id,
+                        message,
+                    ));
+                }
+                Some(registration) => Some((registration.node_id, registration.process_id)),
+                None => None,
+            }
+        };
+        let registration = match registration {
+            Some(registration) => Some(registration),
+            None => lookup_name(caller.data(), &name)
+                .await
+                .or_trap("lunatic::message::send_to_name")?,
+        };
+        let (node_id, process_id) = match registration {
+            Some(registration) => registration,
+            None => {
+                caller.data_mut().message_scratch_area().replace(message);
+                return Ok(2);
+            }
+        };
+        if node_id == caller.data().node_id() {
+            return Ok(send_to_local_process(&mut caller, process_id, message));
+        }
+
+        let (tag, buffer, resources) = match message {
+            Message::Data(DataMessage {
+                tag,
+                buffer,
+                resources,
+                ..
+            }) => (tag, buffer, resources),
+            _ => return Err(Trap::new("Only Message::Data can be sent across nodes.")),
+        };
+        let resources = match serialize_resources::<T>(resources) {
+            Ok(resources) => resources,
+            Err(_) => return Ok(3),
+        };
+        let state = caller.data();
+        let distributed = state
+            .distributed()
+            .or_trap("lunatic::message::send_to_name")?;
+        let node_client = distributed.node_client.clone();
+        let sender = MessageSender {
+            node_id: distributed.node_id(),
+            process_id: state.id(),
+        };
+        let environment_id = state.environment_id();
+        match node_client
+            .message_process(
+                node_id,
+                environment_id,
+                process_id,
+                tag,
+                buffer,
+                resources,
+                sender,
+            )
+            .await
+        {
+            Ok(_) => Ok(0),
+            Err(error) => match error {
+                ClientError::ProcessNotFound => Ok(2),
+                ClientError::UnsupportedResource(_)
+                | ClientError::ModuleNotFound
+                | ClientError::MessageTooLarge => Ok(3),
+                ClientError::NodeNotFound | ClientError::Connection(_) => Ok(9027),
+                ClientError::Unexpected(cause) => Err(Trap::new(cause)),
+            },
+        }
+    })
+}
+
+// Delivers the message to a process on this node and returns the `send_to_name` code. If the
+// message can't be delivered, it's put back into the scratch area.
+fn send_to_local_process<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    process_id: u64,
+    message: Message,
+) -> u32 {
+    let code = match caller.data().environment().get_process(process_id) {
+        Some(process) if process.is_full(message.is_priority()) => 1,
+        Some(process) => {
+            process.send(Signal::Message(message));
+            return 0;
+        }
+        None => 2,
+    };
+    caller.data_mut().message_scratch_area().replace(message);
+    code
+}
+
+// Sends a copy of the message to every process in the group `name` and returns the number of
+// processes it was sent to.
+//
+// Groups are managed with the `lunatic::registry::group_*` functions. The data of the message is
+// copied, shared buffers attached to it are shared by all copies. Members with a full mailbox
+// that uses the backpressure overflow policy are skipped.
+//
+// Traps:
+// * If the message carries resources other than shared buffers, they can only have one owner.
+// * If any memory outside the guest heap space is referenced.
+// * If the name is not a valid utf8 string.
+// * If it's called without a data message being inside of the scratch area.
+fn broadcast<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+) -> Result<u32, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let name = memory
+        .data(&caller)
+        .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+        .or_trap("lunatic::message::broadcast")?;
+    let name = std::str::from_utf8(name)
+        .or_trap("lunatic::message::broadcast")?
+        .to_owned();
+    let message = caller
+        .data_mut()
+        .message_scratch_area()
+        .take()
+        .or_trap("lunatic::message::broadcast::no_message")?;
+    let message = match stamp_sender(message, caller.data()) {
+        Message::Data(message) => message,
+        _ => {
+            return Err(Trap::new(
+                "lunatic::message::broadcast: Only data messages can be broadcast",
+            ))
+        }
+    };
+
+    let environment = caller.data().environment();
+    broadcast_to_group(environment.as_ref(), &name, &message).or_trap(
+        "lunatic::message::broadcast: Only shared buffers can be sent to multiple processes",
+    )
+}
+
 // Sends the message to a process and waits for a reply, but doesn't look through existing
 // messages in the mailbox queue while waiting. This is an optimization that only makes sense
 // with tagged messages. In a request/reply scenario we can tag the request message with an
@@ -482,6 +1375,7 @@ fn send_receive_skip_search<T: ProcessState + ProcessCtx<T> + Send>(
             .message_scratch_area()
             .take()
             .or_trap("lunatic::message::send_receive_skip_search")?;
+        let message = stamp_sender(message, caller.data());
         let mut _tags = [0; 1];
         let tags = if let Some(tag) = message.tag() {
             _tags = [tag];
@@ -526,6 +1420,8 @@ fn send_receive_skip_search<T: ProcessState + ProcessCtx<T> + Send>(
 // Returns:
 // * 0    if it's a data message.
 // * 1    if it's a signal turned into a message.
+// * 2    if it's a monitored process' death turned into a message.
+// * 3    if it's a shutdown request, the process should finish before the shutdown timeout.
 // * 9027 if call timed out.
 //
 // Traps:
@@ -537,43 +1433,160 @@ fn receive<T: ProcessState + ProcessCtx<T> + Send>(
     timeout_duration: u64,
 ) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
     Box::new(async move {
-        let tags = if tag_len > 0 {
-            let memory = get_memory(&mut caller)?;
-            let buffer = memory
-                .data(&caller)
-                .get(tag_ptr as usize..(tag_ptr + tag_len * 8) as usize)
-                .or_trap("lunatic::message::receive")?;
-
-            // Gether all tags
-            let tags: Vec<i64> = buffer
-                .chunks_exact(8)
-                .map(|chunk| i64::from_le_bytes(chunk.try_into().expect("works")))
-                .collect();
-            Some(tags)
-        } else {
-            None
+        let tags = read_tags(&mut caller, tag_ptr, tag_len, "lunatic::message::receive")?;
+        let selector = match tags {
+            Some(tags) => Selector::Tags(tags),
+            None => Selector::Any,
         };
+        receive_selected(caller, selector, timeout_duration).await
+    })
+}
 
-        let pop = caller.data_mut().mailbox().pop(tags.as_deref());
-        if let Ok(message) = match timeout_duration {
-            // Without timeout
-            u64::MAX => Ok(pop.await),
-            // With timeout
-            t => timeout(Duration::from_millis(t), pop).await,
-        } {
-            let result = match message {
-                Message::Data(_) => 0,
-                Message::LinkDied(_) => 1,
-            };
-            // Put the message into the scratch area
-            caller.data_mut().message_scratch_area().replace(message);
-            Ok(result)
-        } else {
-            Ok(9027)
-        }
+// Takes the next data message that is not tagged with any of the supplied tags out of the queue
+// or blocks until one is received.
+//
+// **tag_ptr** points to an array of **tag_len** i64 values encoded as little endian values. If
+// **tag_len** is 0, any data message is matched.
+//
+// Timeout and return values are the same as in `lunatic::message::receive`, but only data
+// messages can be returned.
+//
+// Traps:
+// * If **tag_ptr + (ciovec_array_len * 8) is outside the memory
+fn receive_except<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<T>,
+    tag_ptr: u32,
+    tag_len: u32,
+    timeout_duration: u64,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        let tags = read_tags(
+            &mut caller,
+            tag_ptr,
+            tag_len,
+            "lunatic::message::receive_except",
+        )?;
+        let selector = Selector::DataExceptTags(tags.unwrap_or_default());
+        receive_selected(caller, selector, timeout_duration).await
+    })
+}
+
+// Takes the next `LinkDied` message out of the queue or blocks until one is received.
+//
+// If **tag_len** is a value greater than 0 it will only match `LinkDied` messages with any of the
+// supplied link tags. **tag_ptr** points to an array containing i64 value encoded as little
+// endian values.
+//
+// Timeout and return values are the same as in `lunatic::message::receive`, but only `LinkDied`
+// messages can be returned.
+//
+// Traps:
+// * If **tag_ptr + (ciovec_array_len * 8) is outside the memory
+fn receive_link_died<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<T>,
+    tag_ptr: u32,
+    tag_len: u32,
+    timeout_duration: u64,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        let tags = read_tags(
+            &mut caller,
+            tag_ptr,
+            tag_len,
+            "lunatic::message::receive_link_died",
+        )?;
+        receive_selected(caller, Selector::LinkDied(tags), timeout_duration).await
+    })
+}
+
+// Takes the next data message sent by the process **process_id** on the node **node_id** out of the
+// queue or blocks until one is received. Processes on the local node can be selected with the
+// node ID returned by `lunatic::distributed::node_id`, that is 0 if the node is not part of a
+// cluster.
+//
+// Timeout and return values are the same as in `lunatic::message::receive`, but only data
+// messages can be returned.
+fn receive_from<T: ProcessState + ProcessCtx<T> + Send>(
+    caller: Caller<T>,
+    node_id: u64,
+    process_id: u64,
+    timeout_duration: u64,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        let sender = MessageSender {
+            node_id,
+            process_id,
+        };
+        receive_selected(caller, Selector::Sender(sender), timeout_duration).await
     })
 }
 
+// Reads **tag_len** little endian i64 values from **tag_ptr**, returns `None` if **tag_len** is 0.
+fn read_tags<T: ProcessState + ProcessCtx<T>>(
+    caller: &mut Caller<T>,
+    tag_ptr: u32,
+    tag_len: u32,
+    trap_name: &str,
+) -> Result<Option<Vec<i64>>, Trap> {
+    if tag_len == 0 {
+        return Ok(None);
+    }
+    let memory = get_memory(caller)?;
+    let buffer = memory
+        .data(&caller)
+        .get(tag_ptr as usize..(tag_ptr + tag_len * 8) as usize)
+        .or_trap(trap_name)?;
+
+    // Gether all tags
+    let tags: Vec<i64> = buffer
+        .chunks_exact(8)
+        .map(|chunk| i64::from_le_bytes(chunk.try_into().expect("works")))
+        .collect();
+    Ok(Some(tags))
+}
+
+// Waits on the next message matched by the selector and puts it into the scratch area.
+async fn receive_selected<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<'_, T>,
+    selector: Selector,
+    timeout_duration: u64,
+) -> Result<u32, Trap> {
+    // Record fuel usage before waiting, so that it can be reported by `lunatic::process::info`
+    let fuel_consumed = caller.fuel_consumed().unwrap_or(0);
+    caller.data().usage().set_fuel_consumed(fuel_consumed);
+
+    let pop = caller.data_mut().mailbox().pop_selected(selector);
+    if let Ok(message) = match timeout_duration {
+        // Without timeout
+        u64::MAX => Ok(pop.await),
+        // With timeout
+        t => timeout(Duration::from_millis(t), pop).await,
+    } {
+        let result = match message {
+            Message::Data(_) => 0,
+            Message::LinkDied(_, _) => 1,
+            Message::ProcessDied(_) => 2,
+            Message::Shutdown => 3,
+        };
+        // Put the message into the scratch area
+        caller.data_mut().message_scratch_area().replace(message);
+        Ok(result)
+    } else {
+        Ok(9027)
+    }
+}
+
+// Records the current process as sender of data messages.
+fn stamp_sender<T: ProcessState + ProcessCtx<T>>(mut message: Message, state: &T) -> Message {
+    if let Message::Data(data) = &mut message {
+        data.sender = Some(MessageSender {
+            node_id: state.node_id(),
+            process_id: state.id(),
+        });
+    }
+    message
+}
+
 // Adds a udp socket resource to the message that is currently in the scratch area and returns
 // the new location of it. This will remove the socket from the current process' resources.
 //
@@ -595,9 +1608,17 @@ fn push_udp_socket<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
         .or_trap("lunatic::message::push_udp_socket")?;
     let index = match message {
         Message::Data(data) => data.add_resource(socket) as u64,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(index)
 }
@@ -621,9 +1642,17 @@ fn take_udp_socket<T: ProcessState + ProcessCtx<T> + NetworkingCtx>(
         Message::Data(data) => data
             .take_udp_socket(index as usize)
             .or_trap("lunatic::message::take_udp_socket")?,
-        Message::LinkDied(_) => {
+        Message::LinkDied(_, _) => {
             return Err(Trap::new("Unexpected `Message::LinkDied` in scratch area"))
         }
+        Message::ProcessDied(_) => {
+            return Err(Trap::new(
+                "Unexpected `Message::ProcessDied` in scratch area",
+            ))
+        }
+        Message::Shutdown => {
+            return Err(Trap::new("Unexpected `Message::Shutdown` in scratch area"))
+        }
     };
     Ok(caller.data_mut().udp_resources_mut().add(udp_socket))
 }
@@ -10,8 +10,9 @@ use hash_map_id::HashMapId;
 use lunatic_common_api::{get_memory, IntoTrap};
 use lunatic_error_api::ErrorCtx;
 use lunatic_process::{
-    config::ProcessConfig,
-    env::Environment,
+    config::{MailboxOverflow, ProcessConfig},
+    env::{processes_info, Environment},
+    info::ProcessInfo,
     mailbox::MessageMailbox,
     message::Message,
     runtimes::{wasmtime::WasmtimeCompiledModule, RawWasm},
@@ -31,6 +32,12 @@ pub trait ProcessConfigCtx {
     fn set_can_create_configs(&mut self, can: bool);
     fn can_spawn_processes(&self) -> bool;
     fn set_can_spawn_processes(&mut self, can: bool);
+    fn can_list_processes(&self) -> bool;
+    fn set_can_list_processes(&mut self, can: bool);
+    fn can_access_environments(&self) -> bool;
+    fn set_can_access_environments(&mut self, can: bool);
+    fn can_create_environments(&self) -> bool;
+    fn set_can_create_environments(&mut self, can: bool);
 }
 
 pub trait ProcessCtx<S: ProcessState> {
@@ -39,6 +46,19 @@ pub trait ProcessCtx<S: ProcessState> {
     fn module_resources(&self) -> &ModuleResources<S>;
     fn module_resources_mut(&mut self) -> &mut ModuleResources<S>;
     fn environment(&self) -> Arc<dyn Environment>;
+    // Id of the node this process is running on, 0 if the node is not part of a cluster.
+    fn node_id(&self) -> u64;
+    // Creates a new environment on this node and returns its ID. Returns `None` if the
+    // environment of this process doesn't know about other environments.
+    fn create_environment(&self, max_processes: Option<usize>) -> Option<u64>;
+    // Creates the state of a new process inside the environment `environment_id`. Returns `None`
+    // if the environment doesn't exist on this node.
+    fn new_state_in_environment(
+        &self,
+        module: Arc<WasmtimeCompiledModule<S>>,
+        config: Arc<S::Config>,
+        environment_id: u64,
+    ) -> Result<Option<S>>;
 }
 
 // Register the process APIs to the linker
@@ -125,6 +145,36 @@ where
         "config_get_max_fuel",
         config_get_max_fuel,
     )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_mailbox_capacity",
+        config_set_mailbox_capacity,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_mailbox_capacity",
+        config_get_mailbox_capacity,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_mailbox_overflow",
+        config_set_mailbox_overflow,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_mailbox_overflow",
+        config_get_mailbox_overflow,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_max_message_size",
+        config_set_max_message_size,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_get_max_message_size",
+        config_get_max_message_size,
+    )?;
     linker.func_wrap(
         "lunatic::process",
         "config_can_compile_modules",
@@ -155,17 +205,61 @@ where
         "config_set_can_spawn_processes",
         config_set_can_spawn_processes,
     )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_can_list_processes",
+        config_can_list_processes,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_can_list_processes",
+        config_set_can_list_processes,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_can_access_environments",
+        config_can_access_environments,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_can_access_environments",
+        config_set_can_access_environments,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_can_create_environments",
+        config_can_create_environments,
+    )?;
+    linker.func_wrap(
+        "lunatic::process",
+        "config_set_can_create_environments",
+        config_set_can_create_environments,
+    )?;
+    linker.func_wrap("lunatic::process", "create_environment", create_environment)?;
 
     linker.func_wrap8_async("lunatic::process", "spawn", spawn)?;
+    linker.func_wrap9_async(
+        "lunatic::process",
+        "spawn_in_environment",
+        spawn_in_environment,
+    )?;
 
     linker.func_wrap1_async("lunatic::process", "sleep_ms", sleep_ms)?;
     linker.func_wrap("lunatic::process", "die_when_link_dies", die_when_link_dies)?;
 
     linker.func_wrap("lunatic::process", "process_id", process_id)?;
     linker.func_wrap("lunatic::process", "environment_id", environment_id)?;
+    linker.func_wrap2_async("lunatic::process", "info", info)?;
+    linker.func_wrap("lunatic::process", "list", list)?;
+    linker.func_wrap2_async("lunatic::process", "list_info", list_info)?;
     linker.func_wrap("lunatic::process", "link", link)?;
     linker.func_wrap("lunatic::process", "unlink", unlink)?;
     linker.func_wrap("lunatic::process", "kill", kill)?;
+    linker.func_wrap("lunatic::process", "shutdown", shutdown)?;
+    linker.func_wrap2_async("lunatic::process", "exit", exit)?;
+    linker.func_wrap("lunatic::process", "hibernate", hibernate)?;
+    linker.func_wrap("lunatic::process", "monitor", monitor)?;
+    linker.func_wrap("lunatic::process", "demonitor", demonitor)?;
 
     Ok(())
 }
@@ -381,6 +475,149 @@ fn config_get_max_fuel<T: ProcessState + ProcessCtx<T>>(
     }
 }
 
+// Sets the maximum number of messages that can wait in the mailbox of processes spawned from this
+// configuration.
+//
+// A value of 0 indicates an unbounded mailbox.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_mailbox_capacity<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    capacity: u64,
+) -> Result<(), Trap> {
+    let capacity = match capacity {
+        0 => None,
+        capacity => Some(capacity as usize),
+    };
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_mailbox_capacity: Config ID doesn't exist")?
+        .set_mailbox_capacity(capacity);
+    Ok(())
+}
+
+// Returns the mailbox capacity of a configuration.
+//
+// A value of 0 indicates an unbounded mailbox.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_mailbox_capacity<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u64, Trap> {
+    let capacity = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_mailbox_capacity: Config ID doesn't exist")?
+        .get_mailbox_capacity();
+    match capacity {
+        None => Ok(0),
+        Some(capacity) => Ok(capacity as u64),
+    }
+}
+
+// Sets what happens when a message arrives at a full mailbox of processes spawned from this
+// configuration.
+//
+// Overflow policies:
+// * 0 - drop the new message (default)
+// * 1 - drop the oldest message in the mailbox
+// * 2 - kill the receiving process
+// * 3 - don't send the message and report backpressure to the sender
+//
+// Traps:
+// * If the config ID doesn't exist.
+// * If the overflow policy is unknown.
+fn config_set_mailbox_overflow<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    overflow: u32,
+) -> Result<(), Trap> {
+    let overflow = MailboxOverflow::try_from(overflow)
+        .or_trap("lunatic::process::config_set_mailbox_overflow")?;
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_mailbox_overflow: Config ID doesn't exist")?
+        .set_mailbox_overflow(overflow);
+    Ok(())
+}
+
+// Returns the mailbox overflow policy of a configuration.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_mailbox_overflow<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u32, Trap> {
+    let overflow = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_mailbox_overflow: Config ID doesn't exist")?
+        .get_mailbox_overflow();
+    Ok(overflow.into())
+}
+
+// Sets the maximum size in bytes of data messages that processes spawned from this configuration
+// can create. Writes that would grow a message over this size are rejected. Shared buffers
+// attached to a message are exempt, they are not part of the message data.
+//
+// A value of 0 indicates unlimited message sizes.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_max_message_size<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    max_message_size: u64,
+) -> Result<(), Trap> {
+    let max_message_size = match max_message_size {
+        0 => None,
+        max_message_size => Some(max_message_size as usize),
+    };
+
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_max_message_size: Config ID doesn't exist")?
+        .set_max_message_size(max_message_size);
+    Ok(())
+}
+
+// Returns the maximum message size of a configuration.
+//
+// A value of 0 indicates unlimited message sizes.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_get_max_message_size<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    config_id: u64,
+) -> Result<u64, Trap> {
+    let max_message_size = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_get_max_message_size: Config ID doesn't exist")?
+        .get_max_message_size();
+    match max_message_size {
+        None => Ok(0),
+        Some(max_message_size) => Ok(max_message_size as u64),
+    }
+}
+
 // Returns 1 if processes spawned from this configuration can compile Wasm modules, otherwise 0.
 //
 // Traps:
@@ -505,6 +742,161 @@ where
     Ok(())
 }
 
+// Returns 1 if processes spawned from this configuration can list all processes in the
+// environment, otherwise 0.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_can_list_processes<T>(caller: Caller<T>, config_id: u64) -> Result<u32, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    let can = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_can_list_processes: Config ID doesn't exist")?
+        .can_list_processes();
+    Ok(can as u32)
+}
+
+// If set to a value >0 (true), processes spawned from this configuration will be able to list
+// all processes in the environment.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_can_list_processes<T>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    can: u32,
+) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_can_list_processes: Config ID doesn't exist")?
+        .set_can_list_processes(can != 0);
+    Ok(())
+}
+
+// Returns 1 if processes spawned from this configuration can look up names registered in other
+// environments, otherwise 0.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_can_access_environments<T>(caller: Caller<T>, config_id: u64) -> Result<u32, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    let can = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_can_access_environments: Config ID doesn't exist")?
+        .can_access_environments();
+    Ok(can as u32)
+}
+
+// If set to a value >0 (true), processes spawned from this configuration will be able to look
+// up names registered in other environments.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_can_access_environments<T>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    can: u32,
+) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_can_access_environments: Config ID doesn't exist")?
+        .set_can_access_environments(can != 0);
+    Ok(())
+}
+
+// Returns 1 if processes spawned from this configuration can create new environments,
+// otherwise 0.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_can_create_environments<T>(caller: Caller<T>, config_id: u64) -> Result<u32, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    let can = caller
+        .data()
+        .config_resources()
+        .get(config_id)
+        .or_trap("lunatic::process::config_can_create_environments: Config ID doesn't exist")?
+        .can_create_environments();
+    Ok(can as u32)
+}
+
+// If set to a value >0 (true), processes spawned from this configuration will be able to create
+// new environments.
+//
+// Traps:
+// * If the config ID doesn't exist.
+fn config_set_can_create_environments<T>(
+    mut caller: Caller<T>,
+    config_id: u64,
+    can: u32,
+) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    caller
+        .data_mut()
+        .config_resources_mut()
+        .get_mut(config_id)
+        .or_trap("lunatic::process::config_set_can_create_environments: Config ID doesn't exist")?
+        .set_can_create_environments(can != 0);
+    Ok(())
+}
+
+// Creates a new environment on this node and returns its ID.
+//
+// Environments isolate processes from each other. Each environment has its own process ID space
+// and registry. Processes can be spawned into the new environment with `spawn_in_environment`.
+//
+// If **max_processes** is not 0, at most **max_processes** processes can run inside of the
+// environment at the same time.
+//
+// Traps:
+// * If the process doesn't have permissions to create environments.
+// * If the environment of the process can't create new environments.
+fn create_environment<T>(caller: Caller<T>, max_processes: u64) -> Result<u64, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_create_environments() {
+        return Err(anyhow!("Process doesn't have permissions to create environments").into());
+    }
+    let max_processes = match max_processes {
+        0 => None,
+        max => Some(max as usize),
+    };
+    caller
+        .data()
+        .create_environment(max_processes)
+        .or_trap("lunatic::process::create_environment: Environment can't create environments")
+}
+
 // Spawns a new process using the passed in function inside a module as the entry point.
 //
 // If **link** is not 0, it will link the child and parent processes. The value of the **link**
@@ -533,7 +925,91 @@ where
 // * If any memory outside the guest heap space is referenced.
 #[allow(clippy::too_many_arguments)]
 fn spawn<T>(
+    caller: Caller<T>,
+    link: i64,
+    config_id: i64,
+    module_id: i64,
+    func_str_ptr: u32,
+    func_str_len: u32,
+    params_ptr: u32,
+    params_len: u32,
+    id_ptr: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + ErrorCtx + LunaticWasiCtx + ResourceLimiter + Send + 'static,
+    for<'a> &'a T: Send,
+    T::Config: ProcessConfigCtx,
+{
+    spawn_process(
+        caller,
+        None,
+        link,
+        config_id,
+        module_id,
+        func_str_ptr,
+        func_str_len,
+        params_ptr,
+        params_len,
+        id_ptr,
+    )
+}
+
+// Spawns a new process inside the environment **environment_id**. Otherwise it works the same
+// as `spawn`.
+//
+// The process gets an ID from the process ID space of the target environment and uses the
+// registry of it.
+//
+// Returns:
+// * 0 on success - The ID of the newly created process is written to **id_ptr**
+// * 1 on error   - The error ID is written to **id_ptr**. This also happens if the environment
+//                  doesn't exist or reached its limit of processes.
+//
+// Traps:
+// * If the process doesn't have permissions to access other environments and the target
+//   environment is not its own.
+// * If the module ID doesn't exist.
+// * If the function string is not a valid utf8 string.
+// * If the params array is in a wrong format.
+// * If any memory outside the guest heap space is referenced.
+#[allow(clippy::too_many_arguments)]
+fn spawn_in_environment<T>(
+    caller: Caller<T>,
+    environment_id: u64,
+    link: i64,
+    config_id: i64,
+    module_id: i64,
+    func_str_ptr: u32,
+    func_str_len: u32,
+    params_ptr: u32,
+    params_len: u32,
+    id_ptr: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + ErrorCtx + LunaticWasiCtx + ResourceLimiter + Send + 'static,
+    for<'a> &'a T: Send,
+    T::Config: ProcessConfigCtx,
+{
+    spawn_process(
+        caller,
+        Some(environment_id),
+        link,
+        config_id,
+        module_id,
+        func_str_ptr,
+        func_str_len,
+        params_ptr,
+        params_len,
+        id_ptr,
+    )
+}
+
+// Spawns a process inside of the environment **environment_id**, or inside of the environment of
+// the calling process if it's `None`.
+#[allow(clippy::too_many_arguments)]
+fn spawn_process<T>(
     mut caller: Caller<T>,
+    environment_id: Option<u64>,
     link: i64,
     config_id: i64,
     module_id: i64,
@@ -552,6 +1028,13 @@ where
         if !caller.data().config().can_spawn_processes() {
             return Err(anyhow!("Process doesn't have permissions to spawn sub-processes").into());
         }
+        // Spawning into the own environment doesn't need any additional permissions.
+        let environment_id = environment_id.filter(|id| *id != caller.data().environment().id());
+        if environment_id.is_some() && !caller.data().config().can_access_environments() {
+            return Err(
+                anyhow!("Process doesn't have permissions to access other environments").into(),
+            );
+        }
 
         let state = caller.data();
 
@@ -581,7 +1064,23 @@ where
                 .clone(),
         };
 
-        let mut state = state.new_state(module.clone(), config)?;
+        let mut state = match environment_id {
+            None => state.new_state(module.clone(), config)?,
+            Some(environment_id) => {
+                match state.new_state_in_environment(module.clone(), config, environment_id)? {
+                    Some(state) => state,
+                    None => {
+                        let error = anyhow!("Environment {environment_id} doesn't exist");
+                        let error_id = caller.data_mut().error_resources_mut().add(error);
+                        let memory = get_memory(&mut caller)?;
+                        memory
+                            .write(&mut caller, id_ptr as usize, &error_id.to_le_bytes())
+                            .or_trap("lunatic::process::spawn")?;
+                        return Ok(1);
+                    }
+                }
+            }
+        };
 
         let memory = get_memory(&mut caller)?;
         let func_str = memory
@@ -593,26 +1092,7 @@ where
             .data(&caller)
             .get(params_ptr as usize..(params_ptr + params_len) as usize)
             .or_trap("lunatic::process::spawn")?;
-        let params_chunks = &mut params.chunks_exact(17);
-        let params = params_chunks
-            .map(|chunk| {
-                let value = u128::from_le_bytes(chunk[1..].try_into()?);
-                let result = match chunk[0] {
-                    0x7F => Val::I32(value as i32),
-                    0x7E => Val::I64(value as i64),
-                    0x7B => Val::V128(value),
-                    _ => return Err(anyhow!("Unsupported type ID")),
-                };
-                Ok(result)
-            })
-            .collect::<Result<Vec<_>>>()?;
-        if !params_chunks.remainder().is_empty() {
-            return Err(anyhow!(
-                "Params array must be in chunks of 17 bytes, but {} bytes remained",
-                params_chunks.remainder().len()
-            )
-            .into());
-        }
+        let params = parse_params(params)?;
         // Should processes be linked together?
         let link: Option<(Option<i64>, Arc<dyn Process>)> = match link {
             0 => None,
@@ -648,12 +1128,31 @@ where
         }
 
         // set state instead of config TODO
-        let env = caller.data().environment();
-        let (proc_or_error_id, result) = match lunatic_process::wasm::spawn_wasm(
-            env, runtime, &module, state, function, params, link,
-        )
-        .await
-        {
+        let env = state.environment();
+        let id = state.id();
+        // The place is reserved before spawning, so that concurrent spawns can't exceed the limit
+        let spawned = if !env.reserve_process(id) {
+            Err(anyhow!(
+                "Environment {} reached its limit of processes",
+                env.id()
+            ))
+        } else {
+            let spawned = lunatic_process::wasm::spawn_wasm(
+                env.clone(),
+                runtime,
+                &module,
+                state,
+                function,
+                params,
+                link,
+            )
+            .await;
+            if spawned.is_err() {
+                env.release_process(id);
+            }
+            spawned
+        };
+        let (proc_or_error_id, result) = match spawned {
             Ok((_, process)) => (process.id(), 0),
             Err(error) => (caller.data_mut().error_resources_mut().add(error), 1),
         };
@@ -669,6 +1168,31 @@ where
     })
 }
 
+// Parses function arguments passed as an array with the following structure:
+// [0 byte = type ID; 1..17 bytes = value as u128, ...]
+fn parse_params(params: &[u8]) -> Result<Vec<Val>> {
+    let params_chunks = &mut params.chunks_exact(17);
+    let params = params_chunks
+        .map(|chunk| {
+            let value = u128::from_le_bytes(chunk[1..].try_into()?);
+            let result = match chunk[0] {
+                0x7F => Val::I32(value as i32),
+                0x7E => Val::I64(value as i64),
+                0x7B => Val::V128(value),
+                _ => return Err(anyhow!("Unsupported type ID")),
+            };
+            Ok(result)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if !params_chunks.remainder().is_empty() {
+        return Err(anyhow!(
+            "Params array must be in chunks of 17 bytes, but {} bytes remained",
+            params_chunks.remainder().len()
+        ));
+    }
+    Ok(params)
+}
+
 // lunatic::process::sleep_ms(millis: u64)
 //
 // Suspend process for `millis`.
@@ -707,6 +1231,145 @@ fn environment_id<T: ProcessState + ProcessCtx<T>>(caller: Caller<T>) -> u64 {
     caller.data().environment().id()
 }
 
+// Writes information about the process **process_id** to **info_ptr**.
+//
+// The information is written as 5 consecutive little-endian u64 values:
+// * number of messages in the mailbox
+// * number of links
+// * memory size in bytes
+// * fuel consumed, as of the last time the process entered `receive`
+// * 1 if the process is waiting in `receive`, otherwise 0
+//
+// Returns:
+// * 0 on success
+// * 1 if the process doesn't exist
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+fn info<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<T>,
+    process_id: u64,
+    info_ptr: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_> {
+    Box::new(async move {
+        // Make sure the current process reports up-to-date fuel if it's asking about itself.
+        let fuel_consumed = caller.fuel_consumed().unwrap_or(0);
+        caller.data().usage().set_fuel_consumed(fuel_consumed);
+
+        let process = match caller.data().environment().get_process(process_id) {
+            Some(process) => process,
+            None => return Ok(1),
+        };
+        let info = match lunatic_process::process_info(process.as_ref()).await {
+            Some(info) => info,
+            None => return Ok(1),
+        };
+
+        let mut buffer = Vec::with_capacity(40);
+        write_info(&mut buffer, &info);
+
+        let memory = get_memory(&mut caller)?;
+        memory
+            .write(&mut caller, info_ptr as usize, &buffer)
+            .or_trap("lunatic::process::info")?;
+        Ok(0)
+    })
+}
+
+// Writes the IDs of all processes in the current environment to **ids_ptr**, as little-endian
+// u64 values ordered by ID. At most **ids_len** IDs are written.
+//
+// Returns the total number of processes in the environment. If it's bigger than **ids_len**,
+// the call can be repeated with a bigger buffer.
+//
+// Traps:
+// * If the process doesn't have permissions to list processes.
+// * If any memory outside the guest heap space is referenced.
+fn list<T>(mut caller: Caller<T>, ids_ptr: u32, ids_len: u32) -> Result<u32, Trap>
+where
+    T: ProcessState + ProcessCtx<T>,
+    T::Config: ProcessConfigCtx,
+{
+    if !caller.data().config().can_list_processes() {
+        return Err(anyhow!("Process doesn't have permissions to list processes").into());
+    }
+
+    let mut ids: Vec<u64> = caller
+        .data()
+        .environment()
+        .processes()
+        .iter()
+        .map(|process| process.id())
+        .collect();
+    ids.sort_unstable();
+
+    let buffer: Vec<u8> = ids
+        .iter()
+        .take(ids_len as usize)
+        .flat_map(|id| id.to_le_bytes())
+        .collect();
+    let memory = get_memory(&mut caller)?;
+    memory
+        .write(&mut caller, ids_ptr as usize, &buffer)
+        .or_trap("lunatic::process::list")?;
+    Ok(ids.len() as u32)
+}
+
+// Writes the IDs and information of all processes in the current environment to **info_ptr**,
+// ordered by ID. At most **info_len** entries are written.
+//
+// Each entry is 6 consecutive little-endian u64 values, the process ID followed by the same
+// values `info` writes.
+//
+// Returns the number of entries available. If it's bigger than **info_len**, the call can be
+// repeated with a bigger buffer. Processes that finish while the information is collected are
+// left out.
+//
+// Traps:
+// * If the process doesn't have permissions to list processes.
+// * If any memory outside the guest heap space is referenced.
+fn list_info<T>(
+    mut caller: Caller<T>,
+    info_ptr: u32,
+    info_len: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + Send,
+    T::Config: ProcessConfigCtx,
+{
+    Box::new(async move {
+        if !caller.data().config().can_list_processes() {
+            return Err(anyhow!("Process doesn't have permissions to list processes").into());
+        }
+
+        let fuel_consumed = caller.fuel_consumed().unwrap_or(0);
+        caller.data().usage().set_fuel_consumed(fuel_consumed);
+
+        let environment = caller.data().environment();
+        let processes = processes_info(environment.as_ref()).await;
+
+        let mut buffer = Vec::with_capacity(48 * processes.len().min(info_len as usize));
+        for (id, info) in processes.iter().take(info_len as usize) {
+            buffer.extend(id.to_le_bytes());
+            write_info(&mut buffer, info);
+        }
+        let memory = get_memory(&mut caller)?;
+        memory
+            .write(&mut caller, info_ptr as usize, &buffer)
+            .or_trap("lunatic::process::list_info")?;
+        Ok(processes.len() as u32)
+    })
+}
+
+// Appends the information to the buffer in the layout described by `info`.
+fn write_info(buffer: &mut Vec<u8>, info: &ProcessInfo) {
+    buffer.extend((info.mailbox_len as u64).to_le_bytes());
+    buffer.extend((info.links as u64).to_le_bytes());
+    buffer.extend((info.memory_size as u64).to_le_bytes());
+    buffer.extend(info.fuel_consumed.to_le_bytes());
+    buffer.extend((info.waiting_in_receive as u64).to_le_bytes());
+}
+
 // Link current process to **process_id**. This is not an atomic operation, any of the 2 processes
 // could fail before processing the `Link` signal and may not notify the other.
 //
@@ -794,3 +1457,156 @@ fn kill<T: ProcessState + ProcessCtx<T>>(caller: Caller<T>, process_id: u64) ->
     }
     Ok(())
 }
+
+// Send a Shutdown signal to **process_id**.
+//
+// The process will receive a shutdown message and should finish before **timeout** milliseconds
+// expire, otherwise it's killed. If the process ID doesn't exist, nothing happens.
+fn shutdown<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+    timeout: u64,
+) -> Result<(), Trap> {
+    if let Some(process) = caller.data().environment().get_process(process_id) {
+        process.send(Signal::Shutdown {
+            timeout: Duration::from_millis(timeout),
+        });
+    }
+    Ok(())
+}
+
+// Terminates the current process with a reason. Linked processes will receive the reason as part
+// of the `LinkDied` message.
+//
+// This function never returns.
+//
+// Traps:
+// * If the reason is not a valid utf8 string.
+// * If any memory outside the guest heap space is referenced.
+fn exit<T: ProcessState + ProcessCtx<T> + Send>(
+    mut caller: Caller<T>,
+    reason_ptr: u32,
+    reason_len: u32,
+) -> Box<dyn Future<Output = Result<(), Trap>> + Send + '_> {
+    Box::new(async move {
+        let memory = get_memory(&mut caller)?;
+        let reason = memory
+            .data(&caller)
+            .get(reason_ptr as usize..(reason_ptr + reason_len) as usize)
+            .or_trap("lunatic::process::exit")?;
+        let reason = std::str::from_utf8(reason)
+            .or_trap("lunatic::process::exit")?
+            .to_string();
+
+        // The signal is handled by the process as soon as this future yields and the process is
+        // terminated without polling it again.
+        caller
+            .data()
+            .signal_mailbox()
+            .0
+            .send(Signal::Exit(reason))
+            .expect("The signal is sent to itself and the receiver must exist at this point");
+        std::future::pending().await
+    })
+}
+
+// Puts the current process into hibernation.
+//
+// The instance of the process, including its linear memory, is dropped right away. Once the next
+// message arrives, the process is instantiated again from the same module and resumed by calling
+// the function **func_str** with **params**. The params use the same format as in `spawn`.
+// Resources, the mailbox, links and registered names are kept during hibernation.
+//
+// This function never returns.
+//
+// Traps:
+// * If it's called during module initialization.
+// * If the function string is not a valid utf8 string.
+// * If the params array is in a wrong format.
+// * If any memory outside the guest heap space is referenced.
+fn hibernate<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    func_str_ptr: u32,
+    func_str_len: u32,
+    params_ptr: u32,
+    params_len: u32,
+) -> Result<(), Trap> {
+    if !caller.data().is_initialized() {
+        return Err(anyhow!("Cannot hibernate during module initialization").into());
+    }
+
+    let memory = get_memory(&mut caller)?;
+    let func_str = memory
+        .data(&caller)
+        .get(func_str_ptr as usize..(func_str_ptr + func_str_len) as usize)
+        .or_trap("lunatic::process::hibernate")?;
+    let function = std::str::from_utf8(func_str)
+        .or_trap("lunatic::process::hibernate")?
+        .to_string();
+    let params = memory
+        .data(&caller)
+        .get(params_ptr as usize..(params_ptr + params_len) as usize)
+        .or_trap("lunatic::process::hibernate")?;
+    let params = parse_params(params)?;
+
+    *caller.data_mut().hibernation() = Some((function, params));
+    // Unwind the guest stack, the runtime will drop the instance and keep the state.
+    Err(Trap::new(
+        "lunatic::process::hibernate: Process is hibernating",
+    ))
+}
+
+// Start monitoring **process_id**. Monitors are one-way, if the monitored process dies, the
+// current process will receive a `ProcessDied` message containing the id of the monitored
+// process. The current process will never die because of a monitored process' death, regardless
+// of the `die_when_link_dies` setting.
+//
+// There is no separate monitor reference, monitors are identified by the id of the monitored
+// process. Monitoring the same process more than once results in a single `ProcessDied` message
+// and a single `demonitor` call removes the monitor.
+//
+// If the process doesn't exist, the `ProcessDied` message is delivered right away.
+fn monitor<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    process_id: u64,
+) -> Result<(), Trap> {
+    // Create handle to itself
+    let id = caller.data().id();
+    let signal_mailbox = caller.data().signal_mailbox().clone();
+    let this_process = WasmProcess::new(id, signal_mailbox.0);
+
+    // Send monitor signal to other process
+    let process = caller.data().environment().get_process(process_id);
+
+    if let Some(process) = process {
+        process.send(Signal::Monitor(Arc::new(this_process)));
+    } else {
+        caller
+            .data_mut()
+            .signal_mailbox()
+            .0
+            .send(Signal::ProcessDied(process_id))
+            .expect(
+                "The ProcessDied signal is sent to itself and the receiver must exist at this point",
+            );
+    }
+    Ok(())
+}
+
+// Stop monitoring **process_id**. This is not an atomic operation, a `ProcessDied` message could
+// already be on its way and end up in the mailbox after this call. If the process isn't
+// monitored or doesn't exist, nothing happens.
+fn demonitor<T: ProcessState + ProcessCtx<T>>(
+    caller: Caller<T>,
+    process_id: u64,
+) -> Result<(), Trap> {
+    let this_process_id = caller.data().id();
+
+    // Send demonitor signal to other process
+    if let Some(process) = caller.data().environment().get_process(process_id) {
+        process.send(Signal::Demonitor {
+            process_id: this_process_id,
+        });
+    }
+    Ok(())
+}
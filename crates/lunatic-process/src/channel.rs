@@ -0,0 +1,124 @@
+use tokio::sync::{
+    mpsc::{self, error::SendError, Permit},
+    Mutex,
+};
+
+use crate::message::DataMessage;
+
+/// Creates a bounded channel that carries data messages between processes.
+///
+/// Senders wait if the channel already holds `capacity` messages, this gives processes
+/// backpressure that isn't possible with mailboxes. If `multi_producer` is false, the sender
+/// can't be cloned and the receiver is guaranteed to only get messages from one sender.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+pub fn channel(capacity: usize, multi_producer: bool) -> (ChannelSender, ChannelReceiver) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    (
+        ChannelSender {
+            sender,
+            multi_producer,
+        },
+        ChannelReceiver {
+            receiver: Mutex::new(receiver),
+        },
+    )
+}
+
+/// The sending half of a channel.
+#[derive(Debug)]
+pub struct ChannelSender {
+    sender: mpsc::Sender<DataMessage>,
+    multi_producer: bool,
+}
+
+impl ChannelSender {
+    /// Waits until there is space in the channel and sends the message.
+    ///
+    /// Returns the message back if the receiver was dropped.
+    pub async fn send(&self, message: DataMessage) -> Result<(), SendError<DataMessage>> {
+        self.sender.send(message).await
+    }
+
+    /// Waits until there is space in the channel and reserves it for the next message.
+    ///
+    /// Unlike [`send`](ChannelSender::send), the message doesn't need to be moved into the
+    /// future. If waiting is canceled, the caller still owns the message.
+    pub async fn reserve(&self) -> Result<Permit<'_, DataMessage>, SendError<()>> {
+        self.sender.reserve().await
+    }
+
+    /// Returns a new sender of the same channel, or `None` if the channel is single-producer.
+    pub fn try_clone(&self) -> Option<ChannelSender> {
+        if self.multi_producer {
+            Some(ChannelSender {
+                sender: self.sender.clone(),
+                multi_producer: true,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_multi_producer(&self) -> bool {
+        self.multi_producer
+    }
+}
+
+/// The receiving half of a channel.
+#[derive(Debug)]
+pub struct ChannelReceiver {
+    receiver: Mutex<mpsc::Receiver<DataMessage>>,
+}
+
+impl ChannelReceiver {
+    /// Waits on the next message.
+    ///
+    /// Returns `None` once all senders are dropped and there are no more messages left in the
+    /// channel.
+    ///
+    /// This function is cancellation safe, no message is lost if the future is dropped.
+    pub async fn recv(&self) -> Option<DataMessage> {
+        self.receiver.lock().await.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn single_producer_channel() {
+        let (sender, receiver) = channel(1, false);
+        assert!(sender.try_clone().is_none());
+        sender
+            .send(DataMessage::new_from_vec(Some(1), vec![1]))
+            .await
+            .unwrap();
+        let message = receiver.recv().await.unwrap();
+        assert_eq!(message.tag, Some(1));
+        drop(sender);
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn bounded_capacity() {
+        let (sender, receiver) = channel(1, true);
+        let second = sender.try_clone().unwrap();
+        sender.send(DataMessage::new(Some(1), 0)).await.unwrap();
+        // The channel is full, the second send needs to wait on the receiver.
+        let blocked = tokio::time::timeout(
+            std::time::Duration::from_millis(10),
+            second.send(DataMessage::new(Some(2), 0)),
+        )
+        .await;
+        assert!(blocked.is_err());
+        assert_eq!(receiver.recv().await.unwrap().tag, Some(1));
+        second.send(DataMessage::new(Some(2), 0)).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().tag, Some(2));
+        drop(receiver);
+        assert!(sender.send(DataMessage::new(None, 0)).await.is_err());
+    }
+}
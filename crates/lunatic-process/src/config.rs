@@ -1,4 +1,6 @@
-use serde::{de::DeserializeOwned, Serialize};
+use std::convert::TryFrom;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 // One unit of fuel represents around 100k instructions.
 pub const UNIT_OF_COMPUTE_IN_INSTRUCTIONS: u64 = 100_000;
@@ -12,8 +14,9 @@ pub const UNIT_OF_COMPUTE_IN_INSTRUCTIONS: u64 = 100_000;
 /// the process. This host functions are the ones that consider specific configuration while
 /// performing operations.
 ///
-/// However, two properties of a process are enforced by the runtime (maximum memory and maximum
-/// fuel usage). This two properties need to be part of every configuration.
+/// However, some properties of a process are enforced by the runtime (maximum memory, maximum
+/// fuel usage, the mailbox capacity and the message size). This properties need to be part of
+/// every configuration.
 ///
 /// `ProcessConfig` must be serializable in case it is used to spawn processes on other nodes.
 pub trait ProcessConfig: Clone + Serialize + DeserializeOwned {
@@ -21,4 +24,49 @@ pub trait ProcessConfig: Clone + Serialize + DeserializeOwned {
     fn get_max_fuel(&self) -> Option<u64>;
     fn set_max_memory(&mut self, max_memory: usize);
     fn get_max_memory(&self) -> usize;
+    fn set_mailbox_capacity(&mut self, capacity: Option<usize>);
+    fn get_mailbox_capacity(&self) -> Option<usize>;
+    fn set_mailbox_overflow(&mut self, overflow: MailboxOverflow);
+    fn get_mailbox_overflow(&self) -> MailboxOverflow;
+    fn set_max_message_size(&mut self, max_message_size: Option<usize>);
+    fn get_max_message_size(&self) -> Option<usize>;
+}
+
+/// Defines what happens when a message arrives at a mailbox that reached its capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MailboxOverflow {
+    /// The new message is dropped.
+    #[default]
+    DropNewest,
+    /// The oldest message in the mailbox is dropped to make space for the new one.
+    DropOldest,
+    /// The receiving process is killed.
+    KillReceiver,
+    /// Senders are notified that the mailbox is full and the message is not sent.
+    Backpressure,
+}
+
+impl TryFrom<u32> for MailboxOverflow {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> anyhow::Result<Self> {
+        match value {
+            0 => Ok(MailboxOverflow::DropNewest),
+            1 => Ok(MailboxOverflow::DropOldest),
+            2 => Ok(MailboxOverflow::KillReceiver),
+            3 => Ok(MailboxOverflow::Backpressure),
+            _ => Err(anyhow::anyhow!("Unknown mailbox overflow policy {}", value)),
+        }
+    }
+}
+
+impl From<MailboxOverflow> for u32 {
+    fn from(overflow: MailboxOverflow) -> Self {
+        match overflow {
+            MailboxOverflow::DropNewest => 0,
+            MailboxOverflow::DropOldest => 1,
+            MailboxOverflow::KillReceiver => 2,
+            MailboxOverflow::Backpressure => 3,
+        }
+    }
 }
@@ -1,10 +1,22 @@
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
 use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, RwLock, Weak,
 };
+use tokio::sync::mpsc::UnboundedSender;
 
-use crate::{Process, Signal};
+use crate::{
+    groups::ProcessGroups,
+    info::ProcessInfo,
+    message::{DataMessage, Message},
+    process_info,
+    registry::{ProcessRegistry, Registration},
+    Process, Signal,
+};
+
+/// A name removed from the registry of an environment because the process owning it exited, as
+/// (environment ID, name, registration).
+pub type RemovedName = (u64, String, Registration);
 
 pub trait Environment: Send + Sync {
     fn id(&self) -> u64;
@@ -13,7 +25,64 @@ pub trait Environment: Send + Sync {
     fn add_process(&self, id: u64, proc: Arc<dyn Process>);
     fn remove_process(&self, id: u64);
     fn process_count(&self) -> usize;
+    fn processes(&self) -> Vec<Arc<dyn Process>>;
     fn send(&self, id: u64, signal: Signal);
+    fn groups(&self) -> &ProcessGroups;
+    fn registry(&self) -> &ProcessRegistry;
+    /// Returns the registry of another environment on this node, if it exists.
+    ///
+    /// Environments don't share names, this is the only way to look up processes registered in
+    /// a different environment.
+    fn environment_registry(&self, environment_id: u64) -> Option<ProcessRegistry>;
+    /// Returns the maximum number of processes that can run inside the environment at the same
+    /// time, or `None` if there is no limit.
+    fn max_processes(&self) -> Option<usize>;
+    /// Reserves a place for the process with ID before it's spawned, so that concurrent spawns
+    /// can't exceed [`max_processes`](Self::max_processes). Returns false if the environment is
+    /// full.
+    ///
+    /// The reservation is taken over by `add_process`, or needs to be given back with
+    /// `release_process` if the process fails to spawn.
+    fn reserve_process(&self, id: u64) -> bool;
+    /// Gives back the reservation of a process that failed to spawn.
+    fn release_process(&self, id: u64);
+}
+
+/// Collects information about all processes running in the environment, ordered by process ID.
+///
+/// Processes that finish before answering are skipped.
+pub async fn processes_info(env: &dyn Environment) -> Vec<(u64, ProcessInfo)> {
+    let mut processes = env.processes();
+    processes.sort_by_key(|process| process.id());
+    let mut infos = Vec::with_capacity(processes.len());
+    for process in processes {
+        if let Some(info) = process_info(process.as_ref()).await {
+            infos.push((process.id(), info));
+        }
+    }
+    infos
+}
+
+/// Sends a copy of the message to every process in the group `name` and returns the number of
+/// processes it was sent to. Members with a full mailbox are skipped.
+///
+/// Returns `None` without sending anything if the message carries resources that can't be
+/// shared between processes, see [`DataMessage::try_clone_shared`].
+pub fn broadcast(env: &dyn Environment, name: &str, message: &DataMessage) -> Option<u32> {
+    // Check the resources once, so that a failure doesn't leave the group half notified.
+    message.try_clone_shared()?;
+    let mut sent = 0;
+    for process_id in env.groups().members(name) {
+        if let Some(process) = env.get_process(process_id) {
+            if process.is_full(message.priority) {
+                continue;
+            }
+            let copy = message.try_clone_shared()?;
+            process.send(Signal::Message(Message::Data(copy)));
+            sent += 1;
+        }
+    }
+    Some(sent)
 }
 
 pub trait Environments: Send + Sync {
@@ -27,6 +96,16 @@ pub struct LunaticEnvironment {
     environment_id: u64,
     next_process_id: Arc<AtomicU64>,
     processes: Arc<DashMap<u64, Arc<dyn Process>>>,
+    groups: ProcessGroups,
+    registry: ProcessRegistry,
+    max_processes: Option<usize>,
+    // Running processes plus reserved places of processes that are still spawning.
+    process_slots: Arc<AtomicUsize>,
+    reserved: Arc<DashSet<u64>>,
+    // All environments on this node, if the environment was created through `LunaticEnvironments`.
+    environments: Weak<DashMap<u64, Arc<LunaticEnvironment>>>,
+    // Shared with all environments created through the same `LunaticEnvironments`.
+    removed_names: Arc<RwLock<Option<UnboundedSender<RemovedName>>>>,
 }
 
 impl LunaticEnvironment {
@@ -35,8 +114,24 @@ impl LunaticEnvironment {
             environment_id: id,
             processes: Arc::new(DashMap::new()),
             next_process_id: Arc::new(AtomicU64::new(1)),
+            groups: ProcessGroups::default(),
+            registry: ProcessRegistry::default(),
+            max_processes: None,
+            process_slots: Arc::default(),
+            reserved: Arc::default(),
+            environments: Weak::new(),
+            removed_names: Arc::default(),
         }
     }
+
+    /// Returns all environments on this node, if the environment was created through
+    /// [`LunaticEnvironments`].
+    pub fn environments(&self) -> Option<LunaticEnvironments> {
+        self.environments.upgrade().map(|envs| LunaticEnvironments {
+            envs,
+            removed_names: self.removed_names.clone(),
+        })
+    }
 }
 
 impl Environment for LunaticEnvironment {
@@ -45,6 +140,10 @@ impl Environment for LunaticEnvironment {
     }
 
     fn add_process(&self, id: u64, proc: Arc<dyn Process>) {
+        // Processes spawned without a reservation still take a place.
+        if self.reserved.remove(&id).is_none() {
+            self.process_slots.fetch_add(1, Ordering::AcqRel);
+        }
         self.processes.insert(id, proc);
         #[cfg(all(feature = "metrics", not(feature = "detailed_metrics")))]
         let labels: [(String, String); 0] = [];
@@ -59,7 +158,21 @@ impl Environment for LunaticEnvironment {
     }
 
     fn remove_process(&self, id: u64) {
-        self.processes.remove(&id);
+        if self.processes.remove(&id).is_some() {
+            self.process_slots.fetch_sub(1, Ordering::AcqRel);
+        }
+        self.groups.remove_process(id);
+        // Names registered by the process would point to a dead process and watches would keep it
+        // alive
+        let unregistered = self.registry.remove_process(id);
+        #[cfg(feature = "metrics")]
+        metrics::decrement_gauge!("lunatic.registry.registered", unregistered.len() as f64);
+        if let Some(sender) = self.removed_names.read().unwrap().as_ref() {
+            for (name, registration) in unregistered {
+                // The receiver is only dropped when the node shuts down
+                let _ = sender.send((self.environment_id, name, registration));
+            }
+        }
         #[cfg(all(feature = "metrics", not(feature = "detailed_metrics")))]
         let labels: [(String, String); 0] = [];
         #[cfg(all(feature = "metrics", feature = "detailed_metrics"))]
@@ -76,6 +189,10 @@ impl Environment for LunaticEnvironment {
         self.processes.len()
     }
 
+    fn processes(&self) -> Vec<Arc<dyn Process>> {
+        self.processes.iter().map(|x| x.value().clone()).collect()
+    }
+
     fn send(&self, id: u64, signal: Signal) {
         if let Some(proc) = self.processes.get(&id) {
             proc.send(signal);
@@ -89,17 +206,65 @@ impl Environment for LunaticEnvironment {
     fn id(&self) -> u64 {
         self.environment_id
     }
+
+    fn groups(&self) -> &ProcessGroups {
+        &self.groups
+    }
+
+    fn registry(&self) -> &ProcessRegistry {
+        &self.registry
+    }
+
+    fn environment_registry(&self, environment_id: u64) -> Option<ProcessRegistry> {
+        if environment_id == self.environment_id {
+            return Some(self.registry.clone());
+        }
+        let environments = self.environments.upgrade()?;
+        let environment = environments.get(&environment_id)?;
+        Some(environment.registry.clone())
+    }
+
+    fn max_processes(&self) -> Option<usize> {
+        self.max_processes
+    }
+
+    fn reserve_process(&self, id: u64) -> bool {
+        let reserved = self
+            .process_slots
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |slots| {
+                match self.max_processes {
+                    Some(max) if slots >= max => None,
+                    _ => Some(slots + 1),
+                }
+            })
+            .is_ok();
+        if reserved {
+            self.reserved.insert(id);
+        }
+        reserved
+    }
+
+    fn release_process(&self, id: u64) {
+        if self.reserved.remove(&id).is_some() {
+            self.process_slots.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct LunaticEnvironments {
     envs: Arc<DashMap<u64, Arc<LunaticEnvironment>>>,
+    removed_names: Arc<RwLock<Option<UnboundedSender<RemovedName>>>>,
 }
 
 impl Environments for LunaticEnvironments {
     type Env = LunaticEnvironment;
     fn create(&self, id: u64) -> Arc<Self::Env> {
-        let env = Arc::new(LunaticEnvironment::new(id));
+        let env = Arc::new(LunaticEnvironment {
+            environments: Arc::downgrade(&self.envs),
+            removed_names: self.removed_names.clone(),
+            ..LunaticEnvironment::new(id)
+        });
         self.envs.insert(id, env.clone());
         #[cfg(feature = "metrics")]
         metrics::gauge!("lunatic.process.environment.count", self.envs.len() as f64);
@@ -109,3 +274,226 @@ impl Environments for LunaticEnvironments {
         self.envs.get(&id).map(|e| e.clone())
     }
 }
+
+impl LunaticEnvironments {
+    /// Returns all environments on this node, ordered by ID.
+    pub fn all(&self) -> Vec<Arc<LunaticEnvironment>> {
+        let mut envs: Vec<_> = self.envs.iter().map(|env| env.value().clone()).collect();
+        envs.sort_by_key(|env| env.environment_id);
+        envs
+    }
+
+    /// Creates a new environment with an ID that is not used by any other environment on this
+    /// node.
+    ///
+    /// The environment has its own process ID space and registry. If `max_processes` is set, no
+    /// more than `max_processes` processes can be spawned into it at the same time.
+    pub fn create_isolated(&self, max_processes: Option<usize>) -> Arc<LunaticEnvironment> {
+        // Environments created from the command line or by remote nodes use low IDs, start
+        // searching for a free ID after the highest one in use.
+        let mut id = self.envs.iter().map(|env| *env.key()).max().unwrap_or(0);
+        loop {
+            id += 1;
+            if let Entry::Vacant(entry) = self.envs.entry(id) {
+                let env = Arc::new(LunaticEnvironment {
+                    max_processes,
+                    environments: Arc::downgrade(&self.envs),
+                    removed_names: self.removed_names.clone(),
+                    ..LunaticEnvironment::new(id)
+                });
+                entry.insert(env.clone());
+                #[cfg(feature = "metrics")]
+                metrics::gauge!("lunatic.process.environment.count", self.envs.len() as f64);
+                return env;
+            }
+        }
+    }
+
+    /// Sends every name that is removed from the registry of an environment, because the
+    /// process owning it exited, to `sender`.
+    ///
+    /// Applies to environments that already exist and ones created later. Nodes that are part of
+    /// a cluster use it to remove the names from the cluster-wide registry too.
+    pub fn forward_removed_names(&self, sender: UnboundedSender<RemovedName>) {
+        *self.removed_names.write().unwrap() = Some(sender);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::SharedBuffer;
+    use std::sync::Mutex;
+
+    // Collects the data messages it receives.
+    struct Recorder(u64, Mutex<Vec<DataMessage>>);
+
+    impl Process for Recorder {
+        fn id(&self) -> u64 {
+            self.0
+        }
+
+        fn send(&self, signal: Signal) {
+            if let Signal::Message(Message::Data(message)) = signal {
+                self.1.lock().unwrap().push(message);
+            }
+        }
+    }
+
+    #[test]
+    fn broadcast_only_shares_shared_buffers() {
+        let env = LunaticEnvironment::new(1);
+        let members: Vec<_> = (1..=2)
+            .map(|id| {
+                let member = Arc::new(Recorder(id, Mutex::default()));
+                env.add_process(id, member.clone());
+                env.groups().join("group", id);
+                member
+            })
+            .collect();
+        // Members that died are skipped.
+        env.groups().join("group", 3);
+
+        let mut message = DataMessage::new_from_vec(Some(5), vec![1, 2, 3]);
+        let buffer = Arc::new(SharedBuffer::new(vec![4, 5]));
+        message.add_resource(buffer.clone());
+        assert_eq!(broadcast(&env, "group", &message), Some(2));
+        for member in &members {
+            let mut received = member.1.lock().unwrap();
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].tag, Some(5));
+            assert_eq!(received[0].buffer, vec![1, 2, 3]);
+            let shared = received[0].take_shared_buffer(0).unwrap();
+            assert!(Arc::ptr_eq(&shared, &buffer));
+        }
+
+        // Other resources can only have one owner.
+        message.add_resource(Arc::new(42u64));
+        assert_eq!(broadcast(&env, "group", &message), None);
+        for member in &members {
+            assert_eq!(member.1.lock().unwrap().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn processes_info_skips_finished_processes() {
+        let env = Arc::new(LunaticEnvironment::new(1));
+        let spawn_idle = || {
+            let (_, process) = crate::spawn(env.clone(), |_, mailbox| async move {
+                // Waits on a message that never arrives
+                mailbox.pop(Some(&[0])).await;
+                Ok(())
+            });
+            process
+        };
+        let first = spawn_idle();
+        let second = spawn_idle();
+        // Doesn't answer info requests, like a process that finished in the meantime
+        env.add_process(0, Arc::new(Recorder(0, Mutex::default())));
+
+        let message = DataMessage::new_from_vec(Some(1), vec![]);
+        second.send(Signal::Message(Message::Data(message)));
+        let infos = processes_info(env.as_ref()).await;
+        let ids: Vec<_> = infos.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![first.id(), second.id()]);
+        assert_eq!(infos[0].1.mailbox_len, 0);
+        assert_eq!(infos[1].1.mailbox_len, 1);
+    }
+
+    #[test]
+    fn all_environments_are_ordered_by_id() {
+        let environments = LunaticEnvironments::default();
+        environments.create(3);
+        environments.create(1);
+        let isolated = environments.create_isolated(None);
+        let ids: Vec<_> = environments.all().iter().map(|env| env.id()).collect();
+        assert_eq!(ids, vec![1, 3, isolated.id()]);
+    }
+
+    #[test]
+    fn environments_have_separate_registries() {
+        let environments = LunaticEnvironments::default();
+        let first = environments.create(1);
+        let second = environments.create(2);
+        let registration = Registration {
+            node_id: 0,
+            process_id: 1,
+            owner: None,
+            metadata: Vec::new(),
+        };
+        first.registry().put("name", registration.clone());
+
+        assert!(second.registry().get("name").is_none());
+        let registry = second.environment_registry(1).unwrap();
+        assert_eq!(*registry.get("name").unwrap(), registration);
+        assert!(second.environment_registry(3).is_none());
+        // Environments created outside of `LunaticEnvironments` can only see themselves.
+        let standalone = LunaticEnvironment::new(4);
+        assert!(standalone.environment_registry(1).is_none());
+        assert!(standalone.environment_registry(4).is_some());
+    }
+
+    #[test]
+    fn isolated_environments_get_unused_ids() {
+        let environments = LunaticEnvironments::default();
+        environments.create(1);
+        environments.create(3);
+        let isolated = environments.create_isolated(Some(2));
+        assert_eq!(isolated.id(), 4);
+        assert_eq!(isolated.max_processes(), Some(2));
+        assert_eq!(isolated.get_next_process_id(), 1);
+        let envs = isolated.environments().unwrap();
+        assert!(Arc::ptr_eq(&envs.get(4).unwrap(), &isolated));
+        assert_eq!(envs.create_isolated(None).id(), 5);
+    }
+
+    #[test]
+    fn reservations_count_towards_max_processes() {
+        let env = LunaticEnvironments::default().create_isolated(Some(2));
+        assert!(env.reserve_process(1));
+        assert!(env.reserve_process(2));
+        // Both places are taken, even if no process was added yet
+        assert!(!env.reserve_process(3));
+        env.release_process(2);
+        assert!(env.reserve_process(3));
+
+        let process = Arc::new(Recorder(1, Mutex::default()));
+        env.add_process(1, process.clone());
+        env.add_process(3, process);
+        assert!(!env.reserve_process(4));
+        env.remove_process(1);
+        assert!(env.reserve_process(4));
+        // Releasing a place that was already taken over by a process does nothing
+        env.release_process(3);
+        assert!(!env.reserve_process(5));
+    }
+
+    #[test]
+    fn removed_names_are_forwarded() {
+        let environments = LunaticEnvironments::default();
+        let env = environments.create(1);
+        let (sender, mut removed) = tokio::sync::mpsc::unbounded_channel();
+        environments.forward_removed_names(sender);
+        let registration = Registration {
+            node_id: 0,
+            process_id: 3,
+            owner: Some(2),
+            metadata: Vec::new(),
+        };
+        env.registry().put("owned", registration.clone());
+        env.registry().put(
+            "permanent",
+            Registration {
+                owner: None,
+                ..registration.clone()
+            },
+        );
+
+        env.remove_process(2);
+        assert_eq!(
+            removed.try_recv().unwrap(),
+            (1, "owned".to_string(), registration)
+        );
+        assert!(removed.try_recv().is_err());
+    }
+}
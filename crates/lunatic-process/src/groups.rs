@@ -0,0 +1,93 @@
+use std::{collections::HashSet, sync::Arc};
+
+use dashmap::DashMap;
+
+/// Named groups of processes inside of an environment.
+///
+/// Groups are created when the first process joins them and removed when the last one leaves.
+/// The environment removes dead processes from all groups they are part of.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessGroups {
+    groups: Arc<DashMap<String, HashSet<u64>>>,
+    // Reverse index, so that dead processes can be removed without walking all groups.
+    memberships: Arc<DashMap<u64, HashSet<String>>>,
+}
+
+impl ProcessGroups {
+    /// Adds the process to the group. Joining a group twice has no effect.
+    pub fn join(&self, group: &str, process_id: u64) {
+        self.groups
+            .entry(group.to_owned())
+            .or_default()
+            .insert(process_id);
+        self.memberships
+            .entry(process_id)
+            .or_default()
+            .insert(group.to_owned());
+    }
+
+    /// Removes the process from the group and returns false if it wasn't a member.
+    pub fn leave(&self, group: &str, process_id: u64) -> bool {
+        let removed = match self.groups.get_mut(group) {
+            Some(mut members) => members.remove(&process_id),
+            None => false,
+        };
+        self.groups
+            .remove_if(group, |_, members| members.is_empty());
+        if let Some(mut groups) = self.memberships.get_mut(&process_id) {
+            groups.remove(group);
+        }
+        self.memberships
+            .remove_if(&process_id, |_, groups| groups.is_empty());
+        removed
+    }
+
+    /// Returns the IDs of all processes in the group, in ascending order.
+    pub fn members(&self, group: &str) -> Vec<u64> {
+        let mut members: Vec<u64> = match self.groups.get(group) {
+            Some(members) => members.iter().copied().collect(),
+            None => Vec::new(),
+        };
+        members.sort_unstable();
+        members
+    }
+
+    /// Removes the process from all groups it joined.
+    pub fn remove_process(&self, process_id: u64) {
+        if let Some((_, groups)) = self.memberships.remove(&process_id) {
+            for group in groups {
+                if let Some(mut members) = self.groups.get_mut(&group) {
+                    members.remove(&process_id);
+                }
+                self.groups
+                    .remove_if(&group, |_, members| members.is_empty());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_and_leave() {
+        let groups = ProcessGroups::default();
+        groups.join("workers", 3);
+        groups.join("workers", 1);
+        groups.join("workers", 1);
+        groups.join("loggers", 1);
+        assert_eq!(groups.members("workers"), vec![1, 3]);
+        assert_eq!(groups.members("loggers"), vec![1]);
+
+        assert!(groups.leave("workers", 3));
+        assert!(!groups.leave("workers", 3));
+        assert_eq!(groups.members("workers"), vec![1]);
+
+        groups.remove_process(1);
+        assert!(groups.members("workers").is_empty());
+        assert!(groups.members("loggers").is_empty());
+        assert!(groups.groups.is_empty());
+        assert!(groups.memberships.is_empty());
+    }
+}
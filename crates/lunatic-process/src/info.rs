@@ -0,0 +1,68 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// A snapshot of a running process, sent as reply to a [`Signal::Info`](crate::Signal::Info).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessInfo {
+    /// Number of messages waiting in the mailbox.
+    pub mailbox_len: usize,
+    /// Number of processes linked to this one.
+    pub links: usize,
+    /// Size of the process' memory in bytes.
+    pub memory_size: usize,
+    /// Fuel consumed by the process, as of the last time it entered `receive`.
+    pub fuel_consumed: u64,
+    /// True if the process is currently waiting on a message.
+    pub waiting_in_receive: bool,
+}
+
+/// Resource usage of a process.
+///
+/// The usage is only known to the process' state while it runs, but the signal handler needs it
+/// to answer info requests. Both of them hold onto a clone of the same `ProcessUsage`.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessUsage {
+    inner: Arc<InnerProcessUsage>,
+}
+
+#[derive(Debug, Default)]
+struct InnerProcessUsage {
+    memory_size: AtomicUsize,
+    fuel_consumed: AtomicU64,
+    memory_limit_reached: AtomicBool,
+}
+
+impl ProcessUsage {
+    pub fn memory_size(&self) -> usize {
+        self.inner.memory_size.load(Ordering::Relaxed)
+    }
+
+    pub fn set_memory_size(&self, memory_size: usize) {
+        self.inner.memory_size.store(memory_size, Ordering::Relaxed)
+    }
+
+    pub fn fuel_consumed(&self) -> u64 {
+        self.inner.fuel_consumed.load(Ordering::Relaxed)
+    }
+
+    pub fn set_fuel_consumed(&self, fuel_consumed: u64) {
+        self.inner
+            .fuel_consumed
+            .store(fuel_consumed, Ordering::Relaxed)
+    }
+
+    /// Returns true if the process was refused to grow its memory over the limit.
+    pub fn memory_limit_reached(&self) -> bool {
+        self.inner.memory_limit_reached.load(Ordering::Relaxed)
+    }
+
+    /// Called by the `ResourceLimiter` when it refuses a memory growth, so that the trap that
+    /// usually follows is reported as [`DeathReason::MemoryLimit`](crate::DeathReason).
+    pub fn set_memory_limit_reached(&self) {
+        self.inner
+            .memory_limit_reached
+            .store(true, Ordering::Relaxed)
+    }
+}
@@ -1,12 +1,28 @@
+pub mod channel;
 pub mod config;
 pub mod env;
+pub mod groups;
+pub mod info;
 pub mod mailbox;
 pub mod message;
+pub mod registry;
 pub mod runtimes;
+pub mod snapshot;
 pub mod state;
 pub mod wasm;
 
-use std::{collections::HashMap, fmt::Debug, future::Future, hash::Hash, sync::Arc};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    hash::Hash,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
 use env::Environment;
@@ -15,12 +31,17 @@ use log::{debug, log_enabled, trace, warn, Level};
 use tokio::{
     sync::{
         mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
-        Mutex,
+        oneshot, Mutex,
     },
     task::JoinHandle,
+    time::Instant,
 };
 
-use crate::{mailbox::MessageMailbox, message::Message};
+use crate::{
+    info::{ProcessInfo, ProcessUsage},
+    mailbox::{MessageMailbox, PushResult},
+    message::Message,
+};
 
 #[cfg(feature = "metrics")]
 pub fn describe_metrics() {
@@ -44,6 +65,18 @@ pub fn describe_metrics() {
         "Number of messages sent to processes since startup"
     );
 
+    describe_counter!(
+        "lunatic.process.messages.dropped",
+        Unit::Count,
+        "Number of messages dropped because of a full mailbox since startup"
+    );
+
+    describe_counter!(
+        "lunatic.process.messages.data.rejected",
+        Unit::Count,
+        "Number of data messages rejected for exceeding the maximum message size since startup"
+    );
+
     describe_gauge!(
         "lunatic.process.messages.outstanding",
         Unit::Count,
@@ -80,6 +113,30 @@ pub fn describe_metrics() {
         "Number of LinkDied messages send since startup"
     );
 
+    describe_counter!(
+        "lunatic.process.messages.process_died.count",
+        Unit::Count,
+        "Number of ProcessDied messages send since startup"
+    );
+
+    describe_counter!(
+        "lunatic.process.messages.shutdown.count",
+        Unit::Count,
+        "Number of Shutdown messages send since startup"
+    );
+
+    describe_counter!(
+        "lunatic.process.hibernations",
+        Unit::Count,
+        "Number of times processes went into hibernation since startup"
+    );
+
+    describe_gauge!(
+        "lunatic.process.monitors.alive",
+        Unit::Count,
+        "Number of monitors currently alive"
+    );
+
     describe_gauge!(
         "lunatic.process.environment.process.count",
         Unit::Count,
@@ -104,6 +161,12 @@ pub fn describe_metrics() {
 pub trait Process: Send + Sync {
     fn id(&self) -> u64;
     fn send(&self, signal: Signal);
+    /// Returns true if the process' mailbox is full and senders should hold back new messages.
+    ///
+    /// Priority messages are queued separately, `priority` selects the queue that is checked.
+    fn is_full(&self, _priority: bool) -> bool {
+        false
+    }
 }
 
 impl Debug for dyn Process {
@@ -124,6 +187,11 @@ pub enum Signal {
     Message(Message),
     // When received, the process should stop immediately.
     Kill,
+    // When received, the process should stop immediately. Linked processes will see the reason.
+    Exit(String),
+    // Asks the process to finish. It's delivered to the process as a `Message::Shutdown`. If the
+    // process is still running after the timeout, it's killed.
+    Shutdown { timeout: Duration },
     // Change behaviour of what happens if a linked process dies.
     DieWhenLinkDies(bool),
     // Sent from a process that wants to be linked. In case of a death the tag will be returned
@@ -136,6 +204,18 @@ pub enum Signal {
     // the death reason, the receiving process will turn this signal into a message or the
     // process will immediately die as well.
     LinkDied(u64, Option<i64>, DeathReason),
+    // Sent from a process that wants to monitor this one. Unlike links, monitors are one-way and
+    // the monitoring process will never die because of the monitored process' death. Instead it
+    // will receive a `ProcessDied` signal.
+    Monitor(Arc<dyn Process>),
+    // Request from a process to stop monitoring this one
+    Demonitor { process_id: u64 },
+    // Sent to monitoring processes when the monitored process dies. Contains the id of the
+    // monitored process, that is also used as the monitor reference. It's always turned into a
+    // `Message::ProcessDied` message.
+    ProcessDied(u64),
+    // Request a snapshot of the process' state. The reply is sent back through the channel.
+    Info(oneshot::Sender<ProcessInfo>),
 }
 
 impl Debug for Signal {
@@ -143,21 +223,103 @@ impl Debug for Signal {
         match self {
             Self::Message(_) => write!(f, "Message"),
             Self::Kill => write!(f, "Kill"),
+            Self::Exit(reason) => write!(f, "Exit {reason}"),
+            Self::Shutdown { timeout } => write!(f, "Shutdown {timeout:?}"),
             Self::DieWhenLinkDies(_) => write!(f, "DieWhenLinkDies"),
             Self::Link(_, p) => write!(f, "Link {}", p.id()),
             Self::UnLink { process_id } => write!(f, "UnLink {process_id}"),
             Self::LinkDied(_, _, reason) => write!(f, "LinkDied {:?}", reason),
+            Self::Monitor(p) => write!(f, "Monitor {}", p.id()),
+            Self::Demonitor { process_id } => write!(f, "Demonitor {process_id}"),
+            Self::ProcessDied(id) => write!(f, "ProcessDied {id}"),
+            Self::Info(_) => write!(f, "Info"),
         }
     }
 }
 
 // The reason of a process' death
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DeathReason {
     // Process finished normaly.
     Normal,
-    Failure,
+    // Process failed for an unspecified reason (e.g. native process returned an error or a host
+    // function panicked), contains the description of the failure.
+    Failure(String),
+    // Process didn't exist at the moment of linking.
     NoProcess,
+    // Process was killed by a signal or died because of a linked process.
+    Kill,
+    // Process trapped, contains the trap reason.
+    Trap(String),
+    // Process consumed all the fuel it was given.
+    OutOfFuel,
+    // Process trapped after reaching the memory limit.
+    MemoryLimit,
+    // Process exited with a non-zero exit code (e.g. `proc_exit(n)`).
+    ExitCode(i32),
+    // Process failed to start, contains the reason.
+    SpawnError(String),
+    // Process was killed because its mailbox overflowed.
+    MailboxOverflow,
+    // Process deliberately exited, contains the reason.
+    Exit(String),
+}
+
+impl DeathReason {
+    // Returns a message describing the reason in more detail, if one exists.
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            DeathReason::Failure(message)
+            | DeathReason::Trap(message)
+            | DeathReason::SpawnError(message)
+            | DeathReason::Exit(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+// Labels attached to the metrics of a process.
+#[cfg(not(feature = "detailed_metrics"))]
+type MetricsLabels = [(&'static str, String); 0];
+#[cfg(feature = "detailed_metrics")]
+type MetricsLabels = [(&'static str, String); 1];
+
+// Pushes a message into the mailbox of a process and records the message metrics. Returns how the
+// process finishes if the mailbox overflowed.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+fn deliver<T>(
+    message_mailbox: &MessageMailbox,
+    message: Message,
+    labels: &MetricsLabels,
+) -> Option<Finished<T>> {
+    #[cfg(feature = "metrics")]
+    message.write_metrics();
+
+    let finished = match message_mailbox.push(message) {
+        PushResult::Delivered => None,
+        PushResult::Dropped => {
+            #[cfg(feature = "metrics")]
+            metrics::increment_counter!("lunatic.process.messages.dropped", labels);
+            None
+        }
+        PushResult::Overflow => {
+            #[cfg(feature = "metrics")]
+            metrics::increment_counter!("lunatic.process.messages.dropped", labels);
+            Some(Finished::MailboxOverflow)
+        }
+    };
+
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!("lunatic.process.messages.send", labels);
+
+    #[cfg(feature = "metrics")]
+    metrics::gauge!(
+        "lunatic.process.messages.outstanding",
+        message_mailbox.len() as f64,
+        labels
+    );
+
+    finished
 }
 
 /// The reason of a process finishing
@@ -168,6 +330,38 @@ pub enum Finished<T> {
     Normal(T),
     /// The process was terminated by an external `Kill` signal.
     KillSignal,
+    /// The process was terminated because its mailbox overflowed.
+    MailboxOverflow,
+    /// The process was terminated by an `Exit` signal, containing the reason.
+    Exit(String),
+    /// The process panicked, containing the panic message.
+    Panic(String),
+}
+
+// Polls the inner future and catches panics, so that they can be reported to links.
+struct CatchUnwind<'a, F>(Pin<&'a mut F>);
+
+impl<F: Future> Future for CatchUnwind<'_, F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+// Extracts the message from a panic payload.
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic payload".to_string(),
+        },
+    }
 }
 
 /// A `WasmProcess` represents an instance of a Wasm module that is being executed.
@@ -178,12 +372,30 @@ pub enum Finished<T> {
 pub struct WasmProcess {
     id: u64,
     signal_mailbox: UnboundedSender<Signal>,
+    message_mailbox: Option<MessageMailbox>,
 }
 
 impl WasmProcess {
     /// Create a new WasmProcess
     pub fn new(id: u64, signal_mailbox: UnboundedSender<Signal>) -> Self {
-        Self { id, signal_mailbox }
+        Self {
+            id,
+            signal_mailbox,
+            message_mailbox: None,
+        }
+    }
+
+    /// Create a new WasmProcess that can report if its mailbox is full
+    pub fn with_mailbox(
+        id: u64,
+        signal_mailbox: UnboundedSender<Signal>,
+        message_mailbox: MessageMailbox,
+    ) -> Self {
+        Self {
+            id,
+            signal_mailbox,
+            message_mailbox: Some(message_mailbox),
+        }
     }
 }
 
@@ -209,6 +421,13 @@ impl Process for WasmProcess {
         // to relay on it and could signal wrong guarantees to users.
         let _ = self.signal_mailbox.send(signal);
     }
+
+    fn is_full(&self, priority: bool) -> bool {
+        match self.message_mailbox.as_ref() {
+            Some(mailbox) => mailbox.is_full(priority),
+            None => false,
+        }
+    }
 }
 
 /// Turns a `Future` into a process, enabling signals (e.g. kill).
@@ -233,6 +452,7 @@ pub(crate) async fn new<F, S, R>(
     env: Arc<dyn Environment>,
     signal_mailbox: Arc<Mutex<UnboundedReceiver<Signal>>>,
     message_mailbox: MessageMailbox,
+    usage: ProcessUsage,
 ) -> Result<S>
 where
     R: Into<ExecutionResult<S>>,
@@ -247,15 +467,18 @@ where
     let mut die_when_link_dies = true;
     // Process linked to this one
     let mut links = HashMap::new();
-    // TODO: Maybe wrapping this in some kind of `std::panic::catch_unwind` wold be a good idea,
-    //       to protect against panics in host function calls that unwind through Wasm code.
-    //       Currently a panic would just kill the task, but not notify linked processes.
+    // Processes monitoring this one
+    let mut monitors: HashMap<u64, Arc<dyn Process>> = HashMap::new();
+    // Fires once the process doesn't finish in time after receiving a `Shutdown` signal
+    let shutdown_timer = tokio::time::sleep(Duration::ZERO);
+    tokio::pin!(shutdown_timer);
+    let mut shutting_down = false;
     let mut signal_mailbox = signal_mailbox.lock().await;
     let mut has_sender = true;
-    #[cfg(all(feature = "metrics", not(feature = "detailed_metrics")))]
-    let labels: [(String, String); 0] = [];
-    #[cfg(all(feature = "metrics", feature = "detailed_metrics"))]
-    let labels = [("process_id", id.to_string())];
+    #[cfg(not(feature = "detailed_metrics"))]
+    let labels: MetricsLabels = [];
+    #[cfg(feature = "detailed_metrics")]
+    let labels: MetricsLabels = [("process_id", id.to_string())];
     let result = loop {
         tokio::select! {
             biased;
@@ -266,18 +489,9 @@ where
 
                 match signal.ok_or(()) {
                     Ok(Signal::Message(message)) => {
-
-                        #[cfg(feature = "metrics")]
-                        message.write_metrics();
-
-                        message_mailbox.push(message);
-
-                        // process metrics
-                        #[cfg(feature = "metrics")]
-                        metrics::increment_counter!("lunatic.process.messages.send", &labels);
-
-                        #[cfg(feature = "metrics")]
-                        metrics::gauge!("lunatic.process.messages.outstanding", message_mailbox.len() as f64, &labels);
+                        if let Some(finished) = deliver(&message_mailbox, message, &labels) {
+                            break finished;
+                        }
                     },
                     Ok(Signal::DieWhenLinkDies(value)) => die_when_link_dies = value,
                     // Put process into list of linked processes
@@ -296,6 +510,20 @@ where
                     }
                     // Exit loop and don't poll anymore the future if Signal::Kill received.
                     Ok(Signal::Kill) => break Finished::KillSignal,
+                    Ok(Signal::Exit(reason)) => break Finished::Exit(reason),
+                    // Let the process know that it should finish and kill it if it takes longer
+                    // than the timeout.
+                    Ok(Signal::Shutdown { timeout }) => {
+                        let deadline = Instant::now() + timeout;
+                        if !shutting_down || deadline < shutdown_timer.deadline() {
+                            shutdown_timer.as_mut().reset(deadline);
+                        }
+                        shutting_down = true;
+
+                        if let Some(finished) = deliver(&message_mailbox, Message::Shutdown, &labels) {
+                            break finished;
+                        }
+                    },
                     // Depending if `die_when_link_dies` is set, process will die or turn the
                     // signal into a message
                     Ok(Signal::LinkDied(id, tag, reason)) => {
@@ -304,39 +532,82 @@ where
                         #[cfg(feature = "metrics")]
                         metrics::gauge!("lunatic.process.links.alive", links.len() as f64, &labels);
                         match reason {
-                            DeathReason::Failure | DeathReason::NoProcess => {
+                            // In case a linked process finishes normally, don't do anything.
+                            DeathReason::Normal => {},
+                            reason => {
                                 if die_when_link_dies {
                                     // Even this was not a **kill** signal it has the same effect on
                                     // this process and should be propagated as such.
                                     break Finished::KillSignal
                                 } else {
-                                    let message = Message::LinkDied(tag);
-
-                                    #[cfg(feature = "metrics")]
-                                    metrics::increment_counter!("lunatic.process.messages.send", &labels);
-
-                                    #[cfg(feature = "metrics")]
-                                    metrics::gauge!("lunatic.process.messages.outstanding", message_mailbox.len() as f64, &labels);
-                                    message_mailbox.push(message);
+                                    let message = Message::LinkDied(tag, reason);
+                                    if let Some(finished) = deliver(&message_mailbox, message, &labels) {
+                                        break finished;
+                                    }
                                 }
                             },
-                            // In case a linked process finishes normally, don't do anything.
-                            DeathReason::Normal => {},
                         }
                     },
+                    // Put process into list of monitoring processes
+                    Ok(Signal::Monitor(proc)) => {
+                        monitors.insert(proc.id(), proc);
+
+                        #[cfg(feature = "metrics")]
+                        metrics::gauge!("lunatic.process.monitors.alive", monitors.len() as f64, &labels);
+                    },
+                    // Remove process from list
+                    Ok(Signal::Demonitor { process_id }) => {
+                        monitors.remove(&process_id);
+
+                        #[cfg(feature = "metrics")]
+                        metrics::gauge!("lunatic.process.monitors.alive", monitors.len() as f64, &labels);
+                    },
+                    // A monitored process died, this is always turned into a message
+                    Ok(Signal::ProcessDied(id)) => {
+                        let message = Message::ProcessDied(id);
+                        if let Some(finished) = deliver(&message_mailbox, message, &labels) {
+                            break finished;
+                        }
+                    },
+                    Ok(Signal::Info(reply)) => {
+                        let info = ProcessInfo {
+                            mailbox_len: message_mailbox.len(),
+                            links: links.len(),
+                            memory_size: usage.memory_size(),
+                            fuel_consumed: usage.fuel_consumed(),
+                            waiting_in_receive: message_mailbox.is_waiting(),
+                        };
+                        // The requesting side could have given up in the meantime
+                        let _ = reply.send(info);
+                    },
                     Err(_) => {
                         debug_assert!(has_sender);
                         has_sender = false;
                     }
                 }
             }
-            // Run process
-            output = &mut fut => { break Finished::Normal(output); }
+            // Shutdown timeout expired
+            _ = &mut shutdown_timer, if shutting_down => {
+                warn!("Process {} didn't finish in time after a shutdown request", id);
+                break Finished::KillSignal;
+            }
+            // Run process, a panic (e.g. inside of a host function) is turned into a failure
+            output = CatchUnwind(fut.as_mut()) => {
+                match output {
+                    Ok(output) => break Finished::Normal(output),
+                    Err(panic) => break Finished::Panic(panic_message(panic)),
+                }
+            }
         }
     };
 
     env.remove_process(id);
 
+    // Notify all monitoring processes, independent of the death reason
+    monitors.values().for_each(|proc| {
+        proc.send(Signal::ProcessDied(id));
+    });
+
     match result {
         Finished::Normal(result) => {
             let result = result.into();
@@ -354,8 +625,9 @@ where
                 );
                 debug!("{}", failure);
                 // Notify all links that we finished with an error
+                let reason = result.death_reason();
                 links.iter().for_each(|(_, (proc, tag))| {
-                    proc.send(Signal::LinkDied(id, *tag, DeathReason::Failure));
+                    proc.send(Signal::LinkDied(id, *tag, reason.clone()));
                 });
                 Err(anyhow!(failure.to_string()))
             } else {
@@ -374,13 +646,58 @@ where
             );
             // Notify all links that we finished because of a kill signal
             links.iter().for_each(|(_, (proc, tag))| {
-                proc.send(Signal::LinkDied(id, *tag, DeathReason::Failure));
+                proc.send(Signal::LinkDied(id, *tag, DeathReason::Kill));
             });
             Err(anyhow!("Process received Kill signal"))
         }
+        Finished::MailboxOverflow => {
+            warn!(
+                "Process {} was killed because of a mailbox overflow, notifying: {} links",
+                id,
+                links.len()
+            );
+            links.iter().for_each(|(_, (proc, tag))| {
+                proc.send(Signal::LinkDied(id, *tag, DeathReason::MailboxOverflow));
+            });
+            Err(anyhow!("Process mailbox overflowed"))
+        }
+        Finished::Panic(message) => {
+            warn!("Process {} panicked, notifying: {} links", id, links.len());
+            debug!("{}", message);
+            let reason = DeathReason::Failure(format!("Process panicked: {}", message));
+            links.iter().for_each(|(_, (proc, tag))| {
+                proc.send(Signal::LinkDied(id, *tag, reason.clone()));
+            });
+            Err(anyhow!("Process panicked: {}", message))
+        }
+        Finished::Exit(reason) => {
+            debug!(
+                "Process {} exited with reason: {}, notifying: {} links",
+                id,
+                reason,
+                links.len()
+            );
+            links.iter().for_each(|(_, (proc, tag))| {
+                proc.send(Signal::LinkDied(
+                    id,
+                    *tag,
+                    DeathReason::Exit(reason.clone()),
+                ));
+            });
+            Err(anyhow!("Process exited: {}", reason))
+        }
     }
 }
 
+/// Requests a snapshot of the process' state.
+///
+/// Returns `None` if the process finished before answering.
+pub async fn process_info(process: &dyn Process) -> Option<ProcessInfo> {
+    let (sender, receiver) = oneshot::channel();
+    process.send(Signal::Info(sender));
+    receiver.await.ok()
+}
+
 /// A process spawned from a native Rust closure.
 #[derive(Clone, Debug)]
 pub struct NativeProcess {
@@ -421,8 +738,16 @@ where
         signal_mailbox: signal_sender,
     };
     let fut = func(process.clone(), message_mailbox.clone());
+    env.add_process(id, Arc::new(process.clone()));
     let signal_mailbox = Arc::new(Mutex::new(signal_mailbox));
-    let join = tokio::task::spawn(new(fut, id, env.clone(), signal_mailbox, message_mailbox));
+    let join = tokio::task::spawn(new(
+        fut,
+        id,
+        env.clone(),
+        signal_mailbox,
+        message_mailbox,
+        ProcessUsage::default(),
+    ));
     (join, process)
 }
 
@@ -461,10 +786,19 @@ pub struct ExecutionResult<T> {
 impl<T> ExecutionResult<T> {
     // Returns the failure as `String` if the process failed.
     pub fn failure(&self) -> Option<&str> {
+        self.result.failure()
+    }
+
+    // Returns the reason of death that is reported to linked processes.
+    pub fn death_reason(&self) -> DeathReason {
         match self.result {
-            ResultValue::Failed(ref failure) => Some(failure),
-            ResultValue::SpawnError(ref failure) => Some(failure),
-            _ => None,
+            ResultValue::Ok => DeathReason::Normal,
+            ResultValue::Failed(ref failure) => DeathReason::Failure(failure.clone()),
+            ResultValue::SpawnError(ref failure) => DeathReason::SpawnError(failure.clone()),
+            ResultValue::Trap(ref failure) => DeathReason::Trap(failure.clone()),
+            ResultValue::OutOfFuel(_) => DeathReason::OutOfFuel,
+            ResultValue::MemoryLimit(_) => DeathReason::MemoryLimit,
+            ResultValue::ExitCode(code, _) => DeathReason::ExitCode(code),
         }
     }
 
@@ -493,9 +827,160 @@ where
     }
 }
 
+// All failure variants contain the full failure description (including the stacktrace) that is
+// used for logging.
 #[derive(PartialEq, Eq)]
 pub enum ResultValue {
     Ok,
     Failed(String),
     SpawnError(String),
+    Trap(String),
+    OutOfFuel(String),
+    MemoryLimit(String),
+    ExitCode(i32, String),
+}
+
+impl ResultValue {
+    // Returns the failure as `String` if the execution failed.
+    pub fn failure(&self) -> Option<&str> {
+        match self {
+            ResultValue::Ok => None,
+            ResultValue::Failed(failure)
+            | ResultValue::SpawnError(failure)
+            | ResultValue::Trap(failure)
+            | ResultValue::OutOfFuel(failure)
+            | ResultValue::MemoryLimit(failure)
+            | ResultValue::ExitCode(_, failure) => Some(failure),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::sync::oneshot;
+
+    use crate::{
+        env::LunaticEnvironment,
+        message::{DataMessage, Message},
+        spawn, DeathReason, Process, Signal,
+    };
+
+    #[tokio::test]
+    async fn panic_notifies_links() {
+        let env = Arc::new(LunaticEnvironment::new(1));
+
+        // Receives the `LinkDied` message instead of dying
+        let (sender, receiver) = oneshot::channel();
+        let (_, observer) = spawn(env.clone(), |this, mailbox| {
+            this.send(Signal::DieWhenLinkDies(false));
+            async move {
+                let message = mailbox.pop(None).await;
+                sender.send(message).unwrap();
+                Ok(())
+            }
+        });
+
+        let (join, _) = spawn(env, |this, _| {
+            this.send(Signal::Link(Some(42), Arc::new(observer)));
+            async move {
+                tokio::task::yield_now().await;
+                panic!("host function failed");
+                #[allow(unreachable_code)]
+                Ok(())
+            }
+        });
+
+        // The task itself finishes without panicking, but reports the failure
+        let result = join.await.expect("panic must be caught");
+        assert!(result.is_err());
+
+        match receiver.await.unwrap() {
+            Message::LinkDied(Some(42), DeathReason::Failure(message)) => {
+                assert!(message.contains("host function failed"))
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_kills_process_after_timeout() {
+        let env = Arc::new(LunaticEnvironment::new(1));
+
+        let (sender, receiver) = oneshot::channel();
+        let (join, process) = spawn(env, |_, mailbox| async move {
+            // Acknowledge the shutdown request, but keep running
+            let message = mailbox.pop(None).await;
+            sender.send(message).unwrap();
+            std::future::pending::<()>().await;
+            Ok(())
+        });
+
+        process.send(Signal::Shutdown {
+            timeout: Duration::from_millis(50),
+        });
+        assert!(matches!(receiver.await.unwrap(), Message::Shutdown));
+
+        let result = tokio::time::timeout(Duration::from_secs(5), join)
+            .await
+            .expect("process must be killed after the shutdown timeout")
+            .unwrap();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn monitor_receives_process_died() {
+        let env = Arc::new(LunaticEnvironment::new(1));
+
+        let (sender, receiver) = oneshot::channel();
+        let (_, observer) = spawn(env.clone(), |_, mailbox| async move {
+            let message = mailbox.pop(None).await;
+            sender.send(message).unwrap();
+            Ok(())
+        });
+
+        // Finishes normally once it receives a message
+        let (_, monitored) = spawn(env, |_, mailbox| async move {
+            mailbox.pop(None).await;
+            Ok(())
+        });
+        monitored.send(Signal::Monitor(Arc::new(observer)));
+        monitored.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+
+        match receiver.await.unwrap() {
+            Message::ProcessDied(id) => assert_eq!(id, monitored.id()),
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn demonitor_stops_process_died() {
+        let env = Arc::new(LunaticEnvironment::new(1));
+
+        let (sender, receiver) = oneshot::channel();
+        let (_, observer) = spawn(env.clone(), |_, mailbox| async move {
+            let message = mailbox.pop(None).await;
+            sender.send(message).unwrap();
+            Ok(())
+        });
+
+        let (join, monitored) = spawn(env, |_, mailbox| async move {
+            mailbox.pop(None).await;
+            Ok(())
+        });
+        monitored.send(Signal::Monitor(Arc::new(observer.clone())));
+        monitored.send(Signal::Demonitor {
+            process_id: observer.id(),
+        });
+        monitored.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+        join.await.unwrap().unwrap();
+
+        // A `ProcessDied` message would have been sent before the monitored process finished
+        observer.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+        match receiver.await.unwrap() {
+            Message::Data(_) => {}
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
 }
@@ -1,10 +1,14 @@
 use std::collections::VecDeque;
+use std::fmt::Debug;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Waker};
 
-use crate::message::Message;
+use crate::{
+    config::MailboxOverflow,
+    message::{Message, MessageSender},
+};
 
 /// The `MessageMailbox` is a data structure holding all messages of a process.
 ///
@@ -12,6 +16,14 @@ use crate::message::Message;
 /// this structure. The order of messages is preserved. This struct also implements the [`Future`]
 /// trait and `pop()` operations can be awaited on if the queue is empty.
 ///
+/// A mailbox can be bounded to a capacity. Once the capacity is reached, new messages are
+/// handled according to the [`MailboxOverflow`] policy.
+///
+/// Priority messages (see [`Message::is_priority`]) are kept in a separate queue that is always
+/// searched first. The priority queue is bounded by the same capacity and overflow policy, but
+/// independently of the regular queue, so that priority messages can't get stuck behind or be
+/// dropped because of regular messages.
+///
 /// ## Safety
 ///
 /// This should be cancellation safe and can be used inside `tokio::select!` statements:
@@ -24,12 +36,90 @@ pub struct MessageMailbox {
 #[derive(Default)]
 struct InnerMessageMailbox {
     waker: Option<Waker>,
-    tags: Option<Vec<i64>>,
+    selector: Selector,
     found: Option<Message>,
     messages: VecDeque<Message>,
+    priority_messages: VecDeque<Message>,
+    capacity: Option<usize>,
+    overflow: MailboxOverflow,
+}
+
+/// Selects which messages are returned by [`MessageMailbox::pop_selected`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Selector {
+    /// Any message.
+    #[default]
+    Any,
+    /// Messages tagged with any of the tags.
+    Tags(Vec<i64>),
+    /// Data messages not tagged with any of the tags.
+    DataExceptTags(Vec<i64>),
+    /// `LinkDied` messages, if tags are given only the ones tagged with any of them.
+    LinkDied(Option<Vec<i64>>),
+    /// Data messages sent by the process.
+    Sender(MessageSender),
+}
+
+impl Selector {
+    pub fn from_tags(tags: Option<&[i64]>) -> Self {
+        match tags {
+            Some(tags) => Selector::Tags(tags.into()),
+            None => Selector::Any,
+        }
+    }
+
+    /// Returns true if the message is selected.
+    pub fn matches(&self, message: &Message) -> bool {
+        match self {
+            Selector::Any => true,
+            // Only consider messages that also have a tag.
+            Selector::Tags(tags) => match message.tag() {
+                Some(tag) => tags.contains(&tag),
+                None => false,
+            },
+            Selector::DataExceptTags(tags) => match message {
+                Message::Data(data) => match data.tag {
+                    Some(tag) => !tags.contains(&tag),
+                    None => true,
+                },
+                _ => false,
+            },
+            Selector::LinkDied(tags) => match (message, tags) {
+                (Message::LinkDied(_, _), None) => true,
+                (Message::LinkDied(Some(tag), _), Some(tags)) => tags.contains(tag),
+                _ => false,
+            },
+            Selector::Sender(sender) => message.sender() == Some(*sender),
+        }
+    }
+}
+
+/// The result of pushing a message into the mailbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushResult {
+    /// The message was delivered.
+    Delivered,
+    /// The mailbox was full and a message was dropped.
+    Dropped,
+    /// The mailbox was full and the receiving process should be killed.
+    Overflow,
 }
 
 impl MessageMailbox {
+    /// Creates a mailbox that can hold at most `capacity` messages.
+    ///
+    /// If `capacity` is `None`, the mailbox is unbounded.
+    pub fn new(capacity: Option<usize>, overflow: MailboxOverflow) -> Self {
+        let inner = InnerMessageMailbox {
+            capacity,
+            overflow,
+            ..Default::default()
+        };
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
     /// Return message in FIFO order from mailbox.
     ///
     /// If function is called with a `tags` value different from None, it will only return the first
@@ -37,6 +127,13 @@ impl MessageMailbox {
     ///
     /// If no message exist, blocks until a message is received.
     pub async fn pop(&self, tags: Option<&[i64]>) -> Message {
+        self.pop_selected(Selector::from_tags(tags)).await
+    }
+
+    /// Return the first message matching the selector, in FIFO order.
+    ///
+    /// If no matching message exist, blocks until one is received.
+    pub async fn pop_selected(&self, selector: Selector) -> Message {
         // Mailbox lock must be released before .await
         {
             let mut mailbox = self.inner.lock().expect("only accessed by one process");
@@ -44,31 +141,25 @@ impl MessageMailbox {
             // If a found message exists here, it means that the previous `.await` was canceled
             // after a `wake()` call. To not lose this message it should be put into the queue.
             if let Some(found) = mailbox.found.take() {
-                mailbox.messages.push_back(found);
+                mailbox.queue(&found).push_back(found);
             }
 
-            // When looking for specific tags, loop through all messages to check for it
-            if let Some(tags) = tags {
-                let index = mailbox.messages.iter().position(|x| {
-                    // Only consider messages that also have a tag.
-                    if let Some(tag) = x.tag() {
-                        tags.contains(&tag)
-                    } else {
-                        false
-                    }
-                });
-                // If message matching tags is found, remove it.
+            // Loop through all messages to find the first one matching the selector, priority
+            // messages are always considered first.
+            let InnerMessageMailbox {
+                priority_messages,
+                messages,
+                ..
+            } = &mut *mailbox;
+            for queue in [priority_messages, messages] {
+                let index = queue.iter().position(|message| selector.matches(message));
+                // If a matching message is found, remove it.
                 if let Some(index) = index {
-                    return mailbox.messages.remove(index).expect("must exist");
-                }
-            } else {
-                // If not looking for a specific tags try to pop the first message available.
-                if let Some(message) = mailbox.messages.pop_front() {
-                    return message;
+                    return queue.remove(index).expect("must exist");
                 }
             }
-            // Mark the tags to wait on.
-            mailbox.tags = tags.map(|tags| tags.into());
+            // Mark the selector to wait on.
+            mailbox.selector = selector;
         }
         self.await
     }
@@ -100,57 +191,125 @@ impl MessageMailbox {
             // If a found message exists here, it means that the previous `.await` was canceled
             // after a `wake()` call. To not lose this message it should be put into the queue.
             if let Some(found) = mailbox.found.take() {
-                mailbox.messages.push_back(found);
+                mailbox.queue(&found).push_back(found);
             }
 
             // Mark the tags to wait on.
-            mailbox.tags = tags.map(|tags| tags.into());
+            mailbox.selector = Selector::from_tags(tags);
         }
         self.await
     }
 
+    /// Blocks until at least one message is available, without removing it from the mailbox.
+    ///
+    /// The message is put back at the front of the queue and will be returned by the next `pop`.
+    pub async fn wait_for_message(&self) {
+        let message = self.pop(None).await;
+        let mut mailbox = self.inner.lock().expect("only accessed by one process");
+        mailbox.queue(&message).push_front(message);
+    }
+
     /// Pushes a message into the mailbox.
     ///
     /// If the message is being .awaited on, this call will immediately notify the waker that it's
     /// ready, otherwise it will push it at the end of the queue.
-    pub fn push(&self, message: Message) {
+    ///
+    /// If the queue is full, the overflow policy decides the outcome. With the `Backpressure`
+    /// policy the message is still queued, because senders are expected to check
+    /// [`is_full`](Self::is_full) before sending and only messages already in flight can arrive.
+    pub fn push(&self, message: Message) -> PushResult {
         let mut mailbox = self.inner.lock().expect("only accessed by one process");
         // If waiting on a new message notify executor that it arrived.
         if let Some(waker) = mailbox.waker.take() {
-            // If waiting on specific tags only notify if tags are matched, otherwise forward every message.
-            // Note that because of the short-circuit rule in Rust it's safe to use `unwrap()` here.
-            if mailbox.tags.is_none()
-                || (message.tag().is_some()
-                    && mailbox
-                        .tags
-                        .as_ref()
-                        .unwrap()
-                        .contains(&message.tag().unwrap()))
-            {
+            // Only notify if the message is matched by the selector waited on.
+            if mailbox.selector.matches(&message) {
                 mailbox.found = Some(message);
                 waker.wake();
-                return;
+                return PushResult::Delivered;
             } else {
                 // Put the waker back if this is not the message we are looking for.
                 mailbox.waker = Some(waker);
             }
         }
-        // Otherwise put message into queue
-        mailbox.messages.push_back(message);
+        // Otherwise put message into its queue, if there is space for it
+        if mailbox.is_full(message.is_priority()) {
+            match mailbox.overflow {
+                MailboxOverflow::DropNewest => return PushResult::Dropped,
+                MailboxOverflow::DropOldest => {
+                    let queue = mailbox.queue(&message);
+                    queue.pop_front();
+                    queue.push_back(message);
+                    return PushResult::Dropped;
+                }
+                MailboxOverflow::KillReceiver => return PushResult::Overflow,
+                MailboxOverflow::Backpressure => {}
+            }
+        }
+        mailbox.queue(&message).push_back(message);
+        PushResult::Delivered
+    }
+
+    /// Returns true if the mailbox is bounded and senders should hold back new messages.
+    ///
+    /// Priority and regular messages are bounded separately, `priority` selects the queue that is
+    /// checked.
+    pub fn is_full(&self, priority: bool) -> bool {
+        let mailbox = self.inner.lock().expect("only accessed by one process");
+
+        mailbox.overflow == MailboxOverflow::Backpressure && mailbox.is_full(priority)
     }
 
     /// Returns the number of messages currently available
     pub fn len(&self) -> usize {
         let mailbox = self.inner.lock().expect("only accessed by one process");
 
-        mailbox.messages.len()
+        mailbox.messages.len() + mailbox.priority_messages.len()
+    }
+
+    /// Returns true if the process is waiting on a new message
+    pub fn is_waiting(&self) -> bool {
+        let mailbox = self.inner.lock().expect("only accessed by one process");
+
+        mailbox.waker.is_some()
     }
 
     /// Returns true if the mailbox has no available messages
     pub fn is_empty(&self) -> bool {
         let mailbox = self.inner.lock().expect("only accessed by one process");
 
-        mailbox.messages.is_empty()
+        mailbox.messages.is_empty() && mailbox.priority_messages.is_empty()
+    }
+}
+
+impl InnerMessageMailbox {
+    // Returns the queue the message belongs to.
+    fn queue(&mut self, message: &Message) -> &mut VecDeque<Message> {
+        if message.is_priority() {
+            &mut self.priority_messages
+        } else {
+            &mut self.messages
+        }
+    }
+
+    // Returns true if the priority or the regular queue reached the capacity.
+    fn is_full(&self, priority: bool) -> bool {
+        let queue = if priority {
+            &self.priority_messages
+        } else {
+            &self.messages
+        };
+        match self.capacity {
+            Some(capacity) => queue.len() >= capacity,
+            None => false,
+        }
+    }
+}
+
+impl Debug for MessageMailbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MessageMailbox")
+            .field("len", &self.len())
+            .finish()
     }
 }
 
@@ -176,16 +335,21 @@ mod tests {
         task::{Context, Poll, Wake},
     };
 
-    use super::{Message, MessageMailbox};
+    use super::{Message, MessageMailbox, PushResult, Selector};
+    use crate::{
+        config::MailboxOverflow,
+        message::{DataMessage, MessageSender},
+        DeathReason,
+    };
 
     #[tokio::test]
     async fn no_tags_signal_message() {
         let mailbox = MessageMailbox::default();
-        let message = Message::LinkDied(None);
+        let message = Message::LinkDied(None, DeathReason::Failure(String::new()));
         mailbox.push(message);
         let result = mailbox.pop(None).await;
         match result {
-            Message::LinkDied(None) => (),
+            Message::LinkDied(None, _) => (),
             _ => panic!("Wrong message received"),
         }
     }
@@ -194,7 +358,7 @@ mod tests {
     async fn tag_signal_message() {
         let mailbox = MessageMailbox::default();
         let tag = 1337;
-        let message = Message::LinkDied(Some(tag));
+        let message = Message::LinkDied(Some(tag), DeathReason::Failure(String::new()));
         mailbox.push(message);
         let message = mailbox.pop(None).await;
         assert_eq!(message.tag(), Some(tag));
@@ -208,11 +372,26 @@ mod tests {
         let tag3 = 3;
         let tag4 = 4;
         let tag5 = 5;
-        mailbox.push(Message::LinkDied(Some(tag1)));
-        mailbox.push(Message::LinkDied(Some(tag2)));
-        mailbox.push(Message::LinkDied(Some(tag3)));
-        mailbox.push(Message::LinkDied(Some(tag4)));
-        mailbox.push(Message::LinkDied(Some(tag5)));
+        mailbox.push(Message::LinkDied(
+            Some(tag1),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag2),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag3),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag4),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag5),
+            DeathReason::Failure(String::new()),
+        ));
         let message = mailbox.pop(Some(&[tag2])).await;
         assert_eq!(message.tag(), Some(tag2));
         let message = mailbox.pop(Some(&[tag1])).await;
@@ -234,11 +413,26 @@ mod tests {
         let tag3 = 3;
         let tag4 = 4;
         let tag5 = 5;
-        mailbox.push(Message::LinkDied(Some(tag1)));
-        mailbox.push(Message::LinkDied(Some(tag2)));
-        mailbox.push(Message::LinkDied(Some(tag3)));
-        mailbox.push(Message::LinkDied(Some(tag4)));
-        mailbox.push(Message::LinkDied(Some(tag5)));
+        mailbox.push(Message::LinkDied(
+            Some(tag1),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag2),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag3),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag4),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(tag5),
+            DeathReason::Failure(String::new()),
+        ));
         let message = mailbox.pop(Some(&[tag2, tag1, tag3])).await;
         assert_eq!(message.tag(), Some(tag1));
         let message = mailbox.pop(Some(&[tag2, tag1, tag3])).await;
@@ -274,12 +468,15 @@ mod tests {
         // Request tags None
         let fut = mailbox.pop(None);
         let mut fut = Box::pin(fut);
+        assert!(!mailbox.is_waiting());
         // First poll will block
         let result = fut.as_mut().poll(&mut context);
         assert!(result.is_pending());
         assert!(!*waker_ref.0.lock().unwrap());
+        assert!(mailbox.is_waiting());
         // Pushing a message to the mailbox will call the waker
-        mailbox.push(Message::LinkDied(tags));
+        mailbox.push(Message::LinkDied(tags, DeathReason::Failure(String::new())));
+        assert!(!mailbox.is_waiting());
         assert!(*waker_ref.0.lock().unwrap());
         // Next poll will return the value
         let result = fut.as_mut().poll(&mut context);
@@ -302,15 +499,18 @@ mod tests {
         assert!(result.is_pending());
         assert!(!*waker_ref.0.lock().unwrap());
         // Pushing a message with the `None` tags should not trigger the waker
-        mailbox.push(Message::LinkDied(None));
+        mailbox.push(Message::LinkDied(None, DeathReason::Failure(String::new())));
         assert!(!*waker_ref.0.lock().unwrap());
         // Next poll will still not have the value with the tags 1337
         let result = fut.as_mut().poll(&mut context);
         assert!(result.is_pending());
         // Pushing another None in the meantime should not remove the waker
-        mailbox.push(Message::LinkDied(None));
+        mailbox.push(Message::LinkDied(None, DeathReason::Failure(String::new())));
         // Pushing a message with tags 1337 should trigger the waker
-        mailbox.push(Message::LinkDied(Some(1337)));
+        mailbox.push(Message::LinkDied(
+            Some(1337),
+            DeathReason::Failure(String::new()),
+        ));
         assert!(*waker_ref.0.lock().unwrap());
         // Next poll will have the message ready
         let result = fut.as_mut().poll(&mut context);
@@ -332,7 +532,7 @@ mod tests {
         assert!(result.is_pending());
         assert!(!*waker_ref.0.lock().unwrap());
         // Pushing a message with the `None` tags should call the waker()
-        mailbox.push(Message::LinkDied(None));
+        mailbox.push(Message::LinkDied(None, DeathReason::Failure(String::new())));
         assert!(*waker_ref.0.lock().unwrap());
         // Dropping the future will cancel it
         drop(fut);
@@ -346,8 +546,237 @@ mod tests {
         tokio::pin!(fut);
         let result = fut.poll(&mut context);
         match result {
-            Poll::Ready(Message::LinkDied(tags)) => assert_eq!(tags, None),
+            Poll::Ready(Message::LinkDied(tags, DeathReason::Failure(_))) => assert_eq!(tags, None),
             _ => panic!("Unexpected message"),
         }
     }
+
+    #[tokio::test]
+    async fn drop_newest_when_full() {
+        let mailbox = MessageMailbox::new(Some(2), MailboxOverflow::DropNewest);
+        for tag in 1..=2 {
+            let result = mailbox.push(Message::LinkDied(
+                Some(tag),
+                DeathReason::Failure(String::new()),
+            ));
+            assert_eq!(result, PushResult::Delivered);
+        }
+        let result = mailbox.push(Message::LinkDied(
+            Some(3),
+            DeathReason::Failure(String::new()),
+        ));
+        assert_eq!(result, PushResult::Dropped);
+        assert_eq!(mailbox.len(), 2);
+        assert_eq!(mailbox.pop(None).await.tag(), Some(1));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_when_full() {
+        let mailbox = MessageMailbox::new(Some(2), MailboxOverflow::DropOldest);
+        for tag in 1..=2 {
+            mailbox.push(Message::LinkDied(
+                Some(tag),
+                DeathReason::Failure(String::new()),
+            ));
+        }
+        let result = mailbox.push(Message::LinkDied(
+            Some(3),
+            DeathReason::Failure(String::new()),
+        ));
+        assert_eq!(result, PushResult::Dropped);
+        assert_eq!(mailbox.len(), 2);
+        assert_eq!(mailbox.pop(None).await.tag(), Some(2));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(3));
+    }
+
+    #[test]
+    fn overflow_when_full() {
+        let mailbox = MessageMailbox::new(Some(1), MailboxOverflow::KillReceiver);
+        mailbox.push(Message::LinkDied(
+            Some(1),
+            DeathReason::Failure(String::new()),
+        ));
+        assert!(!mailbox.is_full(false));
+        let result = mailbox.push(Message::LinkDied(
+            Some(2),
+            DeathReason::Failure(String::new()),
+        ));
+        assert_eq!(result, PushResult::Overflow);
+    }
+
+    #[test]
+    fn backpressure_when_full() {
+        let mailbox = MessageMailbox::new(Some(1), MailboxOverflow::Backpressure);
+        assert!(!mailbox.is_full(false));
+        mailbox.push(Message::LinkDied(
+            Some(1),
+            DeathReason::Failure(String::new()),
+        ));
+        assert!(mailbox.is_full(false));
+        // Messages already in flight are still delivered
+        let result = mailbox.push(Message::LinkDied(
+            Some(2),
+            DeathReason::Failure(String::new()),
+        ));
+        assert_eq!(result, PushResult::Delivered);
+        assert_eq!(mailbox.len(), 2);
+    }
+
+    #[test]
+    fn priority_backpressure_when_full() {
+        let mailbox = MessageMailbox::new(Some(1), MailboxOverflow::Backpressure);
+        let mut message = DataMessage::new(Some(1), 0);
+        message.priority = true;
+        mailbox.push(Message::Data(message));
+        // The priority queue is full, but regular messages can still be sent
+        assert!(mailbox.is_full(true));
+        assert!(!mailbox.is_full(false));
+        mailbox.push(Message::Data(DataMessage::new(Some(2), 0)));
+        assert!(mailbox.is_full(false));
+    }
+
+    #[tokio::test]
+    async fn priority_messages_are_bounded() {
+        let priority = |tag| {
+            let mut message = DataMessage::new(Some(tag), 0);
+            message.priority = true;
+            Message::Data(message)
+        };
+        let mailbox = MessageMailbox::new(Some(1), MailboxOverflow::DropOldest);
+        mailbox.push(Message::Data(DataMessage::new(Some(1), 0)));
+        // A full regular queue doesn't hold back priority messages
+        assert_eq!(mailbox.push(priority(2)), PushResult::Delivered);
+        // But they are limited by the same capacity and overflow policy
+        assert_eq!(mailbox.push(priority(3)), PushResult::Dropped);
+        assert_eq!(mailbox.len(), 2);
+        assert_eq!(mailbox.pop(None).await.tag(), Some(3));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(1));
+
+        let mailbox = MessageMailbox::new(Some(1), MailboxOverflow::KillReceiver);
+        mailbox.push(priority(1));
+        assert_eq!(mailbox.push(priority(2)), PushResult::Overflow);
+        // Priority messages don't take space from regular ones
+        let result = mailbox.push(Message::Data(DataMessage::new(Some(3), 0)));
+        assert_eq!(result, PushResult::Delivered);
+    }
+
+    #[tokio::test]
+    async fn wait_for_message_keeps_message() {
+        let mailbox = MessageMailbox::default();
+        let waiting = mailbox.clone();
+        let waiter = tokio::spawn(async move { waiting.wait_for_message().await });
+        // Let the waiter block on the empty mailbox
+        tokio::task::yield_now().await;
+        assert!(mailbox.is_waiting());
+        mailbox.push(Message::LinkDied(
+            Some(1),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::LinkDied(
+            Some(2),
+            DeathReason::Failure(String::new()),
+        ));
+        waiter.await.unwrap();
+        // The order of messages is preserved
+        assert_eq!(mailbox.len(), 2);
+        assert_eq!(mailbox.pop(None).await.tag(), Some(1));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn selectors() {
+        let mailbox = MessageMailbox::default();
+        mailbox.push(Message::LinkDied(
+            Some(1),
+            DeathReason::Failure(String::new()),
+        ));
+        mailbox.push(Message::Data(DataMessage::new(Some(1), 0)));
+        let mut message = DataMessage::new(Some(2), 0);
+        message.sender = Some(MessageSender {
+            node_id: 0,
+            process_id: 42,
+        });
+        mailbox.push(Message::Data(message));
+        mailbox.push(Message::Data(DataMessage::new(None, 0)));
+
+        let sender = MessageSender {
+            node_id: 0,
+            process_id: 42,
+        };
+        let message = mailbox.pop_selected(Selector::Sender(sender)).await;
+        assert_eq!(message.tag(), Some(2));
+        let message = mailbox
+            .pop_selected(Selector::DataExceptTags(vec![1]))
+            .await;
+        assert!(matches!(message, Message::Data(_)));
+        assert_eq!(message.tag(), None);
+        let message = mailbox.pop_selected(Selector::LinkDied(None)).await;
+        assert!(matches!(message, Message::LinkDied(Some(1), _)));
+        assert_eq!(mailbox.len(), 1);
+    }
+
+    #[test]
+    fn waiting_on_selector() {
+        let mailbox = MessageMailbox::default();
+        let waker = FlagWaker(Arc::new(Mutex::new(false)));
+        let waker_ref = waker.clone();
+        let waker = &Arc::new(waker).into();
+        let mut context = Context::from_waker(waker);
+        let fut = mailbox.pop_selected(Selector::LinkDied(Some(vec![1337])));
+        let mut fut = Box::pin(fut);
+        let result = fut.as_mut().poll(&mut context);
+        assert!(result.is_pending());
+        // A data message with the same tag should not trigger the waker
+        mailbox.push(Message::Data(DataMessage::new(Some(1337), 0)));
+        assert!(!*waker_ref.0.lock().unwrap());
+        mailbox.push(Message::LinkDied(
+            Some(1337),
+            DeathReason::Failure(String::new()),
+        ));
+        assert!(*waker_ref.0.lock().unwrap());
+        let result = fut.as_mut().poll(&mut context);
+        assert!(matches!(
+            result,
+            Poll::Ready(Message::LinkDied(Some(1337), _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn priority_messages_first() {
+        let mailbox = MessageMailbox::new(Some(2), MailboxOverflow::DropNewest);
+        mailbox.push(Message::Data(DataMessage::new(Some(1), 0)));
+        mailbox.push(Message::Data(DataMessage::new(Some(2), 0)));
+        // Priority messages are queued separately and aren't limited by the regular messages
+        let mut message = DataMessage::new(Some(3), 0);
+        message.priority = true;
+        assert_eq!(mailbox.push(Message::Data(message)), PushResult::Delivered);
+        assert_eq!(mailbox.push(Message::Shutdown), PushResult::Delivered);
+        assert_eq!(mailbox.len(), 4);
+
+        assert_eq!(mailbox.pop(None).await.tag(), Some(3));
+        assert!(matches!(mailbox.pop(None).await, Message::Shutdown));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(1));
+        assert_eq!(mailbox.pop(None).await.tag(), Some(2));
+    }
+
+    #[test]
+    fn priority_cancellation_safety() {
+        let mailbox = MessageMailbox::default();
+        let waker = FlagWaker(Arc::new(Mutex::new(false)));
+        let waker = &Arc::new(waker).into();
+        let mut context = Context::from_waker(waker);
+        let fut = mailbox.pop(None);
+        let mut fut = Box::pin(fut);
+        assert!(fut.as_mut().poll(&mut context).is_pending());
+        // The priority message is handed over to the waiting future, that is canceled
+        mailbox.push(Message::Shutdown);
+        drop(fut);
+        mailbox.push(Message::Data(DataMessage::new(Some(1), 0)));
+        // The message is put back into the priority queue and is still delivered first
+        let fut = mailbox.pop(None);
+        tokio::pin!(fut);
+        let result = fut.poll(&mut context);
+        assert!(matches!(result, Poll::Ready(Message::Shutdown)));
+    }
 }
@@ -1,41 +1,76 @@
 /*!
 The [`Message`] is a special variant of a [`Signal`](crate::Signal) that can be sent to
 processes. The most common kind of Message is a [`DataMessage`], but there are also some special
-kinds of messages, like the [`Message::LinkDied`], that is received if a linked process dies.
+kinds of messages, like the [`Message::LinkDied`], that is received if a linked process dies,
+the [`Message::ProcessDied`], that is received if a monitored process dies, or the
+[`Message::Shutdown`], that is received if the process is asked to finish.
 */
 
 use std::{
     any::Any,
     fmt::Debug,
     io::{Read, Write},
+    ops::Deref,
     sync::Arc,
 };
 
 use lunatic_networking_api::{TcpConnection, TlsConnection};
+use serde::{Deserialize, Serialize};
 use tokio::net::UdpSocket;
 
-use crate::runtimes::wasmtime::WasmtimeCompiledModule;
+use crate::{
+    channel::{ChannelReceiver, ChannelSender},
+    runtimes::wasmtime::WasmtimeCompiledModule,
+    DeathReason,
+};
 
 pub type Resource = dyn Any + Send + Sync;
 
 /// Can be sent between processes by being embedded into a  [`Signal::Message`][0]
 ///
-/// A [`Message`] has 2 variants:
+/// A [`Message`] has 4 variants:
 /// * Data - Regular message containing a tag, buffer and resources.
-/// * LinkDied - A `LinkDied` signal that was turned into a message.
+/// * LinkDied - A `LinkDied` signal that was turned into a message. Contains the link tag and the
+///   reason of the linked process' death.
+/// * ProcessDied - A `ProcessDied` signal that was turned into a message. Contains the id of the
+///   monitored process.
+/// * Shutdown - A `Shutdown` signal that was turned into a message. The process should finish
+///   before the shutdown timeout expires.
 ///
 /// [0]: crate::Signal
 #[derive(Debug)]
 pub enum Message {
     Data(DataMessage),
-    LinkDied(Option<i64>),
+    LinkDied(Option<i64>, DeathReason),
+    ProcessDied(u64),
+    Shutdown,
 }
 
 impl Message {
     pub fn tag(&self) -> Option<i64> {
         match self {
             Message::Data(message) => message.tag,
-            Message::LinkDied(tag) => *tag,
+            Message::LinkDied(tag, _) => *tag,
+            Message::ProcessDied(_) => None,
+            Message::Shutdown => None,
+        }
+    }
+
+    /// Returns true if the message should be delivered before regular messages.
+    ///
+    /// Shutdown requests are always delivered with priority.
+    pub fn is_priority(&self) -> bool {
+        match self {
+            Message::Data(message) => message.priority,
+            Message::Shutdown => true,
+            _ => false,
+        }
+    }
+
+    pub fn sender(&self) -> Option<MessageSender> {
+        match self {
+            Message::Data(message) => message.sender,
+            _ => None,
         }
     }
 
@@ -43,13 +78,49 @@ impl Message {
     pub fn write_metrics(&self) {
         match self {
             Message::Data(message) => message.write_metrics(),
-            Message::LinkDied(_) => {
+            Message::LinkDied(_, _) => {
                 metrics::increment_counter!("lunatic.process.messages.link_died.count");
             }
+            Message::ProcessDied(_) => {
+                metrics::increment_counter!("lunatic.process.messages.process_died.count");
+            }
+            Message::Shutdown => {
+                metrics::increment_counter!("lunatic.process.messages.shutdown.count");
+            }
         }
     }
 }
 
+/// An immutable byte buffer that can be attached to messages as a resource.
+///
+/// Buffers are reference counted and only the `Arc` is moved between processes, the content is
+/// never copied while the buffer travels inside of messages.
+#[derive(Debug)]
+pub struct SharedBuffer(Box<[u8]>);
+
+impl SharedBuffer {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self(data.into_boxed_slice())
+    }
+}
+
+impl Deref for SharedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Identifies the process that sent a [`DataMessage`].
+///
+/// The node id is 0 if the sending node is not part of a distributed cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageSender {
+    pub node_id: u64,
+    pub process_id: u64,
+}
+
 /// A variant of a [`Message`] that has a buffer of data and resources attached to it.
 ///
 /// It implements the [`Read`](std::io::Read) and [`Write`](std::io::Write) traits.
@@ -57,6 +128,10 @@ impl Message {
 pub struct DataMessage {
     // TODO: Only the Node implementation depends on these fields being public.
     pub tag: Option<i64>,
+    // The sending process, if it's known.
+    pub sender: Option<MessageSender>,
+    // Priority messages are delivered before regular ones.
+    pub priority: bool,
     pub read_ptr: usize,
     pub buffer: Vec<u8>,
     pub resources: Vec<Option<Arc<Resource>>>,
@@ -67,6 +142,8 @@ impl DataMessage {
     pub fn new(tag: Option<i64>, buffer_capacity: usize) -> Self {
         Self {
             tag,
+            sender: None,
+            priority: false,
             read_ptr: 0,
             buffer: Vec::with_capacity(buffer_capacity),
             resources: Vec::new(),
@@ -77,12 +154,36 @@ impl DataMessage {
     pub fn new_from_vec(tag: Option<i64>, buffer: Vec<u8>) -> Self {
         Self {
             tag,
+            sender: None,
+            priority: false,
             read_ptr: 0,
             buffer,
             resources: Vec::new(),
         }
     }
 
+    /// Creates a copy of the message that shares its resources with the original one.
+    ///
+    /// Only shared buffers can have multiple owners, returns `None` if the message carries any
+    /// other resource, like a TCP stream or a channel receiver.
+    pub fn try_clone_shared(&self) -> Option<Self> {
+        let shared = self.resources.iter().all(|resource| match resource {
+            Some(resource) => resource.is::<SharedBuffer>(),
+            None => true,
+        });
+        if !shared {
+            return None;
+        }
+        Some(Self {
+            tag: self.tag,
+            sender: self.sender,
+            priority: self.priority,
+            read_ptr: self.read_ptr,
+            buffer: self.buffer.clone(),
+            resources: self.resources.clone(),
+        })
+    }
+
     /// Adds a resource to the message and returns the index of it inside of the message.
     ///
     /// The resource is `Any` and is downcasted when accessing later.
@@ -126,6 +227,30 @@ impl DataMessage {
         self.take_downcast(index)
     }
 
+    /// Takes a channel sender from the message, but preserves the indexes of all others.
+    ///
+    /// If the index is out of bound or the resource is not a channel sender the function will
+    /// return None.
+    pub fn take_channel_sender(&mut self, index: usize) -> Option<Arc<ChannelSender>> {
+        self.take_downcast(index)
+    }
+
+    /// Takes a channel receiver from the message, but preserves the indexes of all others.
+    ///
+    /// If the index is out of bound or the resource is not a channel receiver the function will
+    /// return None.
+    pub fn take_channel_receiver(&mut self, index: usize) -> Option<Arc<ChannelReceiver>> {
+        self.take_downcast(index)
+    }
+
+    /// Takes a shared buffer from the message, but preserves the indexes of all others.
+    ///
+    /// If the index is out of bound or the resource is not a shared buffer the function will
+    /// return None.
+    pub fn take_shared_buffer(&mut self, index: usize) -> Option<Arc<SharedBuffer>> {
+        self.take_downcast(index)
+    }
+
     /// Moves read pointer to index.
     pub fn seek(&mut self, index: usize) {
         self.read_ptr = index;
@@ -0,0 +1,378 @@
+use std::{collections::HashSet, ops::Deref, sync::Arc};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+
+use crate::{message::DataMessage, message::Message, Process, Signal};
+
+/// The maximum size of metadata that can be stored next to a registration.
+pub const MAX_METADATA_SIZE: usize = 256;
+
+/// A process registered under a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub node_id: u64,
+    pub process_id: u64,
+    /// The process that registered the name. Permanent registrations don't have an owner.
+    pub owner: Option<u64>,
+    /// Opaque data describing the process, at most [`MAX_METADATA_SIZE`] bytes.
+    pub metadata: Vec<u8>,
+}
+
+/// Names of processes inside of an environment.
+///
+/// Registrations with an owner are removed by the environment when the owner exits, so that
+/// names don't keep pointing to processes that crashed.
+///
+/// Processes can watch names. Each time a watched name is registered or removed, the watcher
+/// receives a data message with the tag it picked when it started watching. The first byte of
+/// the message is 0 if the name was registered and 1 if it was removed. If it was registered,
+/// the node ID and process ID follow as little-endian `u64`s, followed by the metadata.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessRegistry {
+    names: Arc<DashMap<String, Registration>>,
+    // Names registered by each process, so that they can be removed when the process exits.
+    owned: Arc<DashMap<u64, HashSet<String>>>,
+    watchers: Arc<DashMap<String, Vec<Watcher>>>,
+    // Names watched by each process, so that watches can be removed when the process exits.
+    watching: Arc<DashMap<u64, HashSet<String>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Watcher {
+    process: Arc<dyn Process>,
+    tag: Option<i64>,
+}
+
+impl ProcessRegistry {
+    /// Registers a process under `name`, replacing any previous registration. Returns the
+    /// replaced registration.
+    pub fn put(&self, name: &str, registration: Registration) -> Option<Registration> {
+        let owner = registration.owner;
+        self.own(name, owner);
+        // Watchers are notified while the entry is locked, so that they observe changes in order.
+        let previous = match self.names.entry(name.to_owned()) {
+            Entry::Occupied(mut entry) => {
+                let previous = entry.insert(registration);
+                self.notify(name, Some(entry.get()));
+                Some(previous)
+            }
+            Entry::Vacant(entry) => {
+                let entry = entry.insert(registration);
+                self.notify(name, Some(&entry));
+                None
+            }
+        };
+        if let Some(previous) = &previous {
+            if previous.owner != owner {
+                self.disown(name, previous.owner);
+            }
+        }
+        previous
+    }
+
+    /// Registers a process under `name`, but only if the name is not registered yet. Returns
+    /// false if the name is already taken.
+    pub fn put_if_absent(&self, name: &str, registration: Registration) -> bool {
+        match self.names.entry(name.to_owned()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                self.own(name, registration.owner);
+                let entry = entry.insert(registration);
+                self.notify(name, Some(&entry));
+                true
+            }
+        }
+    }
+
+    /// Replaces the registration under `name`, but only if the name is currently registered to
+    /// the process `expected` (node ID and process ID). Returns false if it's not.
+    pub fn compare_and_swap(
+        &self,
+        name: &str,
+        expected: (u64, u64),
+        registration: Registration,
+    ) -> bool {
+        let owner = registration.owner;
+        let previous_owner = match self.names.get_mut(name) {
+            Some(mut current) if (current.node_id, current.process_id) == expected => {
+                self.own(name, owner);
+                let previous = std::mem::replace(&mut *current, registration);
+                self.notify(name, Some(&current));
+                previous.owner
+            }
+            _ => return false,
+        };
+        if previous_owner != owner {
+            self.disown(name, previous_owner);
+        }
+        true
+    }
+
+    /// Returns the registration under `name`.
+    ///
+    /// The entry stays locked while the returned value is held, it can't be replaced or removed
+    /// in the meantime.
+    pub fn get(&self, name: &str) -> Option<impl Deref<Target = Registration> + '_> {
+        self.names.get(name)
+    }
+
+    /// Returns all registrations with names starting with `prefix`, ordered by name.
+    pub fn list(&self, prefix: &str) -> Vec<(String, Registration)> {
+        let mut registrations: Vec<(String, Registration)> = self
+            .names
+            .iter()
+            .filter(|entry| entry.key().starts_with(prefix))
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        registrations.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        registrations
+    }
+
+    /// Removes the registration under `name` and returns it.
+    pub fn remove(&self, name: &str) -> Option<Registration> {
+        let (_, registration) = self.names.remove(name)?;
+        self.notify(name, None);
+        self.disown(name, registration.owner);
+        Some(registration)
+    }
+
+    /// Notifies the process each time `name` is registered or removed, with messages tagged
+    /// with `tag`. Watching the same name again only changes the tag.
+    pub fn watch(&self, name: &str, process: Arc<dyn Process>, tag: Option<i64>) {
+        let process_id = process.id();
+        let mut watchers = self.watchers.entry(name.to_owned()).or_default();
+        match watchers
+            .iter_mut()
+            .find(|watcher| watcher.process.id() == process_id)
+        {
+            Some(watcher) => watcher.tag = tag,
+            None => watchers.push(Watcher { process, tag }),
+        }
+        drop(watchers);
+        self.watching
+            .entry(process_id)
+            .or_default()
+            .insert(name.to_owned());
+    }
+
+    /// Stops notifying the process about changes of `name`. Returns false if the process
+    /// wasn't watching it.
+    pub fn unwatch(&self, name: &str, process_id: u64) -> bool {
+        let removed = self.remove_watcher(name, process_id);
+        if let Some(mut names) = self.watching.get_mut(&process_id) {
+            names.remove(name);
+        }
+        self.watching
+            .remove_if(&process_id, |_, names| names.is_empty());
+        removed
+    }
+
+    /// Removes all names that are still registered by the process and all watches of it.
+    /// Returns the removed names with their registrations.
+    pub fn remove_process(&self, process_id: u64) -> Vec<(String, Registration)> {
+        if let Some((_, names)) = self.watching.remove(&process_id) {
+            for name in names {
+                self.remove_watcher(&name, process_id);
+            }
+        }
+        match self.owned.remove(&process_id) {
+            Some((_, names)) => names
+                .into_iter()
+                .filter_map(|name| {
+                    let removed = self.names.remove_if(&name, |_, registration| {
+                        registration.owner == Some(process_id)
+                    });
+                    if removed.is_some() {
+                        self.notify(&name, None);
+                    }
+                    removed
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn own(&self, name: &str, owner: Option<u64>) {
+        if let Some(owner) = owner {
+            self.owned.entry(owner).or_default().insert(name.to_owned());
+        }
+    }
+
+    fn disown(&self, name: &str, owner: Option<u64>) {
+        if let Some(owner) = owner {
+            if let Some(mut names) = self.owned.get_mut(&owner) {
+                names.remove(name);
+            }
+            self.owned.remove_if(&owner, |_, names| names.is_empty());
+        }
+    }
+
+    fn remove_watcher(&self, name: &str, process_id: u64) -> bool {
+        let removed = match self.watchers.get_mut(name) {
+            Some(mut watchers) => {
+                let len = watchers.len();
+                watchers.retain(|watcher| watcher.process.id() != process_id);
+                watchers.len() != len
+            }
+            None => false,
+        };
+        self.watchers
+            .remove_if(name, |_, watchers| watchers.is_empty());
+        removed
+    }
+
+    fn notify(&self, name: &str, registration: Option<&Registration>) {
+        let watchers = match self.watchers.get(name) {
+            Some(watchers) => watchers,
+            None => return,
+        };
+        let buffer = match registration {
+            Some(registration) => {
+                let mut buffer = Vec::with_capacity(17 + registration.metadata.len());
+                buffer.push(0);
+                buffer.extend_from_slice(&registration.node_id.to_le_bytes());
+                buffer.extend_from_slice(&registration.process_id.to_le_bytes());
+                buffer.extend_from_slice(&registration.metadata);
+                buffer
+            }
+            None => vec![1],
+        };
+        for watcher in watchers.iter() {
+            let message = DataMessage::new_from_vec(watcher.tag, buffer.clone());
+            watcher
+                .process
+                .send(Signal::Message(Message::Data(message)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(process_id: u64, owner: Option<u64>) -> Registration {
+        Registration {
+            node_id: 0,
+            process_id,
+            owner,
+            metadata: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn owned_names_are_removed() {
+        let registry = ProcessRegistry::default();
+        registry.put("owned", registration(1, Some(1)));
+        registry.put("permanent", registration(1, None));
+        registry.put("taken_over", registration(1, Some(1)));
+        // Another process registers the same name, it's not removed with the first owner.
+        registry.put("taken_over", registration(2, Some(2)));
+
+        assert_eq!(
+            registry.remove_process(1),
+            vec![("owned".to_string(), registration(1, Some(1)))]
+        );
+        assert!(registry.get("owned").is_none());
+        assert_eq!(*registry.get("permanent").unwrap(), registration(1, None));
+        assert_eq!(
+            *registry.get("taken_over").unwrap(),
+            registration(2, Some(2))
+        );
+        assert!(registry.remove_process(1).is_empty());
+    }
+
+    #[test]
+    fn replaced_and_removed_names_are_disowned() {
+        let registry = ProcessRegistry::default();
+        assert_eq!(registry.put("name", registration(1, Some(1))), None);
+        assert_eq!(
+            registry.put("name", registration(2, Some(2))),
+            Some(registration(1, Some(1)))
+        );
+        assert!(!registry.owned.contains_key(&1));
+
+        assert!(registry.compare_and_swap("name", (0, 2), registration(3, Some(3))));
+        assert!(!registry.owned.contains_key(&2));
+
+        assert_eq!(registry.remove("name"), Some(registration(3, Some(3))));
+        assert!(!registry.owned.contains_key(&3));
+        assert_eq!(registry.remove("name"), None);
+    }
+
+    // Collects the data messages it receives.
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<(Option<i64>, Vec<u8>)>>);
+
+    impl Process for Recorder {
+        fn id(&self) -> u64 {
+            7
+        }
+
+        fn send(&self, signal: Signal) {
+            if let Signal::Message(Message::Data(message)) = signal {
+                self.0.lock().unwrap().push((message.tag, message.buffer));
+            }
+        }
+    }
+
+    #[test]
+    fn conditional_updates_notify_watchers() {
+        let registry = ProcessRegistry::default();
+        let watcher = Arc::new(Recorder::default());
+        registry.watch("name", watcher.clone(), Some(5));
+
+        assert!(registry.put_if_absent("name", registration(1, Some(1))));
+        assert!(!registry.put_if_absent("name", registration(2, Some(2))));
+        assert!(!registry.compare_and_swap("name", (0, 2), registration(3, Some(3))));
+        assert!(registry.compare_and_swap("name", (0, 1), registration(3, Some(3))));
+        assert_eq!(*registry.get("name").unwrap(), registration(3, Some(3)));
+        // The first owner exited, but the name was taken over.
+        assert!(registry.remove_process(1).is_empty());
+        assert_eq!(registry.remove_process(3).len(), 1);
+
+        let mut registered = vec![0];
+        registered.extend_from_slice(&0u64.to_le_bytes());
+        registered.extend_from_slice(&1u64.to_le_bytes());
+        let mut swapped = vec![0];
+        swapped.extend_from_slice(&0u64.to_le_bytes());
+        swapped.extend_from_slice(&3u64.to_le_bytes());
+        assert_eq!(
+            *watcher.0.lock().unwrap(),
+            vec![
+                (Some(5), registered),
+                (Some(5), swapped),
+                (Some(5), vec![1])
+            ]
+        );
+
+        assert!(registry.unwatch("name", 7));
+        assert!(!registry.unwatch("name", 7));
+        registry.put("name", registration(1, None));
+        assert_eq!(watcher.0.lock().unwrap().len(), 3);
+        assert!(registry.watchers.is_empty());
+        assert!(registry.watching.is_empty());
+    }
+
+    #[test]
+    fn list_by_prefix() {
+        let registry = ProcessRegistry::default();
+        let worker = Registration {
+            metadata: b"gpu".to_vec(),
+            ..registration(2, None)
+        };
+        registry.put("worker/2", worker.clone());
+        registry.put("worker/1", registration(1, None));
+        registry.put("workers", registration(3, None));
+        registry.put("logger", registration(4, None));
+
+        assert_eq!(
+            registry.list("worker/"),
+            vec![
+                ("worker/1".to_owned(), registration(1, None)),
+                ("worker/2".to_owned(), worker),
+            ]
+        );
+        assert_eq!(registry.list("").len(), 4);
+        assert!(registry.list("missing").is_empty());
+    }
+}
@@ -1,16 +1,20 @@
-use std::sync::Arc;
+use std::{convert::TryFrom, sync::Arc};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use wasmtime::ResourceLimiter;
 
 use crate::{
     config::{ProcessConfig, UNIT_OF_COMPUTE_IN_INSTRUCTIONS},
+    snapshot::{GlobalImage, InstanceImage, MemoryImage, SerializedVal},
     state::ProcessState,
     ExecutionResult, ResultValue,
 };
 
 use super::RawWasm;
 
+// Size of a WebAssembly memory page in bytes.
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
 #[derive(Clone)]
 pub struct WasmtimeRuntime {
     engine: wasmtime::Engine,
@@ -48,6 +52,25 @@ impl WasmtimeRuntime {
         compiled_module: &WasmtimeCompiledModule<T>,
         state: T,
     ) -> Result<WasmtimeInstance<T>>
+    where
+        T: ProcessState + Send + ResourceLimiter,
+    {
+        self.instantiate_or_return(compiled_module, state, 0)
+            .await
+            .map_err(|(error, _)| error)
+    }
+
+    /// Same as [`instantiate`](Self::instantiate), but gives the state back if the instantiation
+    /// fails.
+    ///
+    /// `fuel_consumed` is subtracted from the maximum fuel of the process, so that a process
+    /// that is instantiated again (e.g. after hibernating) can't reset its fuel.
+    pub(crate) async fn instantiate_or_return<T>(
+        &self,
+        compiled_module: &WasmtimeCompiledModule<T>,
+        state: T,
+        fuel_consumed: u64,
+    ) -> std::result::Result<WasmtimeInstance<T>, (anyhow::Error, T)>
     where
         T: ProcessState + Send + ResourceLimiter,
     {
@@ -58,21 +81,44 @@ impl WasmtimeRuntime {
         // Trap if out of fuel
         store.out_of_fuel_trap();
         // Define maximum fuel
-        match max_fuel {
-            Some(max_fuel) => {
-                store.out_of_fuel_async_yield(max_fuel, UNIT_OF_COMPUTE_IN_INSTRUCTIONS)
+        let fuel_limit = max_fuel.map(|max_fuel| {
+            max_fuel
+                .saturating_mul(UNIT_OF_COMPUTE_IN_INSTRUCTIONS)
+                .saturating_sub(fuel_consumed)
+        });
+        match fuel_limit {
+            Some(fuel) => {
+                // Fuel is injected in units of compute, the rest of a started unit is added now
+                let partial_unit = fuel % UNIT_OF_COMPUTE_IN_INSTRUCTIONS;
+                if partial_unit > 0 {
+                    if let Err(error) = store.add_fuel(partial_unit) {
+                        return Err((error, store.into_data()));
+                    }
+                }
+                store.out_of_fuel_async_yield(
+                    fuel / UNIT_OF_COMPUTE_IN_INSTRUCTIONS,
+                    UNIT_OF_COMPUTE_IN_INSTRUCTIONS,
+                )
             }
             // If no limit is specified use maximum
             None => store.out_of_fuel_async_yield(u64::MAX, UNIT_OF_COMPUTE_IN_INSTRUCTIONS),
         };
         // Create instance
-        let instance = compiled_module
+        let instance = match compiled_module
             .instantiator()
             .instantiate_async(&mut store)
-            .await?;
+            .await
+        {
+            Ok(instance) => instance,
+            Err(error) => return Err((error, store.into_data())),
+        };
         // Mark state as initialized
         store.data_mut().initialize();
-        Ok(WasmtimeInstance { store, instance })
+        Ok(WasmtimeInstance {
+            store,
+            instance,
+            fuel_limit,
+        })
     }
 }
 
@@ -127,20 +173,28 @@ where
 {
     store: wasmtime::Store<T>,
     instance: wasmtime::Instance,
+    // Fuel the store was given, if the process has a fuel limit.
+    fuel_limit: Option<u64>,
 }
 
 impl<T> WasmtimeInstance<T>
 where
-    T: Send,
+    T: ProcessState + Send,
 {
     pub async fn call(mut self, function: &str, params: Vec<wasmtime::Val>) -> ExecutionResult<T> {
+        let result = self.invoke(function, params).await;
+        ExecutionResult {
+            state: self.store.into_data(),
+            result,
+        }
+    }
+
+    /// Calls the function without consuming the instance, so that it can be inspected afterwards.
+    pub async fn invoke(&mut self, function: &str, params: Vec<wasmtime::Val>) -> ResultValue {
         let entry = self.instance.get_func(&mut self.store, function);
 
         if entry.is_none() {
-            return ExecutionResult {
-                state: self.store.into_data(),
-                result: ResultValue::SpawnError(format!("Function '{}' not found", function)),
-            };
+            return ResultValue::SpawnError(format!("Function '{}' not found", function));
         }
 
         let result = entry
@@ -148,31 +202,114 @@ where
             .call_async(&mut self.store, &params, &mut [])
             .await;
 
-        ExecutionResult {
-            state: self.store.into_data(),
-            result: match result {
-                Ok(()) => ResultValue::Ok,
-                Err(err) => {
-                    // If the trap is a result of calling `proc_exit(0)`, treat it as an no-error finish.
-                    match err.downcast_ref::<wasmtime::Trap>() {
-                        Some(trap) => {
-                            if trap.i32_exit_status().is_some()
-                                && trap.i32_exit_status().unwrap() == 0
-                            {
-                                ResultValue::Ok
-                            } else {
-                                ResultValue::Failed(trap.to_string())
-                            }
+        match result {
+            Ok(()) => ResultValue::Ok,
+            Err(err) => match err.downcast_ref::<wasmtime::Trap>() {
+                Some(trap) => match trap.i32_exit_status() {
+                    // If the trap is a result of calling `proc_exit(0)`, treat it as an no-error
+                    // finish.
+                    Some(0) => ResultValue::Ok,
+                    Some(code) => ResultValue::ExitCode(code, trap.to_string()),
+                    None => {
+                        // Wasmtime doesn't expose a trap code for running out of fuel, but it
+                        // only happens once all the fuel given to the store is consumed.
+                        if self.out_of_fuel() {
+                            ResultValue::OutOfFuel(trap.to_string())
+                        // A guest that is refused to grow its memory usually doesn't trap right
+                        // away, but aborts shortly after.
+                        } else if self.store.data().usage().memory_limit_reached() {
+                            ResultValue::MemoryLimit(trap.to_string())
+                        } else {
+                            ResultValue::Trap(trap.to_string())
                         }
-                        None => ResultValue::Failed(format!(
-                            "Can't downcast trap ({}) to wasmtime::Trap",
-                            err
-                        )),
                     }
+                },
+                None => {
+                    ResultValue::Failed(format!("Can't downcast trap ({}) to wasmtime::Trap", err))
                 }
             },
         }
     }
+
+    pub fn state(&self) -> &T {
+        self.store.data()
+    }
+
+    /// Consumes the instance and returns its state.
+    pub fn into_state(self) -> T {
+        self.store.into_data()
+    }
+
+    /// Returns the fuel consumed by the instance so far.
+    pub fn fuel_consumed(&self) -> u64 {
+        self.store.fuel_consumed().unwrap_or(0)
+    }
+
+    pub fn state_mut(&mut self) -> &mut T {
+        self.store.data_mut()
+    }
+
+    /// Exports the content of all exported memories and mutable globals.
+    pub fn export_image(&mut self) -> Result<InstanceImage> {
+        let mut image = InstanceImage::default();
+        let exports: Vec<_> = self
+            .instance
+            .exports(&mut self.store)
+            .map(|export| (export.name().to_string(), export.into_extern()))
+            .collect();
+        for (name, export) in exports {
+            match export {
+                wasmtime::Extern::Memory(memory) => {
+                    let data = memory.data(&self.store).to_vec();
+                    image.memories.push(MemoryImage { name, data });
+                }
+                // Immutable globals are always initialized to the same value
+                wasmtime::Extern::Global(global)
+                    if global.ty(&self.store).mutability() == wasmtime::Mutability::Var =>
+                {
+                    let value = SerializedVal::try_from(&global.get(&mut self.store))
+                        .map_err(|err| anyhow!("Global '{}': {}", name, err))?;
+                    image.globals.push(GlobalImage { name, value });
+                }
+                _ => {}
+            }
+        }
+        Ok(image)
+    }
+
+    /// Imports an image exported from an instance of the same module.
+    ///
+    /// Memories are grown to the size of the image, the memory limit of the process applies.
+    pub fn import_image(&mut self, image: &InstanceImage) -> Result<()> {
+        for MemoryImage { name, data } in image.memories.iter() {
+            let memory = self
+                .instance
+                .get_memory(&mut self.store, name)
+                .ok_or_else(|| anyhow!("Memory '{}' not exported by the module", name))?;
+            let current_size = memory.data_size(&self.store);
+            if data.len() > current_size {
+                let pages = (data.len() - current_size).div_ceil(WASM_PAGE_SIZE);
+                memory.grow(&mut self.store, pages as u64)?;
+            }
+            memory.data_mut(&mut self.store)[..data.len()].copy_from_slice(data);
+        }
+        for GlobalImage { name, value } in image.globals.iter() {
+            let global = self
+                .instance
+                .get_global(&mut self.store, name)
+                .ok_or_else(|| anyhow!("Global '{}' not exported by the module", name))?;
+            global.set(&mut self.store, (*value).into())?;
+        }
+        Ok(())
+    }
+
+    // Returns true if the store consumed all the fuel it was given.
+    fn out_of_fuel(&self) -> bool {
+        match self.fuel_limit {
+            Some(fuel_limit) => self.fuel_consumed() >= fuel_limit,
+            None => false,
+        }
+    }
 }
 
 pub fn default_config() -> wasmtime::Config {
@@ -0,0 +1,89 @@
+use std::convert::TryFrom;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use wasmtime::Val;
+
+/// A snapshot of a hibernated process.
+///
+/// It contains everything needed to restore the process from the same module: the image of the
+/// instance, the serialized resources of the process state and the function (with params) that
+/// the process asked to be resumed with.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessSnapshot {
+    pub(crate) image: InstanceImage,
+    pub(crate) resources: Vec<u8>,
+    pub(crate) function: String,
+    pub(crate) params: Vec<SerializedVal>,
+}
+
+impl ProcessSnapshot {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Exported memories and mutable globals of an instance.
+///
+/// Only exported items are reachable from the host, everything else is re-initialized from the
+/// module when the image is imported into a new instance.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstanceImage {
+    pub(crate) memories: Vec<MemoryImage>,
+    pub(crate) globals: Vec<GlobalImage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct MemoryImage {
+    pub(crate) name: String,
+    pub(crate) data: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct GlobalImage {
+    pub(crate) name: String,
+    pub(crate) value: SerializedVal,
+}
+
+/// A WebAssembly value that can be serialized. References can't be part of a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SerializedVal {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+    V128(u128),
+}
+
+impl TryFrom<&Val> for SerializedVal {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Val) -> Result<Self> {
+        match value {
+            Val::I32(value) => Ok(SerializedVal::I32(*value)),
+            Val::I64(value) => Ok(SerializedVal::I64(*value)),
+            Val::F32(value) => Ok(SerializedVal::F32(*value)),
+            Val::F64(value) => Ok(SerializedVal::F64(*value)),
+            Val::V128(value) => Ok(SerializedVal::V128(*value)),
+            Val::FuncRef(_) | Val::ExternRef(_) => {
+                Err(anyhow!("References can't be part of a snapshot"))
+            }
+        }
+    }
+}
+
+impl From<SerializedVal> for Val {
+    fn from(value: SerializedVal) -> Self {
+        match value {
+            SerializedVal::I32(value) => Val::I32(value),
+            SerializedVal::I64(value) => Val::I64(value),
+            SerializedVal::F32(value) => Val::F32(value),
+            SerializedVal::F64(value) => Val::F64(value),
+            SerializedVal::V128(value) => Val::V128(value),
+        }
+    }
+}
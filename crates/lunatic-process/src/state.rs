@@ -1,17 +1,18 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use dashmap::DashMap;
 use hash_map_id::HashMapId;
 use tokio::sync::{
     mpsc::{UnboundedReceiver, UnboundedSender},
     Mutex,
 };
-use wasmtime::Linker;
+use wasmtime::{Linker, Val};
 
 use crate::{
     config::ProcessConfig,
+    info::ProcessUsage,
     mailbox::MessageMailbox,
+    registry::ProcessRegistry,
     runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime},
     Signal,
 };
@@ -19,6 +20,8 @@ use crate::{
 pub type ConfigResources<T> = HashMapId<T>;
 pub type SignalSender = UnboundedSender<Signal>;
 pub type SignalReceiver = Arc<Mutex<UnboundedReceiver<Signal>>>;
+/// Entry function and params used to resume a hibernated process.
+pub type Hibernation = (String, Vec<Val>);
 
 /// The internal state of a process.
 ///
@@ -59,11 +62,21 @@ pub trait ProcessState: Sized {
     fn signal_mailbox(&self) -> &(SignalSender, SignalReceiver);
     // Returns message mailbox
     fn message_mailbox(&self) -> &MessageMailbox;
+    // Returns resource usage, shared with the signal handler
+    fn usage(&self) -> &ProcessUsage;
+    // Returns the pending hibernation request, it's set by the guest before the instance is
+    // dropped and taken by the runtime once the process is resumed.
+    fn hibernation(&mut self) -> &mut Option<Hibernation>;
+    // Serializes the resources held by the process for a snapshot. Fails if any of the resources
+    // can't be serialized.
+    fn snapshot_resources(&self) -> Result<Vec<u8>>;
+    // Restores resources from a snapshot.
+    fn restore_resources(&mut self, resources: &[u8]) -> Result<()>;
 
     // Config resources
     fn config_resources(&self) -> &ConfigResources<Self::Config>;
     fn config_resources_mut(&mut self) -> &mut ConfigResources<Self::Config>;
 
-    // Registry
-    fn registry(&self) -> &Arc<DashMap<String, (u64, u64)>>;
+    // Registry of the environment the process runs in
+    fn registry(&self) -> &ProcessRegistry;
 }
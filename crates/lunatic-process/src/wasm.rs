@@ -1,14 +1,18 @@
+use std::convert::TryFrom;
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use log::trace;
 use tokio::task::JoinHandle;
 use wasmtime::{ResourceLimiter, Val};
 
 use crate::env::Environment;
-use crate::runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime};
+use crate::info::ProcessUsage;
+use crate::mailbox::MessageMailbox;
+use crate::runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeInstance, WasmtimeRuntime};
+use crate::snapshot::{ProcessSnapshot, SerializedVal};
 use crate::state::ProcessState;
-use crate::{Process, Signal, WasmProcess};
+use crate::{ExecutionResult, Process, ResultValue, Signal, WasmProcess};
 
 /// Spawns a new wasm process from a compiled module.
 ///
@@ -31,16 +35,138 @@ pub async fn spawn_wasm<S>(
 where
     S: ProcessState + Send + ResourceLimiter + 'static,
 {
+    trace!("Spawning process: {}", state.id());
+    let instance = runtime.instantiate(module, state).await?;
+    let process = spawn_instance(
+        env,
+        runtime,
+        module,
+        instance,
+        function.to_string(),
+        params,
+        link,
+    )
+    .await;
+    Ok(process)
+}
+
+/// Runs a wasm process until it hibernates and takes a snapshot of it.
+///
+/// The process is not spawned into the background and can't receive any signals or messages, so
+/// it's expected to only perform its initialization before hibernating. The snapshot can be used
+/// with [`restore_wasm`] to skip the initialization next time.
+///
+/// Fails if the process finishes without hibernating or any of its resources can't be
+/// serialized.
+pub async fn snapshot_wasm<S>(
+    runtime: WasmtimeRuntime,
+    module: &WasmtimeCompiledModule<S>,
+    state: S,
+    function: &str,
+    params: Vec<Val>,
+) -> Result<ProcessSnapshot>
+where
+    S: ProcessState + Send + ResourceLimiter + 'static,
+{
+    trace!("Snapshotting process: {}", state.id());
+    let mut instance = runtime.instantiate(module, state).await?;
+    let result = instance.invoke(function, params).await;
+    let (function, params) = match instance.state_mut().hibernation().take() {
+        Some(hibernation) => hibernation,
+        None => {
+            return Err(match result.failure() {
+                Some(failure) => anyhow!("Process failed before hibernating: {}", failure),
+                None => anyhow!("Process finished without hibernating"),
+            })
+        }
+    };
+
+    let params = params
+        .iter()
+        .map(SerializedVal::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ProcessSnapshot {
+        image: instance.export_image()?,
+        resources: instance.state().snapshot_resources()?,
+        function,
+        params,
+    })
+}
+
+/// Spawns a wasm process from a snapshot taken with [`snapshot_wasm`].
+///
+/// The `module` must be the same one that the snapshot was taken from. The process is resumed by
+/// calling the function it passed to `lunatic::process::hibernate`.
+pub async fn restore_wasm<S>(
+    env: Arc<dyn Environment>,
+    runtime: WasmtimeRuntime,
+    module: &WasmtimeCompiledModule<S>,
+    mut state: S,
+    snapshot: ProcessSnapshot,
+    link: Option<(Option<i64>, Arc<dyn Process>)>,
+) -> Result<(JoinHandle<Result<S>>, Arc<dyn Process>)>
+where
+    S: ProcessState + Send + ResourceLimiter + 'static,
+{
+    trace!("Restoring process: {}", state.id());
+    state.restore_resources(&snapshot.resources)?;
+    let mut instance = runtime.instantiate(module, state).await?;
+    instance.import_image(&snapshot.image)?;
+    let params = snapshot.params.into_iter().map(Val::from).collect();
+    let process = spawn_instance(
+        env,
+        runtime,
+        module,
+        instance,
+        snapshot.function,
+        params,
+        link,
+    )
+    .await;
+    Ok(process)
+}
+
+// Spawns the process of an instance into the background.
+async fn spawn_instance<S>(
+    env: Arc<dyn Environment>,
+    runtime: WasmtimeRuntime,
+    module: &WasmtimeCompiledModule<S>,
+    instance: WasmtimeInstance<S>,
+    function: String,
+    params: Vec<Val>,
+    link: Option<(Option<i64>, Arc<dyn Process>)>,
+) -> (JoinHandle<Result<S>>, Arc<dyn Process>)
+where
+    S: ProcessState + Send + ResourceLimiter + 'static,
+{
+    let state = instance.state();
     let id = state.id();
-    trace!("Spawning process: {}", id);
     let signal_mailbox = state.signal_mailbox().clone();
     let message_mailbox = state.message_mailbox().clone();
+    let usage = state.usage().clone();
 
-    let instance = runtime.instantiate(module, state).await?;
-    let function = function.to_string();
-    let fut = async move { instance.call(&function, params).await };
-    let child_process = crate::new(fut, id, env.clone(), signal_mailbox.1, message_mailbox);
-    let child_process_handle = Arc::new(WasmProcess::new(id, signal_mailbox.0.clone()));
+    let fut = hibernating_call(
+        runtime,
+        module.clone(),
+        instance,
+        function,
+        params,
+        message_mailbox.clone(),
+        usage.clone(),
+    );
+    let child_process_handle = Arc::new(WasmProcess::with_mailbox(
+        id,
+        signal_mailbox.0.clone(),
+        message_mailbox.clone(),
+    ));
+    let child_process = crate::new(
+        fut,
+        id,
+        env.clone(),
+        signal_mailbox.1,
+        message_mailbox,
+        usage,
+    );
 
     env.add_process(id, child_process_handle.clone());
 
@@ -82,5 +208,62 @@ where
     // Spawn a background process
     trace!("Process size: {}", std::mem::size_of_val(&child_process));
     let join = tokio::task::spawn(child_process);
-    Ok((join, child_process_handle))
+    (join, child_process_handle)
+}
+
+// Calls the entry function of the instance.
+//
+// If the process asks to hibernate, the instance (including its linear memory) is dropped and
+// only the state is kept around. Once the next message arrives, the process is re-instantiated
+// from the module and resumed with the function and params it asked for. The mailbox, links,
+// registered names and the remaining fuel belong to the process and survive the
+// re-instantiation.
+async fn hibernating_call<S>(
+    runtime: WasmtimeRuntime,
+    module: WasmtimeCompiledModule<S>,
+    mut instance: WasmtimeInstance<S>,
+    mut function: String,
+    mut params: Vec<Val>,
+    message_mailbox: MessageMailbox,
+    usage: ProcessUsage,
+) -> ExecutionResult<S>
+where
+    S: ProcessState + Send + ResourceLimiter + 'static,
+{
+    // Fuel consumed by all instances of the process
+    let mut fuel_consumed = 0;
+    loop {
+        let result = instance.invoke(&function, params).await;
+        fuel_consumed += instance.fuel_consumed();
+        let mut state = instance.into_state();
+        match state.hibernation().take() {
+            Some((resume_function, resume_params)) => {
+                function = resume_function;
+                params = resume_params;
+            }
+            None => return ExecutionResult { state, result },
+        }
+
+        trace!("Process {} hibernating", state.id());
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!("lunatic.process.hibernations");
+        usage.set_memory_size(0);
+        message_mailbox.wait_for_message().await;
+
+        instance = match runtime
+            .instantiate_or_return(&module, state, fuel_consumed)
+            .await
+        {
+            Ok(instance) => instance,
+            Err((error, state)) => {
+                return ExecutionResult {
+                    state,
+                    result: ResultValue::SpawnError(format!(
+                        "Failed to resume process from hibernation: {}",
+                        error
+                    )),
+                }
+            }
+        };
+    }
 }
@@ -1,15 +1,74 @@
+use std::{future::Future, vec::IntoIter};
+
 use anyhow::Result;
+use hash_map_id::HashMapId;
 use lunatic_common_api::{get_memory, IntoTrap};
-use lunatic_process::state::ProcessState;
-use lunatic_process_api::ProcessCtx;
+use lunatic_distributed::{lookup_name, DistributedCtx};
+use lunatic_process::{
+    env::Environment,
+    registry::{Registration, MAX_METADATA_SIZE},
+    state::ProcessState,
+};
+use lunatic_process_api::{ProcessConfigCtx, ProcessCtx};
 use wasmtime::Trap;
 use wasmtime::{Caller, Linker};
 
+/// Iterates over a snapshot of registry entries, created by `lunatic::registry::list`.
+pub struct RegistryIterator {
+    iter: IntoIter<(String, Registration)>,
+    current: Option<(String, Registration)>,
+}
+
+impl RegistryIterator {
+    pub fn new(iter: IntoIter<(String, Registration)>) -> Self {
+        Self {
+            iter,
+            current: None,
+        }
+    }
+}
+
+pub type RegistryIteratorResources = HashMapId<RegistryIterator>;
+
+pub trait RegistryCtx {
+    fn registry_iterator_resources(&self) -> &RegistryIteratorResources;
+    fn registry_iterator_resources_mut(&mut self) -> &mut RegistryIteratorResources;
+}
+
 // Register the registry APIs to the linker
-pub fn register<T: ProcessState + ProcessCtx<T> + 'static>(linker: &mut Linker<T>) -> Result<()> {
-    linker.func_wrap("lunatic::registry", "put", put)?;
-    linker.func_wrap("lunatic::registry", "get", get)?;
-    linker.func_wrap("lunatic::registry", "remove", remove)?;
+pub fn register<T, E>(linker: &mut Linker<T>) -> Result<()>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + RegistryCtx + Send + 'static,
+    T::Config: ProcessConfigCtx,
+    E: Environment + 'static,
+{
+    linker.func_wrap4_async("lunatic::registry", "put", put)?;
+    linker.func_wrap4_async("lunatic::registry", "put_permanent", put_permanent)?;
+    linker.func_wrap6_async("lunatic::registry", "put_with_metadata", put_with_metadata)?;
+    linker.func_wrap4_async("lunatic::registry", "get", get)?;
+    linker.func_wrap2_async("lunatic::registry", "remove", remove)?;
+    linker.func_wrap5_async(
+        "lunatic::registry",
+        "get_in_environment",
+        get_in_environment,
+    )?;
+    linker.func_wrap4_async("lunatic::registry", "put_if_absent", put_if_absent)?;
+    linker.func_wrap6_async("lunatic::registry", "compare_and_swap", compare_and_swap)?;
+    linker.func_wrap("lunatic::registry", "watch", watch)?;
+    linker.func_wrap("lunatic::registry", "unwatch", unwatch)?;
+    linker.func_wrap("lunatic::registry", "get_metadata", get_metadata)?;
+    linker.func_wrap("lunatic::registry", "list", list)?;
+    linker.func_wrap("lunatic::registry", "list_next", list_next)?;
+    linker.func_wrap("lunatic::registry", "list_current", list_current)?;
+    linker.func_wrap(
+        "lunatic::registry",
+        "drop_list_iterator",
+        drop_list_iterator,
+    )?;
+    linker.func_wrap("lunatic::registry", "group_join", group_join)?;
+    linker.func_wrap("lunatic::registry", "group_leave", group_leave)?;
+    linker.func_wrap("lunatic::registry", "group_size", group_size)?;
+    linker.func_wrap("lunatic::registry", "group_members", group_members)?;
 
     #[cfg(feature = "metrics")]
     metrics::describe_counter!(
@@ -41,99 +100,860 @@ pub fn register<T: ProcessState + ProcessCtx<T> + 'static>(linker: &mut Linker<T
 
 // Registers process with ID under `name`.
 //
+// The registration is owned by the calling process and removed when the calling process exits.
+//
+// If the node is part of a cluster, the name is also registered in the cluster-wide registry of
+// the caller's environment, so that processes on other nodes can look it up. Like the local
+// registration, it's removed when the calling process exits.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+// * If the control server can't be reached.
+fn put<T, E>(
+    caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    node_id: u64,
+    process_id: u64,
+) -> Box<dyn Future<Output = Result<(), Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    let owner = caller.data().id();
+    put_registration(
+        caller,
+        name_str_ptr,
+        name_str_len,
+        Registration {
+            node_id,
+            process_id,
+            owner: Some(owner),
+            metadata: Vec::new(),
+        },
+    )
+}
+
+// Registers process with ID under `name`. Unlike `put`, the registration is not removed when
+// the calling process exits.
+//
+// Traps:
+// * If the process ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+// * If the control server can't be reached.
+fn put_permanent<T, E>(
+    caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    node_id: u64,
+    process_id: u64,
+) -> Box<dyn Future<Output = Result<(), Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    put_registration(
+        caller,
+        name_str_ptr,
+        name_str_len,
+        Registration {
+            node_id,
+            process_id,
+            owner: None,
+            metadata: Vec::new(),
+        },
+    )
+}
+
+// Registers process with ID under `name`, like `put`, and stores `metadata` next to it.
+//
+// Metadata is opaque to the runtime, it can be used to describe the registered process. It's only
+// stored in the registry of this node, the cluster-wide registry only holds the process ID.
+//
 // Traps:
+// * If the metadata is larger than 256 bytes.
 // * If the process ID doesn't exist.
 // * If any memory outside the guest heap space is referenced.
-fn put<T: ProcessState + ProcessCtx<T>>(
+// * If the control server can't be reached.
+fn put_with_metadata<T, E>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    node_id: u64,
+    process_id: u64,
+    metadata_ptr: u32,
+    metadata_len: u32,
+) -> Box<dyn Future<Output = Result<(), Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    Box::new(async move {
+        if metadata_len as usize > MAX_METADATA_SIZE {
+            return Err(Trap::new(format!(
+                "lunatic::registry::put_with_metadata: metadata is larger than {MAX_METADATA_SIZE} bytes"
+            )));
+        }
+        let memory = get_memory(&mut caller)?;
+        let metadata = memory
+            .data(&caller)
+            .get(metadata_ptr as usize..(metadata_ptr + metadata_len) as usize)
+            .or_trap("lunatic::registry::put_with_metadata")?
+            .to_vec();
+        let owner = caller.data().id();
+        Box::into_pin(put_registration(
+            caller,
+            name_str_ptr,
+            name_str_len,
+            Registration {
+                node_id,
+                process_id,
+                owner: Some(owner),
+                metadata,
+            },
+        ))
+        .await
+    })
+}
+
+fn put_registration<T, E>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    registration: Registration,
+) -> Box<dyn Future<Output = Result<(), Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    Box::new(async move {
+        let memory = get_memory(&mut caller)?;
+        let name = memory
+            .data(&caller)
+            .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+            .or_trap("lunatic::registry::put")?;
+        let name = std::str::from_utf8(name)
+            .or_trap("lunatic::registry::put")?
+            .to_owned();
+
+        // The name is only registered locally once the control server accepted it, so that it's
+        // never visible on this node without being visible in the cluster.
+        if let Ok(distributed) = caller.data().distributed() {
+            let control = distributed.control.clone();
+            control
+                .registry_put(
+                    caller.data().environment().id(),
+                    name.clone(),
+                    registration.node_id,
+                    registration.process_id,
+                )
+                .await
+                .or_trap("lunatic::registry::put")?;
+        }
+        let _previous = caller.data().registry().put(&name, registration);
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!("lunatic.registry.write");
+
+        #[cfg(feature = "metrics")]
+        if _previous.is_none() {
+            metrics::increment_gauge!("lunatic.registry.registered", 1.0);
+        }
+        Ok(())
+    })
+}
+
+// Looks up process under `name` and returns 0 if it was found or 1 if not found.
+//
+// Only names registered in the caller's environment are visible, use `get_in_environment` to
+// look up names in other environments.
+//
+// Names registered on this node are looked up first. If the node is part of a cluster and the
+// name isn't registered locally, it's looked up in the cluster-wide registry.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+// * If the control server can't be reached.
+fn get<T, E>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    node_id_ptr: u32,
+    process_id_ptr: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    Box::new(async move {
+        let memory = get_memory(&mut caller)?;
+        let name = memory
+            .data(&caller)
+            .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+            .or_trap("lunatic::registry::get")?;
+        let name = std::str::from_utf8(name).or_trap("lunatic::registry::get")?;
+
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!("lunatic.registry.read");
+
+        let (node_id, process_id) = match lookup_name(caller.data(), name)
+            .await
+            .or_trap("lunatic::registry::get")?
+        {
+            Some(registration) => registration,
+            None => return Ok(1),
+        };
+
+        memory
+            .write(&mut caller, node_id_ptr as usize, &node_id.to_le_bytes())
+            .or_trap("lunatic::registry::get")?;
+
+        memory
+            .write(
+                &mut caller,
+                process_id_ptr as usize,
+                &process_id.to_le_bytes(),
+            )
+            .or_trap("lunatic::registry::get")?;
+        Ok(0)
+    })
+}
+
+// Looks up process under `name` in the environment with ID `environment_id` and returns 0 if it
+// was found or 1 if not found.
+//
+// Each environment has its own registry, names registered in one environment are not visible
+// from `get` in another one. Names registered on this node are looked up first. If the node is
+// part of a cluster and the name isn't registered locally, it's looked up in the cluster-wide
+// registry of the environment.
+//
+// Returns:
+// * 0 if the process was found.
+// * 1 if no process is registered under `name`.
+// * 2 if the node isn't part of a cluster and the environment doesn't exist on this node.
+//
+// Traps:
+// * If the process doesn't have permissions to access other environments.
+// * If any memory outside the guest heap space is referenced.
+// * If the control server can't be reached.
+fn get_in_environment<T, E>(
+    mut caller: Caller<T>,
+    environment_id: u64,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    node_id_ptr: u32,
+    process_id_ptr: u32,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    T::Config: ProcessConfigCtx,
+    E: Environment,
+{
+    Box::new(async move {
+        if !caller.data().config().can_access_environments() {
+            return Err(Trap::new(
+                "lunatic::registry::get_in_environment: Process doesn't have permissions to access other environments",
+            ));
+        }
+        let memory = get_memory(&mut caller)?;
+        let name = memory
+            .data(&caller)
+            .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+            .or_trap("lunatic::registry::get_in_environment")?;
+        let name = std::str::from_utf8(name)
+            .or_trap("lunatic::registry::get_in_environment")?
+            .to_owned();
+
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!("lunatic.registry.read");
+
+        let state = caller.data();
+        let registry = state.environment().environment_registry(environment_id);
+        let registration = registry
+            .as_ref()
+            .and_then(|registry| registry.get(&name))
+            .map(|registration| (registration.node_id, registration.process_id));
+        let registration = match (registration, state.distributed()) {
+            (Some(registration), _) => registration,
+            (None, Ok(distributed)) => {
+                let control = distributed.control.clone();
+                match control
+                    .registry_get(environment_id, &name)
+                    .await
+                    .or_trap("lunatic::registry::get_in_environment")?
+                {
+                    Some(registration) => registration,
+                    None => return Ok(1),
+                }
+            }
+            (None, Err(_)) if registry.is_none() => return Ok(2),
+            (None, Err(_)) => return Ok(1),
+        };
+        let (node_id, process_id) = registration;
+
+        memory
+            .write(&mut caller, node_id_ptr as usize, &node_id.to_le_bytes())
+            .or_trap("lunatic::registry::get_in_environment")?;
+        memory
+            .write(
+                &mut caller,
+                process_id_ptr as usize,
+                &process_id.to_le_bytes(),
+            )
+            .or_trap("lunatic::registry::get_in_environment")?;
+        Ok(0)
+    })
+}
+
+// Removes process under `name` if it exists.
+//
+// Any process can remove the name, not only the one that registered it.
+//
+// If the node is part of a cluster, the name is also removed from the cluster-wide registry of
+// the caller's environment.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+// * If the control server can't be reached.
+fn remove<T, E>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+) -> Box<dyn Future<Output = Result<(), Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    Box::new(async move {
+        let memory = get_memory(&mut caller)?;
+        let name = memory
+            .data(&caller)
+            .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+            .or_trap("lunatic::registry::remove")?;
+        let name = std::str::from_utf8(name)
+            .or_trap("lunatic::registry::remove")?
+            .to_owned();
+
+        // Like with `put`, the name is only removed locally once the control server removed it, so
+        // that a failed request doesn't leave it registered in the cluster but missing on this node.
+        if let Ok(distributed) = caller.data().distributed() {
+            let control = distributed.control.clone();
+            control
+                .registry_remove(caller.data().environment().id(), name.clone())
+                .await
+                .or_trap("lunatic::registry::remove")?;
+        }
+        let _removed = caller.data().registry().remove(&name);
+
+        #[cfg(feature = "metrics")]
+        if _removed.is_some() {
+            metrics::increment_counter!("lunatic.registry.deletion");
+            metrics::decrement_gauge!("lunatic.registry.registered", 1.0);
+        }
+        Ok(())
+    })
+}
+
+// Registers process with ID under `name`, but only if no process is registered under it yet.
+//
+// Like with `put`, the registration is owned by the calling process and removed when the
+// calling process exits.
+//
+// If the node is part of a cluster, the check is done atomically by the cluster-wide registry of
+// the caller's environment and the name is only registered on this node if it succeeds.
+//
+// Returns:
+// * 0 if the process was registered.
+// * 1 if the name is already taken.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+// * If the control server can't be reached.
+fn put_if_absent<T, E>(
     mut caller: Caller<T>,
     name_str_ptr: u32,
     name_str_len: u32,
     node_id: u64,
     process_id: u64,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    Box::new(async move {
+        let memory = get_memory(&mut caller)?;
+        let name = memory
+            .data(&caller)
+            .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+            .or_trap("lunatic::registry::put_if_absent")?;
+        let name = std::str::from_utf8(name)
+            .or_trap("lunatic::registry::put_if_absent")?
+            .to_owned();
+
+        let registration = Registration {
+            node_id,
+            process_id,
+            owner: Some(caller.data().id()),
+            metadata: Vec::new(),
+        };
+        // A stale registration of this node can be replaced, if the cluster-wide registry didn't
+        // have the name.
+        let mut _replaced = false;
+        if let Ok(distributed) = caller.data().distributed() {
+            let control = distributed.control.clone();
+            let registered = control
+                .registry_put_if_absent(
+                    caller.data().environment().id(),
+                    name.clone(),
+                    node_id,
+                    process_id,
+                )
+                .await
+                .or_trap("lunatic::registry::put_if_absent")?;
+            if !registered {
+                return Ok(1);
+            }
+            // The cluster-wide registry decides who gets the name.
+            _replaced = caller.data().registry().put(&name, registration).is_some();
+        } else if !caller.data().registry().put_if_absent(&name, registration) {
+            return Ok(1);
+        }
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!("lunatic.registry.write");
+
+        #[cfg(feature = "metrics")]
+        if !_replaced {
+            metrics::increment_gauge!("lunatic.registry.registered", 1.0);
+        }
+
+        Ok(0)
+    })
+}
+
+// Registers process with ID under `name`, but only if the process currently registered under
+// it is the expected one. This can be used for leader election, where only one of the processes
+// competing for a name should take it over.
+//
+// Like with `put`, the new registration is owned by the calling process and removed when the
+// calling process exits.
+//
+// If the node is part of a cluster, the comparison is done atomically by the cluster-wide
+// registry of the caller's environment and the name is only registered on this node if it
+// succeeds.
+//
+// Returns:
+// * 0 if the registration was replaced.
+// * 1 if the name is not registered or a different process is registered under it.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+// * If the control server can't be reached.
+fn compare_and_swap<T, E>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    expected_node_id: u64,
+    expected_process_id: u64,
+    node_id: u64,
+    process_id: u64,
+) -> Box<dyn Future<Output = Result<u32, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + DistributedCtx<E> + Send,
+    E: Environment,
+{
+    Box::new(async move {
+        let memory = get_memory(&mut caller)?;
+        let name = memory
+            .data(&caller)
+            .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+            .or_trap("lunatic::registry::compare_and_swap")?;
+        let name = std::str::from_utf8(name)
+            .or_trap("lunatic::registry::compare_and_swap")?
+            .to_owned();
+
+        let expected = (expected_node_id, expected_process_id);
+        let registration = Registration {
+            node_id,
+            process_id,
+            owner: Some(caller.data().id()),
+            metadata: Vec::new(),
+        };
+        if let Ok(distributed) = caller.data().distributed() {
+            let control = distributed.control.clone();
+            let swapped = control
+                .registry_compare_and_swap(
+                    caller.data().environment().id(),
+                    name.clone(),
+                    expected,
+                    (node_id, process_id),
+                )
+                .await
+                .or_trap("lunatic::registry::compare_and_swap")?;
+            if !swapped {
+                return Ok(1);
+            }
+            // The cluster-wide registry decides who gets the name.
+            let _previous = caller.data().registry().put(&name, registration);
+            // The name could be unknown on this node, if it was registered from another node.
+            #[cfg(feature = "metrics")]
+            if _previous.is_none() {
+                metrics::increment_gauge!("lunatic.registry.registered", 1.0);
+            }
+        } else if !caller
+            .data()
+            .registry()
+            .compare_and_swap(&name, expected, registration)
+        {
+            return Ok(1);
+        }
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!("lunatic.registry.write");
+
+        Ok(0)
+    })
+}
+
+// Watches the registry entry under `name`.
+//
+// Each time a process is registered under `name` or the entry is removed, the calling process
+// receives a data message tagged with `tag` (0 means no tag). The first byte of the message is
+// 0 if a process was registered and 1 if the entry was removed. If a process was registered, its
+// node ID and process ID follow as little-endian u64 values, followed by its metadata.
+//
+// Watching the same name again only changes the tag. Watches are removed when the process exits.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+fn watch<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    tag: i64,
 ) -> Result<(), Trap> {
     let memory = get_memory(&mut caller)?;
     let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
     let name = memory_slice
         .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
-        .or_trap("lunatic::registry::put")?;
-    let name = std::str::from_utf8(name).or_trap("lunatic::registry::put")?;
-
-    state
-        .registry()
-        .insert(name.to_owned(), (node_id, process_id));
-    #[cfg(feature = "metrics")]
-    metrics::increment_counter!("lunatic.registry.write");
-
-    #[cfg(feature = "metrics")]
-    metrics::increment_gauge!("lunatic.registry.registered", 1.0);
+        .or_trap("lunatic::registry::watch")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::registry::watch")?;
 
+    let tag = match tag {
+        0 => None,
+        tag => Some(tag),
+    };
+    let process = state
+        .environment()
+        .get_process(state.id())
+        .or_trap("lunatic::registry::watch")?;
+    state.registry().watch(name, process, tag);
     Ok(())
 }
 
-// Looks up process under `name` and returns 0 if it was found or 1 if not found.
+// Stops watching the registry entry under `name`.
+//
+// Returns:
+// * 0 if the watch was removed.
+// * 1 if the calling process wasn't watching `name`.
 //
 // Traps:
 // * If any memory outside the guest heap space is referenced.
-fn get<T: ProcessState + ProcessCtx<T>>(
+fn unwatch<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     name_str_ptr: u32,
     name_str_len: u32,
-    node_id_ptr: u32,
-    process_id_ptr: u32,
 ) -> Result<u32, Trap> {
     let memory = get_memory(&mut caller)?;
     let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
     let name = memory_slice
         .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
-        .or_trap("lunatic::registry::get")?;
-    let name = std::str::from_utf8(name).or_trap("lunatic::registry::get")?;
-
-    #[cfg(feature = "metrics")]
-    metrics::increment_counter!("lunatic.registry.read");
+        .or_trap("lunatic::registry::unwatch")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::registry::unwatch")?;
 
-    let (node_id, process_id) = if let Some(process) = state.registry().get(name) {
-        *process
+    if state.registry().unwatch(name, state.id()) {
+        Ok(0)
     } else {
-        return Ok(1);
+        Ok(1)
+    }
+}
+
+// Copies the metadata stored under `name` into **metadata_ptr**, but at most `metadata_len`
+// bytes.
+//
+// Returns:
+// * The size of the metadata, which can be larger than the number of copied bytes.
+// * u64::MAX if no process is registered under `name`.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+fn get_metadata<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    metadata_ptr: u32,
+    metadata_len: u32,
+) -> Result<u64, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let name = memory_slice
+        .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+        .or_trap("lunatic::registry::get_metadata")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::registry::get_metadata")?;
+
+    let metadata = match state.registry().get(name) {
+        Some(registration) => registration.metadata.clone(),
+        None => return Ok(u64::MAX),
+    };
+    let copy_len = metadata.len().min(metadata_len as usize);
+    memory_slice
+        .get_mut(metadata_ptr as usize..metadata_ptr as usize + copy_len)
+        .or_trap("lunatic::registry::get_metadata")?
+        .copy_from_slice(&metadata[..copy_len]);
+    Ok(metadata.len() as u64)
+}
+
+// Lists all processes registered on this node under names starting with `prefix` and returns
+// the ID of an iterator over them.
+//
+// Entries are ordered by name. The iterator holds a snapshot of the registry, later changes are
+// not visible through it.
+//
+// Traps:
+// * If the prefix is not a valid utf8 string.
+// * If any memory outside the guest heap space is referenced.
+fn list<T: ProcessState + ProcessCtx<T> + RegistryCtx>(
+    mut caller: Caller<T>,
+    prefix_str_ptr: u32,
+    prefix_str_len: u32,
+) -> Result<u64, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let prefix = memory_slice
+        .get(prefix_str_ptr as usize..(prefix_str_ptr + prefix_str_len) as usize)
+        .or_trap("lunatic::registry::list")?;
+    let prefix = std::str::from_utf8(prefix).or_trap("lunatic::registry::list")?;
+
+    let entries = state.registry().list(prefix);
+    Ok(state
+        .registry_iterator_resources_mut()
+        .add(RegistryIterator::new(entries.into_iter())))
+}
+
+// Moves the list iterator to the next entry and writes the length of its name to
+// **name_len_ptr**, the length of its metadata to **metadata_len_ptr** and the registered
+// process to **node_id_ptr** and **process_id_ptr**. The name and metadata can be read with
+// `list_current`.
+//
+// Returns:
+// * 0 on success
+// * 1 if there are no more entries in this iterator
+//
+// Traps:
+// * If the iterator ID doesn't exist.
+// * If any memory outside the guest heap space is referenced.
+fn list_next<T: RegistryCtx>(
+    mut caller: Caller<T>,
+    iter_id: u64,
+    name_len_ptr: u32,
+    metadata_len_ptr: u32,
+    node_id_ptr: u32,
+    process_id_ptr: u32,
+) -> Result<u32, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let iter = caller
+        .data_mut()
+        .registry_iterator_resources_mut()
+        .get_mut(iter_id)
+        .or_trap("lunatic::registry::list_next")?;
+    iter.current = iter.iter.next();
+    let (name_len, metadata_len, node_id, process_id) = match &iter.current {
+        Some((name, registration)) => (
+            name.len() as u32,
+            registration.metadata.len() as u32,
+            registration.node_id,
+            registration.process_id,
+        ),
+        None => return Ok(1),
     };
 
+    memory
+        .write(&mut caller, name_len_ptr as usize, &name_len.to_le_bytes())
+        .or_trap("lunatic::registry::list_next")?;
+    memory
+        .write(
+            &mut caller,
+            metadata_len_ptr as usize,
+            &metadata_len.to_le_bytes(),
+        )
+        .or_trap("lunatic::registry::list_next")?;
     memory
         .write(&mut caller, node_id_ptr as usize, &node_id.to_le_bytes())
-        .or_trap("lunatic::registry::get")?;
-
+        .or_trap("lunatic::registry::list_next")?;
     memory
         .write(
             &mut caller,
             process_id_ptr as usize,
             &process_id.to_le_bytes(),
         )
-        .or_trap("lunatic::registry::get")?;
+        .or_trap("lunatic::registry::list_next")?;
     Ok(0)
 }
 
-// Removes process under `name` if it exists.
+// Copies the name of the current list iterator entry into **name_ptr** and its metadata into
+// **metadata_ptr**. The guest needs to reserve as much space as `list_next` returned.
+//
+// Traps:
+// * If the iterator ID doesn't exist.
+// * If `list_next` wasn't called or the iterator has no more entries.
+// * If any memory outside the guest heap space is referenced.
+fn list_current<T: RegistryCtx>(
+    mut caller: Caller<T>,
+    iter_id: u64,
+    name_ptr: u32,
+    metadata_ptr: u32,
+) -> Result<(), Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let (name, registration) = state
+        .registry_iterator_resources()
+        .get(iter_id)
+        .or_trap("lunatic::registry::list_current")?
+        .current
+        .as_ref()
+        .or_trap("lunatic::registry::list_current: no current entry")?;
+
+    memory_slice
+        .get_mut(name_ptr as usize..name_ptr as usize + name.len())
+        .or_trap("lunatic::registry::list_current")?
+        .copy_from_slice(name.as_bytes());
+    memory_slice
+        .get_mut(metadata_ptr as usize..metadata_ptr as usize + registration.metadata.len())
+        .or_trap("lunatic::registry::list_current")?
+        .copy_from_slice(&registration.metadata);
+    Ok(())
+}
+
+// Drops the list iterator resource.
+//
+// Traps:
+// * If the iterator ID doesn't exist.
+fn drop_list_iterator<T: RegistryCtx>(mut caller: Caller<T>, iter_id: u64) -> Result<(), Trap> {
+    caller
+        .data_mut()
+        .registry_iterator_resources_mut()
+        .remove(iter_id)
+        .or_trap("lunatic::registry::drop_list_iterator")?;
+    Ok(())
+}
+
+// Adds the local process with ID to the group `name`. The group is created if it doesn't exist.
+//
+// Processes are removed from all groups when they die.
+//
+// Returns:
+// * 0 if the process joined the group.
+// * 1 if the process doesn't exist.
 //
 // Traps:
 // * If any memory outside the guest heap space is referenced.
-fn remove<T: ProcessState + ProcessCtx<T>>(
+fn group_join<T: ProcessState + ProcessCtx<T>>(
     mut caller: Caller<T>,
     name_str_ptr: u32,
     name_str_len: u32,
-) -> Result<(), Trap> {
+    process_id: u64,
+) -> Result<u32, Trap> {
     let memory = get_memory(&mut caller)?;
     let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
     let name = memory_slice
         .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
-        .or_trap("lunatic::registry::get")?;
-    let name = std::str::from_utf8(name).or_trap("lunatic::registry::get")?;
+        .or_trap("lunatic::registry::group_join")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::registry::group_join")?;
 
-    state.registry().remove(name);
+    let environment = state.environment();
+    if environment.get_process(process_id).is_none() {
+        return Ok(1);
+    }
+    environment.groups().join(name, process_id);
+    // The process could have died before joining, in that case it would never be removed.
+    if environment.get_process(process_id).is_none() {
+        environment.groups().leave(name, process_id);
+        return Ok(1);
+    }
+    Ok(0)
+}
 
-    #[cfg(feature = "metrics")]
-    metrics::increment_counter!("lunatic.registry.deletion");
+// Removes the process with ID from the group `name`.
+//
+// Returns:
+// * 0 if the process left the group.
+// * 1 if the process was not a member of the group.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+fn group_leave<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    process_id: u64,
+) -> Result<u32, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let name = memory_slice
+        .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+        .or_trap("lunatic::registry::group_leave")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::registry::group_leave")?;
 
-    #[cfg(feature = "metrics")]
-    metrics::decrement_gauge!("lunatic.registry.registered", 1.0);
+    if state.environment().groups().leave(name, process_id) {
+        Ok(0)
+    } else {
+        Ok(1)
+    }
+}
 
-    Ok(())
+// Returns the number of processes in the group `name`.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+fn group_size<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+) -> Result<u32, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let name = memory_slice
+        .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+        .or_trap("lunatic::registry::group_size")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::registry::group_size")?;
+
+    Ok(state.environment().groups().members(name).len() as u32)
+}
+
+// Copies the IDs of processes in the group `name` into guest memory, in ascending order.
+// Returns the number of IDs copied.
+//
+// Traps:
+// * If any memory outside the guest heap space is referenced.
+fn group_members<T: ProcessState + ProcessCtx<T>>(
+    mut caller: Caller<T>,
+    name_str_ptr: u32,
+    name_str_len: u32,
+    ids_ptr: u32,
+    ids_len: u32,
+) -> Result<u32, Trap> {
+    let memory = get_memory(&mut caller)?;
+    let (memory_slice, state) = memory.data_and_store_mut(&mut caller);
+    let name = memory_slice
+        .get(name_str_ptr as usize..(name_str_ptr + name_str_len) as usize)
+        .or_trap("lunatic::registry::group_members")?;
+    let name = std::str::from_utf8(name).or_trap("lunatic::registry::group_members")?;
+
+    let members = state.environment().groups().members(name);
+    let copy_len = members.len().min(ids_len as usize);
+    let ids = memory_slice
+        .get_mut(ids_ptr as usize..(ids_ptr as usize + std::mem::size_of::<u64>() * copy_len))
+        .or_trap("lunatic::registry::group_members")?;
+    for (slot, id) in ids.chunks_exact_mut(8).zip(members) {
+        slot.copy_from_slice(&id.to_le_bytes());
+    }
+    Ok(copy_len as u32)
 }
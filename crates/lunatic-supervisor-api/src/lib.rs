@@ -0,0 +1,254 @@
+mod supervisor;
+
+use std::{
+    convert::{TryFrom, TryInto},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use hash_map_id::HashMapId;
+use lunatic_common_api::{get_memory, IntoTrap};
+use lunatic_process::{state::ProcessState, Process, Signal, WasmProcess};
+use lunatic_process_api::{ProcessConfigCtx, ProcessCtx};
+use wasmtime::{Caller, Linker, ResourceLimiter, Trap, Val};
+
+pub use supervisor::{start_supervisor, ChildSpec, Strategy, SupervisorSpec};
+
+pub type SupervisorSpecResources<T> = HashMapId<SupervisorSpec<T>>;
+
+pub trait SupervisorCtx<T: ProcessState> {
+    fn supervisor_spec_resources(&self) -> &SupervisorSpecResources<T>;
+    fn supervisor_spec_resources_mut(&mut self) -> &mut SupervisorSpecResources<T>;
+}
+
+// Register the supervisor APIs to the linker
+pub fn register<T>(linker: &mut Linker<T>) -> Result<()>
+where
+    T: ProcessState + ProcessCtx<T> + SupervisorCtx<T> + ResourceLimiter + Send + 'static,
+    for<'a> &'a T: Send,
+    T::Config: ProcessConfigCtx,
+{
+    #[cfg(feature = "metrics")]
+    metrics::describe_counter!(
+        "lunatic.supervisor.started",
+        metrics::Unit::Count,
+        "number of supervisors started since startup"
+    );
+
+    #[cfg(feature = "metrics")]
+    metrics::describe_counter!(
+        "lunatic.supervisor.restarts",
+        metrics::Unit::Count,
+        "number of restarts performed by supervisors since startup"
+    );
+
+    linker.func_wrap("lunatic::supervisor", "create_spec", create_spec)?;
+    linker.func_wrap("lunatic::supervisor", "drop_spec", drop_spec)?;
+    linker.func_wrap("lunatic::supervisor", "add_child", add_child)?;
+    linker.func_wrap2_async("lunatic::supervisor", "start", start)?;
+
+    Ok(())
+}
+
+// Creates a new supervisor spec without any children.
+//
+// Strategies:
+// * 0 - one_for_one, only the failed child is restarted
+// * 1 - one_for_all, all children are restarted
+// * 2 - rest_for_one, the failed child and all children added after it are restarted
+//
+// If more than **max_restarts** restarts happen in **max_seconds** seconds, the supervisor will
+// fail and terminate all of its children.
+//
+// Returns ID of the newly created spec.
+//
+// Traps:
+// * If the strategy is unknown.
+fn create_spec<T: ProcessState + SupervisorCtx<T>>(
+    mut caller: Caller<T>,
+    strategy: u32,
+    max_restarts: u32,
+    max_seconds: u64,
+) -> Result<u64, Trap> {
+    let strategy = Strategy::try_from(strategy).or_trap("lunatic::supervisor::create_spec")?;
+    let spec = SupervisorSpec::new(strategy, max_restarts, Duration::from_secs(max_seconds));
+    Ok(caller.data_mut().supervisor_spec_resources_mut().add(spec))
+}
+
+// Drops the supervisor spec.
+//
+// Traps:
+// * If the spec ID doesn't exist.
+fn drop_spec<T: ProcessState + SupervisorCtx<T>>(
+    mut caller: Caller<T>,
+    spec_id: u64,
+) -> Result<(), Trap> {
+    caller
+        .data_mut()
+        .supervisor_spec_resources_mut()
+        .remove(spec_id)
+        .or_trap("lunatic::supervisor::drop_spec: Spec ID doesn't exist")?;
+    Ok(())
+}
+
+// Adds a child to the supervisor spec. Children are started in the order they are added.
+//
+// The **config_id**, **module_id**, function name and params are interpreted the same way as
+// in `lunatic::process::spawn`. A value of -1 for **config_id** or **module_id** uses the
+// config or module of the current process.
+//
+// Traps:
+// * If the spec ID doesn't exist.
+// * If the config or module ID doesn't exist.
+// * If the function string is not a valid utf8 string.
+// * If the params array is in a wrong format.
+// * If any memory outside the guest heap space is referenced.
+#[allow(clippy::too_many_arguments)]
+fn add_child<T>(
+    mut caller: Caller<T>,
+    spec_id: u64,
+    config_id: i64,
+    module_id: i64,
+    func_str_ptr: u32,
+    func_str_len: u32,
+    params_ptr: u32,
+    params_len: u32,
+) -> Result<(), Trap>
+where
+    T: ProcessState + ProcessCtx<T> + SupervisorCtx<T>,
+{
+    let state = caller.data();
+    let config = match config_id {
+        -1 => state.config().clone(),
+        config_id => Arc::new(
+            state
+                .config_resources()
+                .get(config_id as u64)
+                .or_trap("lunatic::supervisor::add_child: Config ID doesn't exist")?
+                .clone(),
+        ),
+    };
+    let module = match module_id {
+        -1 => state.module().clone(),
+        module_id => state
+            .module_resources()
+            .get(module_id as u64)
+            .or_trap("lunatic::supervisor::add_child: Module ID doesn't exist")?
+            .clone(),
+    };
+
+    let memory = get_memory(&mut caller)?;
+    let func_str = memory
+        .data(&caller)
+        .get(func_str_ptr as usize..(func_str_ptr + func_str_len) as usize)
+        .or_trap("lunatic::supervisor::add_child")?;
+    let function = std::str::from_utf8(func_str)
+        .or_trap("lunatic::supervisor::add_child")?
+        .to_string();
+    let params = memory
+        .data(&caller)
+        .get(params_ptr as usize..(params_ptr + params_len) as usize)
+        .or_trap("lunatic::supervisor::add_child")?;
+    let params_chunks = &mut params.chunks_exact(17);
+    let params = params_chunks
+        .map(|chunk| {
+            let value = u128::from_le_bytes(chunk[1..].try_into()?);
+            let result = match chunk[0] {
+                0x7F => Val::I32(value as i32),
+                0x7E => Val::I64(value as i64),
+                0x7B => Val::V128(value),
+                _ => return Err(anyhow!("Unsupported type ID")),
+            };
+            Ok(result)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if !params_chunks.remainder().is_empty() {
+        return Err(anyhow!(
+            "Params array must be in chunks of 17 bytes, but {} bytes remained",
+            params_chunks.remainder().len()
+        )
+        .into());
+    }
+
+    caller
+        .data_mut()
+        .supervisor_spec_resources_mut()
+        .get_mut(spec_id)
+        .or_trap("lunatic::supervisor::add_child: Spec ID doesn't exist")?
+        .add_child(ChildSpec {
+            module,
+            config,
+            function,
+            params,
+        });
+    Ok(())
+}
+
+// Starts a supervisor process from the spec and consumes the spec.
+//
+// The supervisor starts all children and restarts them according to the spec's strategy when
+// they fail. If **link** is not 0, the supervisor is linked to the current process using
+// **link** as tag.
+//
+// Returns ID of the supervisor process.
+//
+// Traps:
+// * If the process doesn't have permissions to spawn processes.
+// * If it's called during module initialization.
+// * If the spec ID doesn't exist.
+fn start<T>(
+    mut caller: Caller<T>,
+    spec_id: u64,
+    link: i64,
+) -> Box<dyn Future<Output = Result<u64, Trap>> + Send + '_>
+where
+    T: ProcessState + ProcessCtx<T> + SupervisorCtx<T> + ResourceLimiter + Send + 'static,
+    for<'a> &'a T: Send,
+    T::Config: ProcessConfigCtx,
+{
+    Box::new(async move {
+        if !caller.data().config().can_spawn_processes() {
+            return Err(anyhow!("Process doesn't have permissions to spawn sub-processes").into());
+        }
+        if !caller.data().is_initialized() {
+            return Err(anyhow!("Cannot start supervisor during module initialization").into());
+        }
+
+        let spec = caller
+            .data_mut()
+            .supervisor_spec_resources_mut()
+            .remove(spec_id)
+            .or_trap("lunatic::supervisor::start: Spec ID doesn't exist")?;
+
+        let state = caller.data();
+        // Children states are derived from this one.
+        let template = state.new_state(state.module().clone(), state.config().clone())?;
+        let this: Arc<dyn Process> = Arc::new(WasmProcess::new(
+            state.id(),
+            state.signal_mailbox().0.clone(),
+        ));
+        let parent = match link {
+            0 => None,
+            tag => Some((Some(tag), this.clone())),
+        };
+
+        let supervisor = start_supervisor(
+            state.environment(),
+            state.runtime().clone(),
+            template,
+            spec,
+            parent,
+        );
+
+        if link != 0 {
+            // Send signal to itself to perform the linking
+            this.send(Signal::Link(None, Arc::new(supervisor.clone())));
+            // Suspend itself to process all new signals
+            tokio::task::yield_now().await;
+        }
+
+        Ok(supervisor.id())
+    })
+}
@@ -0,0 +1,256 @@
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use lunatic_process::{
+    env::Environment,
+    mailbox::MessageMailbox,
+    message::Message,
+    runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime},
+    state::ProcessState,
+    wasm::spawn_wasm,
+    NativeProcess, Process, Signal,
+};
+use wasmtime::{ResourceLimiter, Val};
+
+/// Decides which children are restarted after one of them fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Only the failed child is restarted.
+    OneForOne,
+    /// All children are terminated and restarted.
+    OneForAll,
+    /// The failed child and all children that were started after it are terminated and
+    /// restarted.
+    RestForOne,
+}
+
+impl TryFrom<u32> for Strategy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        match value {
+            0 => Ok(Strategy::OneForOne),
+            1 => Ok(Strategy::OneForAll),
+            2 => Ok(Strategy::RestForOne),
+            _ => Err(anyhow!("Unknown supervisor strategy {}", value)),
+        }
+    }
+}
+
+/// Everything needed to (re)start a supervised child.
+pub struct ChildSpec<T: ProcessState> {
+    pub module: Arc<WasmtimeCompiledModule<T>>,
+    pub config: Arc<T::Config>,
+    pub function: String,
+    pub params: Vec<Val>,
+}
+
+/// Describes the children of a supervisor, the order in which they are started and how
+/// failures are handled.
+///
+/// If more than `max_restarts` restarts happen inside of `period`, the supervisor gives up and
+/// fails, taking all of its children with it.
+pub struct SupervisorSpec<T: ProcessState> {
+    strategy: Strategy,
+    max_restarts: u32,
+    period: Duration,
+    children: Vec<ChildSpec<T>>,
+}
+
+impl<T: ProcessState> SupervisorSpec<T> {
+    pub fn new(strategy: Strategy, max_restarts: u32, period: Duration) -> Self {
+        Self {
+            strategy,
+            max_restarts,
+            period,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn add_child(&mut self, child: ChildSpec<T>) {
+        self.children.push(child);
+    }
+}
+
+/// Starts a native supervisor process.
+///
+/// `template` is only used to derive the states of children, it's never instantiated. If `link`
+/// is set, the supervisor is linked to the given process before it starts any children.
+///
+/// Children are restarted only if they fail. A child that finishes normally is not restarted.
+/// If a linked parent fails, the supervisor fails too and all children are terminated.
+pub fn start_supervisor<T>(
+    env: Arc<dyn Environment>,
+    runtime: WasmtimeRuntime,
+    template: T,
+    spec: SupervisorSpec<T>,
+    link: Option<(Option<i64>, Arc<dyn Process>)>,
+) -> NativeProcess
+where
+    T: ProcessState + Send + ResourceLimiter + 'static,
+{
+    let supervisor_env = env.clone();
+    let (_, process) = lunatic_process::spawn(env, move |this, mailbox| {
+        // Signals sent here are processed before the supervisor starts any children.
+        if let Some((tag, process)) = link {
+            this.send(Signal::Link(tag, process));
+        }
+        this.send(Signal::DieWhenLinkDies(false));
+
+        let children = spec.children.iter().map(|_| None).collect();
+        let supervisor = Supervisor {
+            this,
+            env: supervisor_env,
+            runtime,
+            template,
+            spec,
+            children,
+            restarts: VecDeque::new(),
+            next_tag: 1,
+        };
+        supervisor.run(mailbox)
+    });
+
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!("lunatic.supervisor.started");
+
+    process
+}
+
+struct Supervisor<T: ProcessState> {
+    this: NativeProcess,
+    env: Arc<dyn Environment>,
+    runtime: WasmtimeRuntime,
+    template: T,
+    spec: SupervisorSpec<T>,
+    // The link tag and handle of each running child, in the same order as `spec.children`.
+    children: Vec<Option<(i64, Arc<dyn Process>)>>,
+    restarts: VecDeque<Instant>,
+    next_tag: i64,
+}
+
+impl<T> Supervisor<T>
+where
+    T: ProcessState + Send + ResourceLimiter + 'static,
+{
+    async fn run(mut self, mailbox: MessageMailbox) -> Result<()> {
+        self.start_children(0..self.spec.children.len()).await?;
+
+        loop {
+            let (tag, reason) = match mailbox.pop(None).await {
+                Message::LinkDied(tag, reason) => (tag, reason),
+                // Supervisors don't handle any other messages
+                _ => continue,
+            };
+            // Only the parent is linked without a tag
+            let tag = match tag {
+                Some(tag) => tag,
+                None => return Err(anyhow!("Supervisor's parent died: {:?}", reason)),
+            };
+            // Messages from already terminated children are ignored
+            let index = match self
+                .children
+                .iter()
+                .position(|child| matches!(child, Some((child_tag, _)) if *child_tag == tag))
+            {
+                Some(index) => index,
+                None => continue,
+            };
+            warn!(
+                "Supervisor {} child {} failed: {:?}",
+                self.this.id(),
+                index,
+                reason
+            );
+            self.children[index] = None;
+            self.register_restart()?;
+
+            let restart = match self.spec.strategy {
+                Strategy::OneForOne => index..index + 1,
+                Strategy::OneForAll => 0..self.children.len(),
+                Strategy::RestForOne => index..self.children.len(),
+            };
+            // Children are terminated in reverse start order
+            restart
+                .clone()
+                .rev()
+                .for_each(|index| self.terminate_child(index));
+            self.start_children(restart).await?;
+        }
+    }
+
+    async fn start_children(&mut self, range: Range<usize>) -> Result<()> {
+        for index in range {
+            let child = &self.spec.children[index];
+            let state = self
+                .template
+                .new_state(child.module.clone(), child.config.clone())?;
+            let tag = self.next_tag;
+            self.next_tag += 1;
+            let (_, process) = spawn_wasm(
+                self.env.clone(),
+                self.runtime.clone(),
+                &child.module,
+                state,
+                &child.function,
+                child.params.clone(),
+                Some((Some(tag), Arc::new(self.this.clone()))),
+            )
+            .await?;
+            debug!(
+                "Supervisor {} started child {} as process {}",
+                self.this.id(),
+                index,
+                process.id()
+            );
+            self.children[index] = Some((tag, process));
+        }
+        Ok(())
+    }
+
+    // Unlinks the child first, so that its death isn't reported back to the supervisor.
+    fn terminate_child(&mut self, index: usize) {
+        if let Some((_, process)) = self.children[index].take() {
+            process.send(Signal::UnLink {
+                process_id: self.this.id(),
+            });
+            process.send(Signal::Kill);
+            self.this.send(Signal::UnLink {
+                process_id: process.id(),
+            });
+        }
+    }
+
+    // Fails if the restart intensity was exceeded.
+    fn register_restart(&mut self) -> Result<()> {
+        let now = Instant::now();
+        self.restarts.push_back(now);
+        while let Some(restart) = self.restarts.front() {
+            if now.duration_since(*restart) > self.spec.period {
+                self.restarts.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.restarts.len() > self.spec.max_restarts as usize {
+            return Err(anyhow!(
+                "Supervisor exceeded {} restarts in {:?}",
+                self.spec.max_restarts,
+                self.spec.period
+            ));
+        }
+
+        #[cfg(feature = "metrics")]
+        metrics::increment_counter!("lunatic.supervisor.restarts");
+
+        Ok(())
+    }
+}
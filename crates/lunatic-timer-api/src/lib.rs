@@ -77,6 +77,14 @@ impl TimerResources {
     pub fn remove(&mut self, id: u64) -> Option<JoinHandle<()>> {
         self.hash_map.remove(id)
     }
+
+    /// Returns the number of timers that didn't fire yet.
+    pub fn active(&self) -> usize {
+        self.hash_map
+            .iter()
+            .filter(|(_, handle)| !handle.is_finished())
+            .count()
+    }
 }
 
 pub trait TimerCtx {
@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-use lunatic_process::config::ProcessConfig;
+use lunatic_process::config::{MailboxOverflow, ProcessConfig};
 use lunatic_process_api::ProcessConfigCtx;
 use lunatic_wasi_api::LunaticWasiConfigCtx;
 use serde::{Deserialize, Serialize};
@@ -11,12 +11,24 @@ pub struct DefaultProcessConfig {
     max_memory: usize,
     // Maximum amount of compute expressed in units of 100k instructions.
     max_fuel: Option<u64>,
+    // Maximum number of messages waiting in the mailbox, unbounded if `None`
+    mailbox_capacity: Option<usize>,
+    // What happens with new messages if the mailbox is full
+    mailbox_overflow: MailboxOverflow,
+    // Maximum size of data messages in bytes, unlimited if `None`
+    max_message_size: Option<usize>,
     // Can this process compile new WebAssembly modules
     can_compile_modules: bool,
     // Can this process create new configurations
     can_create_configs: bool,
     // Can this process spawn sub-processes
     can_spawn_processes: bool,
+    // Can this process list all processes in the environment
+    can_list_processes: bool,
+    // Can this process look up names in other environments and spawn processes into them
+    can_access_environments: bool,
+    // Can this process create new environments
+    can_create_environments: bool,
     // WASI configs
     preopened_dirs: Vec<String>,
     command_line_arguments: Vec<String>,
@@ -28,6 +40,9 @@ impl Debug for DefaultProcessConfig {
         f.debug_struct("EnvConfig")
             .field("max_memory", &self.max_memory)
             .field("max_fuel", &self.max_fuel)
+            .field("mailbox_capacity", &self.mailbox_capacity)
+            .field("mailbox_overflow", &self.mailbox_overflow)
+            .field("max_message_size", &self.max_message_size)
             .field("preopened_dirs", &self.preopened_dirs)
             .field("args", &self.command_line_arguments)
             .field("envs", &self.environment_variables)
@@ -51,6 +66,30 @@ impl ProcessConfig for DefaultProcessConfig {
     fn get_max_memory(&self) -> usize {
         self.max_memory
     }
+
+    fn set_mailbox_capacity(&mut self, capacity: Option<usize>) {
+        self.mailbox_capacity = capacity
+    }
+
+    fn get_mailbox_capacity(&self) -> Option<usize> {
+        self.mailbox_capacity
+    }
+
+    fn set_mailbox_overflow(&mut self, overflow: MailboxOverflow) {
+        self.mailbox_overflow = overflow
+    }
+
+    fn get_mailbox_overflow(&self) -> MailboxOverflow {
+        self.mailbox_overflow
+    }
+
+    fn set_max_message_size(&mut self, max_message_size: Option<usize>) {
+        self.max_message_size = max_message_size
+    }
+
+    fn get_max_message_size(&self) -> Option<usize> {
+        self.max_message_size
+    }
 }
 
 impl LunaticWasiConfigCtx for DefaultProcessConfig {
@@ -118,6 +157,30 @@ impl ProcessConfigCtx for DefaultProcessConfig {
     fn set_can_spawn_processes(&mut self, can: bool) {
         self.can_spawn_processes = can
     }
+
+    fn can_list_processes(&self) -> bool {
+        self.can_list_processes
+    }
+
+    fn set_can_list_processes(&mut self, can: bool) {
+        self.can_list_processes = can
+    }
+
+    fn can_access_environments(&self) -> bool {
+        self.can_access_environments
+    }
+
+    fn set_can_access_environments(&mut self, can: bool) {
+        self.can_access_environments = can
+    }
+
+    fn can_create_environments(&self) -> bool {
+        self.can_create_environments
+    }
+
+    fn set_can_create_environments(&mut self, can: bool) {
+        self.can_create_environments = can
+    }
 }
 
 impl Default for DefaultProcessConfig {
@@ -125,9 +188,15 @@ impl Default for DefaultProcessConfig {
         Self {
             max_memory: u32::MAX as usize, // = 4 GB
             max_fuel: None,
+            mailbox_capacity: None,
+            mailbox_overflow: MailboxOverflow::default(),
+            max_message_size: None,
             can_compile_modules: false,
             can_create_configs: false,
             can_spawn_processes: false,
+            can_list_processes: false,
+            can_access_environments: false,
+            can_create_environments: false,
             preopened_dirs: vec![],
             command_line_arguments: vec![],
             environment_variables: vec![],
@@ -2,7 +2,6 @@ use std::{env, fs, path::Path, sync::Arc, time::Instant};
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use dashmap::DashMap;
 use lunatic_process::{env::LunaticEnvironment, runtimes, wasm::spawn_wasm};
 use lunatic_process_api::ProcessConfigCtx;
 use lunatic_runtime::{DefaultProcessConfig, DefaultProcessState};
@@ -63,10 +62,14 @@ pub(crate) async fn test() -> Result<()> {
     let args = Args::parse();
 
     let mut config = DefaultProcessConfig::default();
-    // Allow initial process to compile modules, create configurations and spawn sub-processes
+    // Allow initial process to compile modules, create configurations, spawn sub-processes, list
+    // processes and create and access other environments
     config.set_can_compile_modules(true);
     config.set_can_create_configs(true);
     config.set_can_spawn_processes(true);
+    config.set_can_list_processes(true);
+    config.set_can_access_environments(true);
+    config.set_can_create_environments(true);
 
     // Set correct command line arguments for the guest
     config.set_command_line_arguments(args.wasm_args);
@@ -216,14 +219,12 @@ pub(crate) async fn test() -> Result<()> {
         }
 
         let env = Arc::new(LunaticEnvironment::new(0));
-        let registry = Arc::new(DashMap::new());
         let mut state = DefaultProcessState::new(
             env.clone(),
             None,
             runtime.clone(),
             module.clone(),
             config.clone(),
-            registry,
         )
         .unwrap();
 
@@ -8,13 +8,15 @@ use lunatic_distributed::{
     quic,
 };
 use lunatic_process::{
-    env::{Environments, LunaticEnvironments},
+    config::ProcessConfig,
+    env::{Environments, LunaticEnvironments, RemovedName},
     runtimes::{self, Modules, RawWasm},
-    wasm::spawn_wasm,
+    snapshot::ProcessSnapshot,
+    wasm::{restore_wasm, snapshot_wasm, spawn_wasm},
 };
 use lunatic_process_api::ProcessConfigCtx;
 use lunatic_runtime::{DefaultProcessConfig, DefaultProcessState};
-use tokio::sync::mpsc::channel;
+use tokio::sync::mpsc::{channel, unbounded_channel, UnboundedReceiver};
 use uuid::Uuid;
 
 #[derive(Parser, Debug)]
@@ -60,6 +62,25 @@ struct Args {
     #[arg(long)]
     bench: bool,
 
+    /// Run the entry .wasm file until the main process hibernates and write a snapshot of it to
+    /// the file. Only the main process parked in hibernation is captured, processes spawned by it
+    /// are not part of the snapshot
+    #[arg(long, value_name = "SNAPSHOT_FILE", conflicts_with = "no_entry")]
+    snapshot: Option<String>,
+
+    /// Restore the main process from a snapshot taken from the same entry .wasm file
+    #[arg(
+        long,
+        value_name = "SNAPSHOT_FILE",
+        conflicts_with_all = ["no_entry", "snapshot"]
+    )]
+    restore: Option<String>,
+
+    /// Maximum size in bytes of data messages created by the main process and accepted from
+    /// other nodes
+    #[arg(long, value_name = "BYTES")]
+    max_message_size: Option<usize>,
+
     /// Entry .wasm file
     #[arg(conflicts_with = "no_entry", index = 1)]
     wasm: Option<String>,
@@ -114,6 +135,10 @@ pub(crate) async fn execute() -> Result<()> {
 
     let env = envs.create(1);
 
+    // Dump all running processes on SIGUSR1
+    #[cfg(unix)]
+    tokio::task::spawn(dump_processes_on_signal(envs.clone()));
+
     let (distributed_state, control_client, node_id) =
         if let (Some(node_address), Some(control_address)) = (args.node, args.control) {
             // TODO unwrap, better message
@@ -141,6 +166,15 @@ pub(crate) async fn execute() -> Result<()> {
             )
             .await?;
 
+            // Names owned by processes on this node are removed from the cluster-wide registry
+            // when the processes exit
+            let (removed_names, removed_names_receiver) = unbounded_channel();
+            envs.forward_removed_names(removed_names);
+            tokio::task::spawn(remove_owned_names(
+                control_client.clone(),
+                removed_names_receiver,
+            ));
+
             let distributed_client =
                 distributed::Client::new(node_id, control_client.clone(), quic_client.clone())
                     .await?;
@@ -158,6 +192,7 @@ pub(crate) async fn execute() -> Result<()> {
                     modules: Modules::<DefaultProcessState>::default(),
                     distributed: dist.clone(),
                     runtime: runtime.clone(),
+                    max_message_size: args.max_message_size,
                 },
                 node_address,
                 signed_cert_pem,
@@ -190,10 +225,15 @@ pub(crate) async fn execute() -> Result<()> {
     }
 
     let mut config = DefaultProcessConfig::default();
-    // Allow initial process to compile modules, create configurations and spawn sub-processes
+    // Allow initial process to compile modules, create configurations, spawn sub-processes, list
+    // processes and create and access other environments
     config.set_can_compile_modules(true);
     config.set_can_create_configs(true);
     config.set_can_spawn_processes(true);
+    config.set_can_list_processes(true);
+    config.set_can_access_environments(true);
+    config.set_can_create_environments(true);
+    config.set_max_message_size(args.max_message_size);
 
     if args.no_entry {
         // Block forever
@@ -238,16 +278,40 @@ pub(crate) async fn execute() -> Result<()> {
         runtime.clone(),
         module.clone(),
         Arc::new(config),
-        Default::default(),
     )
     .unwrap();
 
-    let (task, _) = spawn_wasm(env, runtime, &module, state, "_start", Vec::new(), None)
-        .await
-        .context(format!(
-            "Failed to spawn process from {}::_start()",
-            path.to_string_lossy()
-        ))?;
+    if let Some(snapshot_path) = args.snapshot {
+        let snapshot = snapshot_wasm(runtime, &module, state, "_start", Vec::new())
+            .await
+            .context(format!(
+                "Failed to snapshot process from {}::_start()",
+                path.to_string_lossy()
+            ))?;
+        fs::write(&snapshot_path, snapshot.to_bytes()?)
+            .context(format!("Failed to write snapshot to {}", snapshot_path))?;
+
+        if let (Some(ctrl), Some(node_id)) = (control_client, node_id) {
+            ctrl.deregister(node_id).await;
+        }
+        return Ok(());
+    }
+
+    let (task, _) = if let Some(snapshot_path) = args.restore {
+        let snapshot = fs::read(&snapshot_path)
+            .context(format!("Failed to read snapshot from {}", snapshot_path))?;
+        let snapshot = ProcessSnapshot::from_bytes(&snapshot)?;
+        restore_wasm(env, runtime, &module, state, snapshot, None)
+            .await
+            .context(format!("Failed to restore process from {}", snapshot_path))?
+    } else {
+        spawn_wasm(env, runtime, &module, state, "_start", Vec::new(), None)
+            .await
+            .context(format!(
+                "Failed to spawn process from {}::_start()",
+                path.to_string_lossy()
+            ))?
+    };
     // Wait on the main process to finish
     let result = task.await.map(|_| ()).map_err(|e| anyhow!(e.to_string()));
 
@@ -259,6 +323,59 @@ pub(crate) async fn execute() -> Result<()> {
     result
 }
 
+/// Print information about all processes on this node every time SIGUSR1 is received
+#[cfg(unix)]
+async fn dump_processes_on_signal(envs: Arc<LunaticEnvironments>) -> Result<()> {
+    use lunatic_process::env::{processes_info, Environment};
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigusr1 = signal(SignalKind::user_defined1())?;
+    while sigusr1.recv().await.is_some() {
+        for env in envs.all() {
+            let processes = processes_info(env.as_ref()).await;
+            eprintln!("Environment {}: {} processes", env.id(), processes.len());
+            eprintln!(
+                "{:>10} {:>10} {:>6} {:>12} {:>14} {:>8}",
+                "ID", "MAILBOX", "LINKS", "MEMORY", "FUEL", "WAITING"
+            );
+            for (id, info) in processes {
+                eprintln!(
+                    "{:>10} {:>10} {:>6} {:>12} {:>14} {:>8}",
+                    id,
+                    info.mailbox_len,
+                    info.links,
+                    info.memory_size,
+                    info.fuel_consumed,
+                    info.waiting_in_receive
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove names from the cluster-wide registry after the processes owning them exit
+async fn remove_owned_names(
+    control: control::Client,
+    mut removed_names: UnboundedReceiver<RemovedName>,
+) {
+    while let Some((environment_id, name, registration)) = removed_names.recv().await {
+        // Another process could have registered the name since, it's only removed if it still
+        // points to the same process
+        if let Err(e) = control
+            .registry_remove_if(
+                environment_id,
+                name,
+                registration.node_id,
+                registration.process_id,
+            )
+            .await
+        {
+            log::warn!("Failed to remove name from the cluster-wide registry: {e}");
+        }
+    }
+}
+
 /// Parse a single key-value pair
 fn parse_key_val(s: &str) -> Result<(String, String)> {
     let scanner = Scanner::new(s.to_string());
@@ -1,25 +1,34 @@
 use std::fmt::Debug;
 use std::sync::Arc;
 
-use anyhow::Result;
-use dashmap::DashMap;
+use anyhow::{anyhow, Result};
 use hash_map_id::HashMapId;
+use lunatic_channel_api::{ChannelCtx, ChannelReceiverResources, ChannelSenderResources};
 use lunatic_distributed::{DistributedCtx, DistributedProcessState};
 use lunatic_error_api::{ErrorCtx, ErrorResource};
+use lunatic_messaging_api::{MessageCtx, SharedBufferResources};
 use lunatic_networking_api::{DnsIterator, TlsConnection, TlsListener};
 use lunatic_networking_api::{NetworkingCtx, TcpConnection};
-use lunatic_process::env::{Environment, LunaticEnvironment};
+use lunatic_process::env::{Environment, Environments, LunaticEnvironment};
+use lunatic_process::registry::ProcessRegistry;
 use lunatic_process::runtimes::wasmtime::{WasmtimeCompiledModule, WasmtimeRuntime};
-use lunatic_process::state::{ConfigResources, ProcessState};
+use lunatic_process::state::{ConfigResources, Hibernation, ProcessState};
 use lunatic_process::{
     config::ProcessConfig,
     state::{SignalReceiver, SignalSender},
 };
-use lunatic_process::{mailbox::MessageMailbox, message::Message};
+use lunatic_process::{
+    info::ProcessUsage,
+    mailbox::MessageMailbox,
+    message::{Message, SharedBuffer},
+};
 use lunatic_process_api::{ProcessConfigCtx, ProcessCtx};
+use lunatic_registry_api::{RegistryCtx, RegistryIteratorResources};
 use lunatic_stdout_capture::StdoutCapture;
+use lunatic_supervisor_api::{SupervisorCtx, SupervisorSpecResources};
 use lunatic_timer_api::{TimerCtx, TimerResources};
 use lunatic_wasi_api::{build_wasi, LunaticWasiCtx};
+use serde::{Deserialize, Serialize};
 use tokio::net::{TcpListener, UdpSocket};
 use tokio::sync::mpsc::unbounded_channel;
 use tokio::sync::Mutex;
@@ -49,6 +58,10 @@ pub struct DefaultProcessState {
     signal_mailbox: (SignalSender, SignalReceiver),
     // Messages sent to the process
     message_mailbox: MessageMailbox,
+    // Resource usage, shared with the signal handler
+    usage: ProcessUsage,
+    // Entry function and params to resume with, if the process asked to hibernate
+    hibernation: Option<Hibernation>,
     // Resources
     resources: Resources,
     // WASI
@@ -59,8 +72,6 @@ pub struct DefaultProcessState {
     wasi_stderr: Option<StdoutCapture>,
     // Set to true if the WASM module has been instantiated
     initialized: bool,
-    // Shared process registry
-    registry: Arc<DashMap<String, (u64, u64)>>,
 }
 
 impl DefaultProcessState {
@@ -70,11 +81,11 @@ impl DefaultProcessState {
         runtime: WasmtimeRuntime,
         module: Arc<WasmtimeCompiledModule<Self>>,
         config: Arc<DefaultProcessConfig>,
-        registry: Arc<DashMap<String, (u64, u64)>>,
     ) -> Result<Self> {
         let signal_mailbox = unbounded_channel();
         let signal_mailbox = (signal_mailbox.0, Arc::new(Mutex::new(signal_mailbox.1)));
-        let message_mailbox = MessageMailbox::default();
+        let message_mailbox =
+            MessageMailbox::new(config.get_mailbox_capacity(), config.get_mailbox_overflow());
         let state = Self {
             id: environment.get_next_process_id(),
             environment,
@@ -85,6 +96,8 @@ impl DefaultProcessState {
             message: None,
             signal_mailbox,
             message_mailbox,
+            usage: ProcessUsage::default(),
+            hibernation: None,
             resources: Resources::default(),
             wasi: build_wasi(
                 Some(config.command_line_arguments()),
@@ -94,26 +107,25 @@ impl DefaultProcessState {
             wasi_stdout: None,
             wasi_stderr: None,
             initialized: false,
-            registry,
         };
         Ok(state)
     }
-}
-
-impl ProcessState for DefaultProcessState {
-    type Config = DefaultProcessConfig;
 
-    fn new_state(
+    // Creates the state of a new process inside of `environment`, inheriting the runtime and the
+    // node connection of this process.
+    fn new_state_with_environment(
         &self,
+        environment: Arc<LunaticEnvironment>,
         module: Arc<WasmtimeCompiledModule<Self>>,
         config: Arc<DefaultProcessConfig>,
     ) -> Result<Self> {
         let signal_mailbox = unbounded_channel();
         let signal_mailbox = (signal_mailbox.0, Arc::new(Mutex::new(signal_mailbox.1)));
-        let message_mailbox = MessageMailbox::default();
+        let message_mailbox =
+            MessageMailbox::new(config.get_mailbox_capacity(), config.get_mailbox_overflow());
         let state = Self {
-            id: self.environment.get_next_process_id(),
-            environment: self.environment.clone(),
+            id: environment.get_next_process_id(),
+            environment,
             distributed: self.distributed.clone(),
             runtime: self.runtime.clone(),
             module: Some(module),
@@ -121,6 +133,8 @@ impl ProcessState for DefaultProcessState {
             message: None,
             signal_mailbox,
             message_mailbox,
+            usage: ProcessUsage::default(),
+            hibernation: None,
             resources: Resources::default(),
             wasi: build_wasi(
                 Some(config.command_line_arguments()),
@@ -130,10 +144,21 @@ impl ProcessState for DefaultProcessState {
             wasi_stdout: None,
             wasi_stderr: None,
             initialized: false,
-            registry: self.registry.clone(),
         };
         Ok(state)
     }
+}
+
+impl ProcessState for DefaultProcessState {
+    type Config = DefaultProcessConfig;
+
+    fn new_state(
+        &self,
+        module: Arc<WasmtimeCompiledModule<Self>>,
+        config: Arc<DefaultProcessConfig>,
+    ) -> Result<Self> {
+        self.new_state_with_environment(self.environment.clone(), module, config)
+    }
 
     fn state_for_instantiation() -> Self {
         let config = DefaultProcessConfig::default();
@@ -146,11 +171,12 @@ impl ProcessState for DefaultProcessState {
             distributed: None,
             runtime: None,
             module: None,
-            registry: Default::default(),
             config: Arc::new(config.clone()),
             message: None,
             signal_mailbox,
             message_mailbox,
+            usage: ProcessUsage::default(),
+            hibernation: None,
             resources: Resources::default(),
             wasi: build_wasi(
                 Some(config.command_line_arguments()),
@@ -168,11 +194,13 @@ impl ProcessState for DefaultProcessState {
         lunatic_error_api::register(linker)?;
         lunatic_process_api::register(linker)?;
         lunatic_messaging_api::register(linker)?;
+        lunatic_channel_api::register(linker)?;
         lunatic_timer_api::register(linker)?;
         lunatic_networking_api::register(linker)?;
         lunatic_version_api::register(linker)?;
         lunatic_wasi_api::register(linker)?;
         lunatic_registry_api::register(linker)?;
+        lunatic_supervisor_api::register(linker)?;
         lunatic_distributed_api::register(linker)?;
         Ok(())
     }
@@ -209,6 +237,78 @@ impl ProcessState for DefaultProcessState {
         &self.message_mailbox
     }
 
+    fn usage(&self) -> &ProcessUsage {
+        &self.usage
+    }
+
+    fn hibernation(&mut self) -> &mut Option<Hibernation> {
+        &mut self.hibernation
+    }
+
+    fn snapshot_resources(&self) -> Result<Vec<u8>> {
+        let resources = &self.resources;
+        let unserializable: Vec<String> = [
+            ("unread messages", self.message.is_some() as usize),
+            ("modules", resources.modules.len()),
+            ("timers", resources.timers.active()),
+            ("supervisor specs", resources.supervisor_specs.len()),
+            ("DNS iterators", resources.dns_iterators.len()),
+            ("TCP listeners", resources.tcp_listeners.len()),
+            ("TCP streams", resources.tcp_streams.len()),
+            ("TLS listeners", resources.tls_listeners.len()),
+            ("TLS streams", resources.tls_streams.len()),
+            ("UDP sockets", resources.udp_sockets.len()),
+            ("channel senders", resources.channel_senders.len()),
+            ("channel receivers", resources.channel_receivers.len()),
+            ("registry iterators", resources.registry_iterators.len()),
+        ]
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(name, count)| format!("{} {}", count, name))
+        .collect();
+        if !unserializable.is_empty() {
+            return Err(anyhow!(
+                "Process holds resources that can't be serialized: {}",
+                unserializable.join(", ")
+            ));
+        }
+
+        let resources = SerializedResources {
+            configs: resources
+                .configs
+                .iter()
+                .map(|(id, config)| (id, config.clone()))
+                .collect(),
+            errors: resources
+                .errors
+                .iter()
+                .map(|(id, error)| (id, error.to_string()))
+                .collect(),
+            shared_buffers: resources
+                .shared_buffers
+                .iter()
+                .map(|(id, buffer)| (id, buffer.to_vec()))
+                .collect(),
+        };
+        Ok(bincode::serialize(&resources)?)
+    }
+
+    fn restore_resources(&mut self, resources: &[u8]) -> Result<()> {
+        let resources: SerializedResources = bincode::deserialize(resources)?;
+        for (id, config) in resources.configs {
+            self.resources.configs.insert(id, config);
+        }
+        for (id, error) in resources.errors {
+            self.resources.errors.insert(id, anyhow!(error));
+        }
+        for (id, buffer) in resources.shared_buffers {
+            self.resources
+                .shared_buffers
+                .insert(id, Arc::new(SharedBuffer::new(buffer)));
+        }
+        Ok(())
+    }
+
     fn config_resources(&self) -> &ConfigResources<<DefaultProcessState as ProcessState>::Config> {
         &self.resources.configs
     }
@@ -219,8 +319,8 @@ impl ProcessState for DefaultProcessState {
         &mut self.resources.configs
     }
 
-    fn registry(&self) -> &Arc<DashMap<String, (u64, u64)>> {
-        &self.registry
+    fn registry(&self) -> &ProcessRegistry {
+        self.environment.registry()
     }
 }
 
@@ -235,7 +335,13 @@ impl Debug for DefaultProcessState {
 // Limit the maximum memory of the process depending on the environment it was spawned in.
 impl ResourceLimiter for DefaultProcessState {
     fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> bool {
-        desired <= self.config().get_max_memory()
+        let allowed = desired <= self.config().get_max_memory();
+        if allowed {
+            self.usage.set_memory_size(desired);
+        } else {
+            self.usage.set_memory_limit_reached();
+        }
+        allowed
     }
 
     fn table_growing(&mut self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
@@ -290,6 +396,36 @@ impl ProcessCtx<DefaultProcessState> for DefaultProcessState {
     fn environment(&self) -> Arc<dyn Environment> {
         self.environment.clone()
     }
+
+    fn node_id(&self) -> u64 {
+        self.distributed
+            .as_ref()
+            .map(|distributed| distributed.node_id())
+            .unwrap_or(0)
+    }
+
+    fn create_environment(&self, max_processes: Option<usize>) -> Option<u64> {
+        let environments = self.environment.environments()?;
+        Some(environments.create_isolated(max_processes).id())
+    }
+
+    fn new_state_in_environment(
+        &self,
+        module: Arc<WasmtimeCompiledModule<Self>>,
+        config: Arc<DefaultProcessConfig>,
+        environment_id: u64,
+    ) -> Result<Option<Self>> {
+        let environment = match self
+            .environment
+            .environments()
+            .and_then(|environments| environments.get(environment_id))
+        {
+            Some(environment) => environment,
+            None => return Ok(None),
+        };
+        self.new_state_with_environment(environment, module, config)
+            .map(Some)
+    }
 }
 
 impl NetworkingCtx for DefaultProcessState {
@@ -342,6 +478,16 @@ impl NetworkingCtx for DefaultProcessState {
     }
 }
 
+impl SupervisorCtx<DefaultProcessState> for DefaultProcessState {
+    fn supervisor_spec_resources(&self) -> &SupervisorSpecResources<Self> {
+        &self.resources.supervisor_specs
+    }
+
+    fn supervisor_spec_resources_mut(&mut self) -> &mut SupervisorSpecResources<Self> {
+        &mut self.resources.supervisor_specs
+    }
+}
+
 impl TimerCtx for DefaultProcessState {
     fn timer_resources(&self) -> &TimerResources {
         &self.resources.timers
@@ -352,6 +498,44 @@ impl TimerCtx for DefaultProcessState {
     }
 }
 
+impl MessageCtx for DefaultProcessState {
+    fn shared_buffer_resources(&self) -> &SharedBufferResources {
+        &self.resources.shared_buffers
+    }
+
+    fn shared_buffer_resources_mut(&mut self) -> &mut SharedBufferResources {
+        &mut self.resources.shared_buffers
+    }
+}
+
+impl ChannelCtx for DefaultProcessState {
+    fn channel_sender_resources(&self) -> &ChannelSenderResources {
+        &self.resources.channel_senders
+    }
+
+    fn channel_sender_resources_mut(&mut self) -> &mut ChannelSenderResources {
+        &mut self.resources.channel_senders
+    }
+
+    fn channel_receiver_resources(&self) -> &ChannelReceiverResources {
+        &self.resources.channel_receivers
+    }
+
+    fn channel_receiver_resources_mut(&mut self) -> &mut ChannelReceiverResources {
+        &mut self.resources.channel_receivers
+    }
+}
+
+impl RegistryCtx for DefaultProcessState {
+    fn registry_iterator_resources(&self) -> &RegistryIteratorResources {
+        &self.resources.registry_iterators
+    }
+
+    fn registry_iterator_resources_mut(&mut self) -> &mut RegistryIteratorResources {
+        &mut self.resources.registry_iterators
+    }
+}
+
 impl LunaticWasiCtx for DefaultProcessState {
     fn wasi(&self) -> &WasiCtx {
         &self.wasi
@@ -387,15 +571,28 @@ pub(crate) struct Resources {
     pub(crate) configs: HashMapId<DefaultProcessConfig>,
     pub(crate) modules: HashMapId<Arc<WasmtimeCompiledModule<DefaultProcessState>>>,
     pub(crate) timers: TimerResources,
+    pub(crate) supervisor_specs: SupervisorSpecResources<DefaultProcessState>,
     pub(crate) dns_iterators: HashMapId<DnsIterator>,
     pub(crate) tcp_listeners: HashMapId<TcpListener>,
     pub(crate) tcp_streams: HashMapId<Arc<TcpConnection>>,
     pub(crate) tls_listeners: HashMapId<TlsListener>,
     pub(crate) tls_streams: HashMapId<Arc<TlsConnection>>,
     pub(crate) udp_sockets: HashMapId<Arc<UdpSocket>>,
+    pub(crate) shared_buffers: SharedBufferResources,
+    pub(crate) channel_senders: ChannelSenderResources,
+    pub(crate) channel_receivers: ChannelReceiverResources,
+    pub(crate) registry_iterators: RegistryIteratorResources,
     pub(crate) errors: HashMapId<anyhow::Error>,
 }
 
+// The part of `Resources` that can be serialized into a snapshot.
+#[derive(Serialize, Deserialize)]
+struct SerializedResources {
+    configs: Vec<(u64, DefaultProcessConfig)>,
+    errors: Vec<(u64, String)>,
+    shared_buffers: Vec<(u64, Vec<u8>)>,
+}
+
 impl DistributedCtx<LunaticEnvironment> for DefaultProcessState {
     fn distributed_mut(&mut self) -> Result<&mut DistributedProcessState> {
         match self.distributed.as_mut() {
@@ -435,7 +632,8 @@ impl DistributedCtx<LunaticEnvironment> for DefaultProcessState {
     ) -> Result<Self> {
         let signal_mailbox = unbounded_channel();
         let signal_mailbox = (signal_mailbox.0, Arc::new(Mutex::new(signal_mailbox.1)));
-        let message_mailbox = MessageMailbox::default();
+        let message_mailbox =
+            MessageMailbox::new(config.get_mailbox_capacity(), config.get_mailbox_overflow());
         let state = Self {
             id: environment.get_next_process_id(),
             environment,
@@ -446,6 +644,8 @@ impl DistributedCtx<LunaticEnvironment> for DefaultProcessState {
             message: None,
             signal_mailbox,
             message_mailbox,
+            usage: ProcessUsage::default(),
+            hibernation: None,
             resources: Resources::default(),
             wasi: build_wasi(
                 Some(config.command_line_arguments()),
@@ -455,7 +655,6 @@ impl DistributedCtx<LunaticEnvironment> for DefaultProcessState {
             wasi_stdout: None,
             wasi_stderr: None,
             initialized: false,
-            registry: Default::default(), // TODO move registry into env?
         };
         Ok(state)
     }
@@ -482,14 +681,12 @@ mod tests {
         let raw_module = wat::parse_file("./wat/all_imports.wat").unwrap();
         let module = Arc::new(runtime.compile_module(raw_module.into()).unwrap());
         let env = Arc::new(lunatic_process::env::LunaticEnvironment::new(0));
-        let registry = Arc::new(dashmap::DashMap::new());
         let state = DefaultProcessState::new(
             env.clone(),
             None,
             runtime.clone(),
             module.clone(),
             Arc::new(config),
-            registry,
         )
         .unwrap();
 
@@ -497,4 +694,1223 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn hibernation_keeps_consumed_fuel() {
+        use crate::state::DefaultProcessState;
+        use crate::DefaultProcessConfig;
+        use lunatic_process::config::ProcessConfig;
+        use lunatic_process::message::{DataMessage, Message};
+        use lunatic_process::runtimes::wasmtime::WasmtimeRuntime;
+        use lunatic_process::wasm::spawn_wasm;
+        use lunatic_process::Signal;
+        use std::sync::Arc;
+
+        // Each call of `spin` consumes around 3/4 of the only unit of fuel the process gets.
+        let raw_module = wat::parse_str(
+            r#"
+            (module
+                (import "lunatic::process" "hibernate" (func $hibernate (param i32 i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "resume")
+                (func $spin (local i32)
+                    (local.set 0 (i32.const 15000))
+                    (loop $continue
+                        (br_if $continue (local.tee 0 (i32.sub (local.get 0) (i32.const 1))))))
+                (func (export "start")
+                    (call $spin)
+                    (call $hibernate (i32.const 0) (i32.const 6) (i32.const 0) (i32.const 0)))
+                (func (export "resume")
+                    (call $spin)))
+            "#,
+        )
+        .unwrap();
+
+        let mut config = DefaultProcessConfig::default();
+        config.set_max_fuel(Some(1));
+        let mut wasmtime_config = wasmtime::Config::new();
+        wasmtime_config.async_support(true).consume_fuel(true);
+        let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+        let module = Arc::new(runtime.compile_module(raw_module.into()).unwrap());
+        let env = Arc::new(lunatic_process::env::LunaticEnvironment::new(0));
+        let state = DefaultProcessState::new(
+            env.clone(),
+            None,
+            runtime.clone(),
+            module.clone(),
+            Arc::new(config),
+        )
+        .unwrap();
+
+        let (join, process) = spawn_wasm(env, runtime, &module, state, "start", Vec::new(), None)
+            .await
+            .unwrap();
+        // Wake the process up from hibernation
+        process.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+        let error = join
+            .await
+            .unwrap()
+            .expect_err("process must run out of fuel");
+        assert!(error.to_string().contains("all fuel consumed"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn monitoring_dead_process_delivers_process_died() {
+        use crate::state::DefaultProcessState;
+        use crate::DefaultProcessConfig;
+        use lunatic_process::runtimes::wasmtime::WasmtimeRuntime;
+        use lunatic_process::wasm::spawn_wasm;
+        use std::sync::Arc;
+
+        // Traps unless a `ProcessDied` message for the never spawned process 999 arrives
+        let raw_module = wat::parse_str(
+            r#"
+            (module
+                (import "lunatic::process" "monitor" (func $monitor (param i64)))
+                (import "lunatic::message" "receive" (func $receive (param i32 i32 i64) (result i32)))
+                (import "lunatic::message" "get_process_died_id" (func $process_died_id (result i64)))
+                (func (export "start")
+                    (call $monitor (i64.const 999))
+                    (if (i32.ne (call $receive (i32.const 0) (i32.const 0) (i64.const 5000)) (i32.const 2))
+                        (then unreachable))
+                    (if (i64.ne (call $process_died_id) (i64.const 999))
+                        (then unreachable))))
+            "#,
+        )
+        .unwrap();
+
+        let mut wasmtime_config = wasmtime::Config::new();
+        wasmtime_config.async_support(true).consume_fuel(true);
+        let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+        let module = Arc::new(runtime.compile_module(raw_module.into()).unwrap());
+        let env = Arc::new(lunatic_process::env::LunaticEnvironment::new(0));
+        let state = DefaultProcessState::new(
+            env.clone(),
+            None,
+            runtime.clone(),
+            module.clone(),
+            Arc::new(DefaultProcessConfig::default()),
+        )
+        .unwrap();
+
+        let (join, _) = spawn_wasm(env, runtime, &module, state, "start", Vec::new(), None)
+            .await
+            .unwrap();
+        join.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn snapshot_round_trip() {
+        use crate::state::DefaultProcessState;
+        use crate::DefaultProcessConfig;
+        use lunatic_process::runtimes::wasmtime::WasmtimeRuntime;
+        use lunatic_process::snapshot::ProcessSnapshot;
+        use lunatic_process::wasm::{restore_wasm, snapshot_wasm};
+        use lunatic_process_api::ProcessConfigCtx;
+        use std::sync::Arc;
+
+        // The config ID is kept in memory and the config itself in the resources, `resume` traps
+        // unless both survive the snapshot.
+        let raw_module = wat::parse_str(
+            r#"
+            (module
+                (import "lunatic::process" "hibernate" (func $hibernate (param i32 i32 i32 i32)))
+                (import "lunatic::process" "create_config" (func $create_config (result i64)))
+                (import "lunatic::process" "config_set_max_fuel" (func $set_max_fuel (param i64 i64)))
+                (import "lunatic::process" "config_get_max_fuel" (func $get_max_fuel (param i64) (result i64)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "resume")
+                (func (export "start")
+                    (i64.store (i32.const 0) (call $create_config))
+                    (call $set_max_fuel (i64.load (i32.const 0)) (i64.const 7))
+                    (call $hibernate (i32.const 16) (i32.const 6) (i32.const 0) (i32.const 0)))
+                (func (export "resume")
+                    (if (i64.ne (call $get_max_fuel (i64.load (i32.const 0))) (i64.const 7))
+                        (then unreachable))))
+            "#,
+        )
+        .unwrap();
+
+        let mut config = DefaultProcessConfig::default();
+        config.set_can_create_configs(true);
+        let config = Arc::new(config);
+        let mut wasmtime_config = wasmtime::Config::new();
+        wasmtime_config.async_support(true).consume_fuel(true);
+        let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+        let module = Arc::new(runtime.compile_module(raw_module.into()).unwrap());
+        let env = Arc::new(lunatic_process::env::LunaticEnvironment::new(0));
+
+        let state = DefaultProcessState::new(
+            env.clone(),
+            None,
+            runtime.clone(),
+            module.clone(),
+            config.clone(),
+        )
+        .unwrap();
+        let snapshot = snapshot_wasm(runtime.clone(), &module, state, "start", Vec::new())
+            .await
+            .unwrap();
+        let snapshot = ProcessSnapshot::from_bytes(&snapshot.to_bytes().unwrap()).unwrap();
+
+        let state =
+            DefaultProcessState::new(env.clone(), None, runtime.clone(), module.clone(), config)
+                .unwrap();
+        let (join, _) = restore_wasm(env, runtime, &module, state, snapshot, None)
+            .await
+            .unwrap();
+        join.await.unwrap().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod supervisor_tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use lunatic_process::env::{Environment, LunaticEnvironment};
+    use lunatic_process::message::{DataMessage, Message};
+    use lunatic_process::runtimes::wasmtime::WasmtimeRuntime;
+    use lunatic_process::{NativeProcess, Process, Signal};
+    use lunatic_supervisor_api::{start_supervisor, ChildSpec, Strategy, SupervisorSpec};
+
+    use crate::state::DefaultProcessState;
+    use crate::DefaultProcessConfig;
+
+    // Starts a supervisor with three children that wait for messages forever and returns the
+    // supervisor together with the ids of its children.
+    async fn start_supervised_children(
+        env: Arc<LunaticEnvironment>,
+        strategy: Strategy,
+        max_restarts: u32,
+        parent: Option<Arc<dyn Process>>,
+    ) -> (NativeProcess, Vec<u64>) {
+        let raw_module = wat::parse_str(
+            r#"
+            (module
+                (import "lunatic::message" "receive" (func $receive (param i32 i32 i64) (result i32)))
+                (func (export "wait")
+                    (drop (call $receive (i32.const 0) (i32.const 0) (i64.const -1)))))
+            "#,
+        )
+        .unwrap();
+
+        let mut wasmtime_config = wasmtime::Config::new();
+        wasmtime_config.async_support(true).consume_fuel(true);
+        let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+        let module = Arc::new(runtime.compile_module(raw_module.into()).unwrap());
+        let config = Arc::new(DefaultProcessConfig::default());
+        let template = DefaultProcessState::new(
+            env.clone(),
+            None,
+            runtime.clone(),
+            module.clone(),
+            config.clone(),
+        )
+        .unwrap();
+
+        let mut spec = SupervisorSpec::new(strategy, max_restarts, Duration::from_secs(60));
+        for _ in 0..3 {
+            spec.add_child(ChildSpec {
+                module: module.clone(),
+                config: config.clone(),
+                function: "wait".to_string(),
+                params: Vec::new(),
+            });
+        }
+        let supervisor = start_supervisor(
+            env.clone(),
+            runtime,
+            template,
+            spec,
+            parent.map(|parent| (None, parent)),
+        );
+        let children = wait_for_children(&env, supervisor.id(), |_| true).await;
+        (supervisor, children)
+    }
+
+    // Waits until the supervisor runs three children matching `predicate` and returns their
+    // sorted ids.
+    async fn wait_for_children<P: Fn(&[u64]) -> bool>(
+        env: &LunaticEnvironment,
+        supervisor: u64,
+        predicate: P,
+    ) -> Vec<u64> {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let mut children: Vec<u64> = env
+                    .processes()
+                    .iter()
+                    .map(|process| process.id())
+                    // Children are always spawned after the supervisor
+                    .filter(|id| *id > supervisor)
+                    .collect();
+                children.sort_unstable();
+                if children.len() == 3 && predicate(&children) {
+                    return children;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("supervisor children didn't start in time")
+    }
+
+    // Kills the child `id` and waits until the supervisor has replaced it.
+    async fn kill_child(env: &LunaticEnvironment, supervisor: u64, id: u64) -> Vec<u64> {
+        env.get_process(id).unwrap().send(Signal::Kill);
+        wait_for_children(env, supervisor, |children| !children.contains(&id)).await
+    }
+
+    // Waits until the supervisor and all of its children are gone.
+    async fn wait_for_empty_environment(env: &LunaticEnvironment) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while env.process_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("supervisor and its children didn't exit in time");
+    }
+
+    #[tokio::test]
+    async fn one_for_one_restarts_failed_child() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (supervisor, children) =
+            start_supervised_children(env.clone(), Strategy::OneForOne, 5, None).await;
+        let restarted = kill_child(&env, supervisor.id(), children[1]).await;
+        assert_eq!(restarted[..2], [children[0], children[2]]);
+        assert!(restarted[2] > children[2]);
+    }
+
+    #[tokio::test]
+    async fn one_for_all_restarts_all_children() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (supervisor, children) =
+            start_supervised_children(env.clone(), Strategy::OneForAll, 5, None).await;
+        let restarted = kill_child(&env, supervisor.id(), children[1]).await;
+        assert!(restarted.iter().all(|id| *id > children[2]));
+    }
+
+    #[tokio::test]
+    async fn rest_for_one_restarts_later_children() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (supervisor, children) =
+            start_supervised_children(env.clone(), Strategy::RestForOne, 5, None).await;
+        let restarted = kill_child(&env, supervisor.id(), children[1]).await;
+        assert_eq!(restarted[0], children[0]);
+        assert!(restarted[1..].iter().all(|id| *id > children[2]));
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_restart_intensity_is_exceeded() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (supervisor, children) =
+            start_supervised_children(env.clone(), Strategy::OneForOne, 1, None).await;
+        // The first failure is inside of the allowed intensity
+        let restarted = kill_child(&env, supervisor.id(), children[0]).await;
+        // The second one isn't
+        env.get_process(restarted[2]).unwrap().send(Signal::Kill);
+        wait_for_empty_environment(&env).await;
+    }
+
+    #[tokio::test]
+    async fn exits_when_parent_dies() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        // The parent fails as soon as it receives a message
+        let (_, parent) = lunatic_process::spawn(env.clone(), |_this, mailbox| async move {
+            mailbox.pop(None).await;
+            Err::<(), _>(anyhow::anyhow!("parent failed"))
+        });
+        let (supervisor, _) = start_supervised_children(
+            env.clone(),
+            Strategy::OneForOne,
+            5,
+            Some(Arc::new(parent.clone())),
+        )
+        .await;
+        parent.send(Signal::Link(None, Arc::new(supervisor)));
+
+        parent.send(Signal::Message(Message::Data(DataMessage::new(None, 0))));
+        wait_for_empty_environment(&env).await;
+    }
+}
+
+#[cfg(test)]
+mod host_function_tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Once};
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use lunatic_process::config::MailboxOverflow;
+    use lunatic_process::config::ProcessConfig;
+    use lunatic_process::env::{Environment, LunaticEnvironment};
+    use lunatic_process::info::ProcessInfo;
+    use lunatic_process::mailbox::MessageMailbox;
+    use lunatic_process::message::{DataMessage, Message, MessageSender};
+    use lunatic_process::registry::Registration;
+    use lunatic_process::runtimes::wasmtime::WasmtimeRuntime;
+    use lunatic_process::wasm::spawn_wasm;
+    use lunatic_process::{process_info, DeathReason, Process, Signal, WasmProcess};
+    use lunatic_process_api::ProcessConfigCtx;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, Key, KeyName, Recorder, SharedString, Unit,
+    };
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+    use tokio::task::JoinHandle;
+    use wasmtime::Val;
+
+    use crate::state::DefaultProcessState;
+    use crate::DefaultProcessConfig;
+
+    // Runs `function` of the module inside of `env` until it finishes.
+    async fn run(
+        env: Arc<LunaticEnvironment>,
+        config: DefaultProcessConfig,
+        wat: &str,
+        function: &str,
+        params: Vec<Val>,
+    ) -> Result<DefaultProcessState> {
+        let (join, _) = spawn_module(env, config, wat, function, params).await;
+        join.await.unwrap()
+    }
+
+    // Spawns `function` of the module inside of `env`.
+    async fn spawn_module(
+        env: Arc<LunaticEnvironment>,
+        config: DefaultProcessConfig,
+        wat: &str,
+        function: &str,
+        params: Vec<Val>,
+    ) -> (JoinHandle<Result<DefaultProcessState>>, Arc<dyn Process>) {
+        let raw_module = wat::parse_str(wat).unwrap();
+        let mut wasmtime_config = wasmtime::Config::new();
+        wasmtime_config.async_support(true).consume_fuel(true);
+        let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+        let module = Arc::new(runtime.compile_module(raw_module.into()).unwrap());
+        let state = DefaultProcessState::new(
+            env.clone(),
+            None,
+            runtime.clone(),
+            module.clone(),
+            Arc::new(config),
+        )
+        .unwrap();
+        spawn_wasm(env, runtime, &module, state, function, params, None)
+            .await
+            .unwrap()
+    }
+
+    // Adds a process with a bounded mailbox to the environment, that only collects signals.
+    pub(super) fn add_receiver(
+        env: &LunaticEnvironment,
+        capacity: usize,
+    ) -> (u64, MessageMailbox, UnboundedReceiver<Signal>) {
+        let id = env.get_next_process_id();
+        let (sender, receiver) = unbounded_channel();
+        let mailbox = MessageMailbox::new(Some(capacity), MailboxOverflow::Backpressure);
+        env.add_process(
+            id,
+            Arc::new(WasmProcess::with_mailbox(id, sender, mailbox.clone())),
+        );
+        (id, mailbox, receiver)
+    }
+
+    // Number of data messages rejected because of their size, over all tests.
+    static REJECTED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+    struct RejectedMessages;
+
+    impl CounterFn for RejectedMessages {
+        fn increment(&self, value: u64) {
+            REJECTED_MESSAGES.fetch_add(value, Ordering::SeqCst);
+        }
+
+        fn absolute(&self, _value: u64) {}
+    }
+
+    // Only records the `lunatic.process.messages.data.rejected` counter.
+    struct TestRecorder;
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key) -> Counter {
+            if key.name() == "lunatic.process.messages.data.rejected" {
+                Counter::from_arc(Arc::new(RejectedMessages))
+            } else {
+                Counter::noop()
+            }
+        }
+
+        fn register_gauge(&self, _: &Key) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, _: &Key) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    // Returns the number of rejected data messages so far, the recorder is installed on first use.
+    pub(super) fn rejected_messages() -> u64 {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| metrics::set_boxed_recorder(Box::new(TestRecorder)).unwrap());
+        REJECTED_MESSAGES.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn write_data_rejects_messages_over_max_size() {
+        let rejected = rejected_messages();
+        let mut config = DefaultProcessConfig::default();
+        config.set_max_message_size(Some(16));
+        run(
+            Arc::new(LunaticEnvironment::new(0)),
+            config,
+            r#"
+            (module
+                (import "lunatic::message" "create_data" (func $create_data (param i64 i64)))
+                (import "lunatic::message" "write_data" (func $write_data (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "write")
+                    ;; The capacity hint is capped at the maximum message size
+                    (call $create_data (i64.const 0) (i64.const -1))
+                    (if (i32.ne (call $write_data (i32.const 0) (i32.const 16)) (i32.const 16))
+                        (then unreachable))
+                    (if (i32.ne (call $write_data (i32.const 0) (i32.const 1)) (i32.const 0))
+                        (then unreachable))))
+            "#,
+            "write",
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+        assert!(rejected_messages() > rejected);
+    }
+
+    // Checks the processes listed by `list` and `list_info`, the first one is **idle** with one
+    // message in its mailbox and the second one is the calling process.
+    const LIST: &str = r#"
+        (module
+            (import "lunatic::process" "process_id" (func $process_id (result i64)))
+            (import "lunatic::process" "list" (func $list (param i32 i32) (result i32)))
+            (import "lunatic::process" "list_info" (func $list_info (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func $expect (param i64 i64)
+                (if (i64.ne (local.get 0) (local.get 1)) (then unreachable)))
+            (func (export "list") (param $idle i64)
+                ;; Only the first ID fits, but both processes are counted
+                (call $expect (i64.extend_i32_u (call $list (i32.const 0) (i32.const 1))) (i64.const 2))
+                (call $expect (i64.load (i32.const 0)) (local.get $idle))
+                (call $expect (i64.load (i32.const 8)) (i64.const 0))
+
+                (call $expect (i64.extend_i32_u (call $list_info (i32.const 16) (i32.const 2))) (i64.const 2))
+                ;; id, mailbox length, links, memory size, fuel and waiting in receive
+                (call $expect (i64.load (i32.const 16)) (local.get $idle))
+                (call $expect (i64.load (i32.const 24)) (i64.const 1))
+                (call $expect (i64.load (i32.const 32)) (i64.const 0))
+                (call $expect (i64.load (i32.const 40)) (i64.const 0))
+                (call $expect (i64.load (i32.const 48)) (i64.const 0))
+                (call $expect (i64.load (i32.const 56)) (i64.const 1))
+                (call $expect (i64.load (i32.const 64)) (call $process_id))
+                (call $expect (i64.load (i32.const 72)) (i64.const 0))
+                (call $expect (i64.load (i32.const 80)) (i64.const 0))
+                (call $expect (i64.load (i32.const 88)) (i64.const 65536))
+                (if (i64.eqz (i64.load (i32.const 96))) (then unreachable))
+                (call $expect (i64.load (i32.const 104)) (i64.const 0))))
+    "#;
+
+    // Spawns a process that waits forever, with one message in its mailbox.
+    async fn spawn_idle(env: &Arc<LunaticEnvironment>) -> u64 {
+        let (_, idle) = lunatic_process::spawn(env.clone(), |_, mailbox| async move {
+            mailbox.pop(Some(&[0])).await;
+            Ok(())
+        });
+        let message = DataMessage::new_from_vec(Some(1), Vec::new());
+        idle.send(Signal::Message(Message::Data(message)));
+        loop {
+            let info = process_info(&idle).await.unwrap();
+            if info.waiting_in_receive && info.mailbox_len == 1 {
+                return idle.id();
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn list_processes() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let idle = spawn_idle(&env).await;
+        let mut config = DefaultProcessConfig::default();
+        config.set_can_list_processes(true);
+        let params = vec![Val::I64(idle as i64)];
+        run(env, config, LIST, "list", params).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn list_processes_requires_permission() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let idle = spawn_idle(&env).await;
+        let params = vec![Val::I64(idle as i64)];
+        let result = run(env, Default::default(), LIST, "list", params).await;
+        assert!(result.is_err());
+    }
+
+    // Checks the information `info` writes about **idle**, the calling process and a process that
+    // doesn't exist.
+    const INFO: &str = r#"
+        (module
+            (import "lunatic::process" "process_id" (func $process_id (result i64)))
+            (import "lunatic::process" "info" (func $info (param i64 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func $expect (param i64 i64)
+                (if (i64.ne (local.get 0) (local.get 1)) (then unreachable)))
+            (func (export "info") (param $idle i64)
+                (call $expect (i64.extend_i32_u (call $info (local.get $idle) (i32.const 0))) (i64.const 0))
+                ;; mailbox length, links, memory size, fuel and waiting in receive
+                (call $expect (i64.load (i32.const 0)) (i64.const 1))
+                (call $expect (i64.load (i32.const 8)) (i64.const 0))
+                (call $expect (i64.load (i32.const 16)) (i64.const 0))
+                (call $expect (i64.load (i32.const 24)) (i64.const 0))
+                (call $expect (i64.load (i32.const 32)) (i64.const 1))
+
+                (call $expect (i64.extend_i32_u (call $info (call $process_id) (i32.const 40))) (i64.const 0))
+                (call $expect (i64.load (i32.const 40)) (i64.const 0))
+                (call $expect (i64.load (i32.const 48)) (i64.const 0))
+                (call $expect (i64.load (i32.const 56)) (i64.const 65536))
+                ;; The fuel of the calling process is up to date
+                (if (i64.eqz (i64.load (i32.const 64))) (then unreachable))
+                (call $expect (i64.load (i32.const 72)) (i64.const 0))
+
+                (call $expect (i64.extend_i32_u (call $info (i64.const -1) (i32.const 80))) (i64.const 1))))
+    "#;
+
+    #[tokio::test]
+    async fn info_layout() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let idle = spawn_idle(&env).await;
+        let params = vec![Val::I64(idle as i64)];
+        run(env, Default::default(), INFO, "info", params)
+            .await
+            .unwrap();
+    }
+
+    // Waits until the process is waiting in `receive` with an empty mailbox.
+    async fn wait_in_receive(process: &dyn Process) -> ProcessInfo {
+        let wait = async {
+            loop {
+                let info = process_info(process).await.unwrap();
+                if info.waiting_in_receive && info.mailbox_len == 0 {
+                    return info;
+                }
+                tokio::task::yield_now().await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn info_reports_fuel_as_of_last_receive() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (join, process) = spawn_module(
+            env,
+            Default::default(),
+            r#"
+            (module
+                (import "lunatic::message" "receive" (func $receive (param i32 i32 i64) (result i32)))
+                (memory (export "memory") 1)
+                (func $burn (local $i i32)
+                    (loop $continue
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $continue (i32.lt_u (local.get $i) (i32.const 1000)))))
+                (func (export "receive_twice")
+                    (call $burn)
+                    (drop (call $receive (i32.const 0) (i32.const 0) (i64.const -1)))
+                    (call $burn)
+                    (drop (call $receive (i32.const 0) (i32.const 0) (i64.const -1)))))
+            "#,
+            "receive_twice",
+            Vec::new(),
+        )
+        .await;
+        let send = || {
+            let message = DataMessage::new_from_vec(None, Vec::new());
+            process.send(Signal::Message(Message::Data(message)));
+        };
+
+        let first = wait_in_receive(process.as_ref()).await;
+        assert_eq!(first.memory_size, 65536);
+        assert!(first.fuel_consumed > 0);
+        send();
+        let second = wait_in_receive(process.as_ref()).await;
+        assert!(second.fuel_consumed > first.fuel_consumed);
+        send();
+        join.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_stamps_sender() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (receiver, _, mut signals) = add_receiver(&env, 1);
+        let state = run(
+            env,
+            Default::default(),
+            r#"
+            (module
+                (import "lunatic::message" "create_data" (func $create_data (param i64 i64)))
+                (import "lunatic::message" "send" (func $send (param i64) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "send") (param $receiver i64)
+                    (call $create_data (i64.const 0) (i64.const 0))
+                    (if (call $send (local.get $receiver)) (then unreachable))))
+            "#,
+            "send",
+            vec![Val::I64(receiver as i64)],
+        )
+        .await
+        .unwrap();
+        match signals.try_recv() {
+            Ok(Signal::Message(message)) => assert_eq!(
+                message.sender(),
+                Some(MessageSender {
+                    node_id: 0,
+                    process_id: state.id,
+                })
+            ),
+            signal => panic!("Unexpected signal {:?}", signal),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_sender_reads_sender() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (join, process) = spawn_module(
+            env,
+            Default::default(),
+            r#"
+            (module
+                (import "lunatic::message" "receive" (func $receive (param i32 i32 i64) (result i32)))
+                (import "lunatic::message" "get_sender" (func $get_sender (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "receive")
+                    (drop (call $receive (i32.const 0) (i32.const 0) (i64.const -1)))
+                    (if (call $get_sender (i32.const 0) (i32.const 8)) (then unreachable))
+                    (if (i64.ne (i64.load (i32.const 0)) (i64.const 3)) (then unreachable))
+                    (if (i64.ne (i64.load (i32.const 8)) (i64.const 4)) (then unreachable))
+                    ;; Messages without a sender
+                    (drop (call $receive (i32.const 0) (i32.const 0) (i64.const -1)))
+                    (if (i32.ne (call $get_sender (i32.const 0) (i32.const 8)) (i32.const 1))
+                        (then unreachable))))
+            "#,
+            "receive",
+            Vec::new(),
+        )
+        .await;
+        let mut message = DataMessage::new_from_vec(None, Vec::new());
+        message.sender = Some(MessageSender {
+            node_id: 3,
+            process_id: 4,
+        });
+        process.send(Signal::Message(Message::Data(message)));
+        let message = DataMessage::new_from_vec(None, Vec::new());
+        process.send(Signal::Message(Message::Data(message)));
+        join.await.unwrap().unwrap();
+    }
+
+    // Sends the shared buffer "abc" to **receiver** or broadcasts it to the group "group", then
+    // drops it.
+    const SHARE: &str = r#"
+        (module
+            (import "lunatic::message" "create_data" (func $create_data (param i64 i64)))
+            (import "lunatic::message" "create_shared_buffer" (func $create_shared_buffer (param i32 i32) (result i64)))
+            (import "lunatic::message" "push_shared_buffer" (func $push_shared_buffer (param i64) (result i64)))
+            (import "lunatic::message" "drop_shared_buffer" (func $drop_shared_buffer (param i64)))
+            (import "lunatic::message" "send" (func $send (param i64) (result i32)))
+            (import "lunatic::message" "broadcast" (func $broadcast (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "abcgroup")
+            (func $message (result i64)
+                (local $buffer i64)
+                (local.set $buffer (call $create_shared_buffer (i32.const 0) (i32.const 3)))
+                (call $create_data (i64.const 0) (i64.const 0))
+                (if (i64.ne (call $push_shared_buffer (local.get $buffer)) (i64.const 0))
+                    (then unreachable))
+                (local.get $buffer))
+            (func (export "send") (param $receiver i64)
+                (local $buffer i64)
+                (local.set $buffer (call $message))
+                (if (call $send (local.get $receiver)) (then unreachable))
+                (call $drop_shared_buffer (local.get $buffer)))
+            (func (export "broadcast")
+                (local $buffer i64)
+                (local.set $buffer (call $message))
+                (if (i32.ne (call $broadcast (i32.const 3) (i32.const 5)) (i32.const 2))
+                    (then unreachable))
+                (call $drop_shared_buffer (local.get $buffer))))
+    "#;
+
+    #[tokio::test]
+    async fn shared_buffer_outlives_sender() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (join, receiver) = spawn_module(
+            env.clone(),
+            Default::default(),
+            r#"
+            (module
+                (import "lunatic::message" "receive" (func $receive (param i32 i32 i64) (result i32)))
+                (import "lunatic::message" "take_shared_buffer" (func $take_shared_buffer (param i64) (result i64)))
+                (import "lunatic::message" "shared_buffer_size" (func $shared_buffer_size (param i64) (result i64)))
+                (import "lunatic::message" "read_shared_buffer" (func $read_shared_buffer (param i64 i64 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "read")
+                    (local $buffer i64)
+                    (drop (call $receive (i32.const 0) (i32.const 0) (i64.const -1)))
+                    (local.set $buffer (call $take_shared_buffer (i64.const 0)))
+                    ;; Wait until the sender finished
+                    (drop (call $receive (i32.const 0) (i32.const 0) (i64.const -1)))
+                    (if (i64.ne (call $shared_buffer_size (local.get $buffer)) (i64.const 3))
+                        (then unreachable))
+                    (if (i32.ne (call $read_shared_buffer (local.get $buffer) (i64.const 1) (i32.const 0) (i32.const 10)) (i32.const 2))
+                        (then unreachable))
+                    (if (i32.ne (i32.load16_u (i32.const 0)) (i32.const 0x6362))
+                        (then unreachable))
+                    (if (call $read_shared_buffer (local.get $buffer) (i64.const 3) (i32.const 0) (i32.const 10))
+                        (then unreachable))))
+            "#,
+            "read",
+            Vec::new(),
+        )
+        .await;
+
+        let params = vec![Val::I64(receiver.id() as i64)];
+        run(env, Default::default(), SHARE, "send", params)
+            .await
+            .unwrap();
+        let message = DataMessage::new_from_vec(None, Vec::new());
+        receiver.send(Signal::Message(Message::Data(message)));
+        join.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn broadcast_shares_buffer_without_copy() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let mut members = Vec::new();
+        for _ in 0..2 {
+            let (id, _, signals) = add_receiver(&env, 1);
+            env.groups().join("group", id);
+            members.push(signals);
+        }
+        run(env, Default::default(), SHARE, "broadcast", Vec::new())
+            .await
+            .unwrap();
+
+        let mut buffers: Vec<_> = members
+            .iter_mut()
+            .map(|signals| match signals.try_recv() {
+                Ok(Signal::Message(Message::Data(mut message))) => {
+                    message.take_shared_buffer(0).unwrap()
+                }
+                signal => panic!("Unexpected signal {:?}", signal),
+            })
+            .collect();
+        assert!(Arc::ptr_eq(&buffers[0], &buffers[1]));
+        assert_eq!(&buffers[0][..], b"abc");
+        // The sender dropped its reference, only the receivers hold onto the buffer
+        assert_eq!(Arc::strong_count(&buffers[0]), 2);
+        buffers.pop();
+        assert_eq!(Arc::strong_count(&buffers[0]), 1);
+    }
+
+    // Sends a message tagged with 7 to the name "test" and traps unless `send_to_name` returns
+    // the expected code. If the message wasn't sent, it must still be in the scratch area.
+    const SEND_TO_NAME: &str = r#"
+        (module
+            (import "lunatic::message" "create_data" (func $create_data (param i64 i64)))
+            (import "lunatic::message" "get_tag" (func $get_tag (result i64)))
+            (import "lunatic::message" "send_to_name" (func $send_to_name (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "test")
+            (func (export "send") (param $expected i32)
+                (call $create_data (i64.const 7) (i64.const 0))
+                (if (i32.ne (call $send_to_name (i32.const 0) (i32.const 4)) (local.get $expected))
+                    (then unreachable))
+                (if (i32.ne (local.get $expected) (i32.const 0))
+                    (then (if (i64.ne (call $get_tag) (i64.const 7))
+                        (then unreachable))))))
+    "#;
+
+    fn register_test_name(env: &LunaticEnvironment, process_id: u64) {
+        env.registry().put(
+            "test",
+            Registration {
+                node_id: 0,
+                process_id,
+                owner: None,
+                metadata: Vec::new(),
+            },
+        );
+    }
+
+    #[tokio::test]
+    async fn send_to_name_delivers_locally() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (id, _, mut signals) = add_receiver(&env, 1);
+        register_test_name(&env, id);
+
+        let params = vec![Val::I32(0)];
+        run(env, Default::default(), SEND_TO_NAME, "send", params)
+            .await
+            .unwrap();
+        match signals.try_recv() {
+            Ok(Signal::Message(message)) => assert_eq!(message.tag(), Some(7)),
+            signal => panic!("Unexpected signal {:?}", signal),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_to_name_keeps_message_if_name_is_missing() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let params = vec![Val::I32(2)];
+        run(env, Default::default(), SEND_TO_NAME, "send", params)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_to_name_keeps_message_if_mailbox_is_full() {
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let (id, mailbox, mut signals) = add_receiver(&env, 1);
+        mailbox.push(Message::Data(DataMessage::new(None, 0)));
+        register_test_name(&env, id);
+
+        let params = vec![Val::I32(1)];
+        run(env, Default::default(), SEND_TO_NAME, "send", params)
+            .await
+            .unwrap();
+        assert!(signals.try_recv().is_err());
+    }
+
+    // Calls `start` of the module and returns the reason reported to linked processes.
+    async fn death_reason(config: DefaultProcessConfig, wat: &str) -> DeathReason {
+        let raw_module = wat::parse_str(wat).unwrap();
+        let mut wasmtime_config = wasmtime::Config::new();
+        wasmtime_config.async_support(true).consume_fuel(true);
+        let runtime = WasmtimeRuntime::new(&wasmtime_config).unwrap();
+        let module = Arc::new(runtime.compile_module(raw_module.into()).unwrap());
+        let env = Arc::new(LunaticEnvironment::new(0));
+        let state =
+            DefaultProcessState::new(env, None, runtime.clone(), module.clone(), Arc::new(config))
+                .unwrap();
+        let instance = runtime.instantiate(&module, state).await.unwrap();
+        instance.call("start", Vec::new()).await.death_reason()
+    }
+
+    #[tokio::test]
+    async fn trap_death_reason() {
+        // The memory is full, but the trap is unrelated to it
+        let mut config = DefaultProcessConfig::default();
+        config.set_max_memory(64 * 1024);
+        let reason = death_reason(
+            config,
+            r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "start") unreachable))
+            "#,
+        )
+        .await;
+        assert!(matches!(reason, DeathReason::Trap(_)), "{:?}", reason);
+    }
+
+    #[tokio::test]
+    async fn out_of_fuel_death_reason() {
+        let mut config = DefaultProcessConfig::default();
+        config.set_max_fuel(Some(1));
+        let reason = death_reason(
+            config,
+            r#"
+            (module
+                (func (export "start") (loop $forever (br $forever))))
+            "#,
+        )
+        .await;
+        assert_eq!(reason, DeathReason::OutOfFuel);
+    }
+
+    #[tokio::test]
+    async fn memory_limit_death_reason() {
+        // Aborts like an allocator would, after the memory couldn't grow
+        let mut config = DefaultProcessConfig::default();
+        config.set_max_memory(64 * 1024);
+        let reason = death_reason(
+            config,
+            r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "start")
+                    (if (i32.eq (memory.grow (i32.const 1)) (i32.const -1))
+                        (then unreachable))))
+            "#,
+        )
+        .await;
+        assert_eq!(reason, DeathReason::MemoryLimit);
+    }
+
+    #[tokio::test]
+    async fn exit_code_death_reason() {
+        let reason = death_reason(
+            DefaultProcessConfig::default(),
+            r#"
+            (module
+                (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+                (memory (export "memory") 1)
+                (func (export "start") (call $proc_exit (i32.const 3))))
+            "#,
+        )
+        .await;
+        assert_eq!(reason, DeathReason::ExitCode(3));
+    }
+}
+
+#[cfg(test)]
+mod distributed_tests {
+    use std::collections::HashMap;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use lunatic_distributed::distributed::message::{serialize_resources, ClientError, Resource};
+    use lunatic_distributed::distributed::server::ServerCtx;
+    use lunatic_distributed::{control, distributed, quic, DistributedProcessState};
+    use lunatic_process::env::{Environments, LunaticEnvironment, LunaticEnvironments};
+    use lunatic_process::message::{DataMessage, Message, MessageSender, SharedBuffer};
+    use lunatic_process::runtimes::wasmtime::{default_config, WasmtimeRuntime};
+    use lunatic_process::runtimes::Modules;
+    use lunatic_process::Signal;
+    use uuid::Uuid;
+
+    use super::host_function_tests::{add_receiver, rejected_messages};
+    use crate::state::DefaultProcessState;
+
+    struct TestNode {
+        id: u64,
+        // Environment 1 of the node
+        env: Arc<LunaticEnvironment>,
+        distributed: DistributedProcessState,
+        runtime: WasmtimeRuntime,
+    }
+
+    // Returns a local address that isn't used at the moment.
+    fn free_address() -> SocketAddr {
+        UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    fn start_control_server() -> SocketAddr {
+        let address = free_address();
+        let ca_cert = control::server::root_cert(true, None, None).unwrap();
+        tokio::task::spawn(control::server::control_server(address, ca_cert));
+        address
+    }
+
+    // Registers a new node with the control server and starts its node server.
+    async fn start_node(control_address: SocketAddr, max_message_size: Option<usize>) -> TestNode {
+        let node_address = free_address();
+        let node_name = Uuid::new_v4().to_string();
+        let ca_cert = distributed::server::root_cert(true, None).unwrap();
+        let node_cert = distributed::server::gen_node_cert(&node_name).unwrap();
+        let quic_client = quic::new_quic_client(&ca_cert).unwrap();
+        let (id, control_client, signed_cert_pem) = control::Client::register(
+            node_address,
+            node_name,
+            HashMap::new(),
+            control_address,
+            quic_client.clone(),
+            node_cert.serialize_request_pem().unwrap(),
+        )
+        .await
+        .unwrap();
+        let node_client = distributed::Client::new(id, control_client.clone(), quic_client)
+            .await
+            .unwrap();
+        let distributed = DistributedProcessState::new(id, control_client, node_client)
+            .await
+            .unwrap();
+
+        let runtime = WasmtimeRuntime::new(&default_config()).unwrap();
+        let envs = Arc::new(LunaticEnvironments::default());
+        let env = envs.create(1);
+        tokio::task::spawn(distributed::server::node_server(
+            ServerCtx {
+                envs,
+                modules: Modules::<DefaultProcessState>::default(),
+                distributed: distributed.clone(),
+                runtime: runtime.clone(),
+                max_message_size,
+            },
+            node_address,
+            signed_cert_pem,
+            node_cert.serialize_private_key_pem(),
+        ));
+        TestNode {
+            id,
+            env,
+            distributed,
+            runtime,
+        }
+    }
+
+    #[tokio::test]
+    async fn node_rejects_messages_over_max_size() {
+        let rejected = rejected_messages();
+        let control_address = start_control_server();
+        let sender = start_node(control_address, None).await;
+        let receiver = start_node(control_address, Some(4)).await;
+        let (process_id, _, mut signals) = add_receiver(&receiver.env, 1);
+
+        let send = |data: Vec<u8>| {
+            let message = sender.distributed.node_client.message_process(
+                receiver.id,
+                1,
+                process_id,
+                None,
+                data,
+                Vec::new(),
+                MessageSender {
+                    node_id: sender.id,
+                    process_id: 1,
+                },
+            );
+            tokio::time::timeout(Duration::from_secs(10), message)
+        };
+        assert!(matches!(
+            send(vec![0; 5]).await.unwrap(),
+            Err(ClientError::MessageTooLarge)
+        ));
+        assert!(rejected_messages() > rejected);
+        assert!(signals.try_recv().is_err());
+
+        send(vec![0; 4]).await.unwrap().unwrap();
+        assert!(matches!(signals.recv().await, Some(Signal::Message(_))));
+    }
+
+    #[tokio::test]
+    async fn remote_messages_keep_their_sender() {
+        let control_address = start_control_server();
+        let sender = start_node(control_address, None).await;
+        let receiver = start_node(control_address, None).await;
+        let (process_id, _, mut signals) = add_receiver(&receiver.env, 1);
+
+        let message_sender = MessageSender {
+            node_id: sender.id,
+            process_id: 7,
+        };
+        let message = sender.distributed.node_client.message_process(
+            receiver.id,
+            1,
+            process_id,
+            None,
+            Vec::new(),
+            Vec::new(),
+            message_sender,
+        );
+        tokio::time::timeout(Duration::from_secs(10), message)
+            .await
+            .unwrap()
+            .unwrap();
+        match signals.recv().await {
+            Some(Signal::Message(message)) => assert_eq!(message.sender(), Some(message_sender)),
+            signal => panic!("Unexpected signal {:?}", signal),
+        }
+    }
+
+    #[tokio::test]
+    async fn modules_are_sent_by_id() {
+        let control_address = start_control_server();
+        let sender = start_node(control_address, None).await;
+        let receiver = start_node(control_address, None).await;
+        let (process_id, _, mut signals) = add_receiver(&receiver.env, 1);
+
+        let raw_module = wat::parse_str("(module)").unwrap();
+        let raw_module = sender
+            .distributed
+            .control
+            .add_module(raw_module)
+            .await
+            .unwrap();
+        let module_id = raw_module.id.unwrap();
+        let module = sender
+            .runtime
+            .compile_module::<DefaultProcessState>(raw_module)
+            .unwrap();
+        let mut message = DataMessage::new_from_vec(None, Vec::new());
+        message.add_resource(Arc::new(module));
+        // Taken resources keep their place
+        message.resources.push(None);
+        let resources = serialize_resources::<DefaultProcessState>(message.resources).unwrap();
+
+        let send = |resources| {
+            let message = sender.distributed.node_client.message_process(
+                receiver.id,
+                1,
+                process_id,
+                None,
+                Vec::new(),
+                resources,
+                MessageSender {
+                    node_id: sender.id,
+                    process_id: 1,
+                },
+            );
+            tokio::time::timeout(Duration::from_secs(10), message)
+        };
+        send(resources).await.unwrap().unwrap();
+        match signals.recv().await {
+            Some(Signal::Message(Message::Data(mut message))) => {
+                assert_eq!(message.resources.len(), 2);
+                assert!(message.resources[1].is_none());
+                let module = message.take_module::<DefaultProcessState>(0).unwrap();
+                assert_eq!(module.source().id, Some(module_id));
+            }
+            signal => panic!("Unexpected signal {:?}", signal),
+        }
+
+        // Modules that the control server doesn't know about
+        let unknown = vec![Some(Resource::Module(u64::MAX))];
+        assert!(matches!(
+            send(unknown).await.unwrap(),
+            Err(ClientError::ModuleNotFound)
+        ));
+        assert!(signals.try_recv().is_err());
+    }
+
+    #[test]
+    fn only_registered_modules_can_be_serialized() {
+        let runtime = WasmtimeRuntime::new(&default_config()).unwrap();
+        let raw_module = wat::parse_str("(module)").unwrap();
+        // Compiled at runtime, without an ID from the control server
+        let module = runtime
+            .compile_module::<DefaultProcessState>(raw_module.into())
+            .unwrap();
+        let mut message = DataMessage::new_from_vec(None, Vec::new());
+        message.add_resource(Arc::new(module));
+        assert!(matches!(
+            serialize_resources::<DefaultProcessState>(message.resources),
+            Err(ClientError::UnsupportedResource(_))
+        ));
+
+        let mut message = DataMessage::new_from_vec(None, Vec::new());
+        message.add_resource(Arc::new(SharedBuffer::new(vec![1, 2, 3])));
+        assert!(matches!(
+            serialize_resources::<DefaultProcessState>(message.resources),
+            Err(ClientError::UnsupportedResource(_))
+        ));
+    }
 }
@@ -10,14 +10,43 @@
     (import "lunatic::message" "read_data" (func (param i32 i32) (result i32)))
     (import "lunatic::message" "seek_data" (func (param i64)))
     (import "lunatic::message" "get_tag" (func (result i64)))
+    (import "lunatic::message" "get_sender" (func (param i32 i32) (result i32)))
     (import "lunatic::message" "data_size" (func (result i64)))
+    (import "lunatic::message" "get_process_died_id" (func (result i64)))
+    (import "lunatic::message" "get_link_died_reason" (func (result i32)))
+    (import "lunatic::message" "get_link_died_exit_code" (func (result i32)))
+    (import "lunatic::message" "link_died_message_size" (func (result i32)))
+    (import "lunatic::message" "get_link_died_message" (func (param i32)))
     (import "lunatic::message" "push_tcp_stream" (func (param i64) (result i64)))
     (import "lunatic::message" "take_tcp_stream" (func (param i64) (result i64)))
     (import "lunatic::message" "push_udp_socket" (func (param i64) (result i64)))
     (import "lunatic::message" "take_udp_socket" (func (param i64) (result i64)))
+    (import "lunatic::message" "create_shared_buffer" (func (param i32 i32) (result i64)))
+    (import "lunatic::message" "shared_buffer_size" (func (param i64) (result i64)))
+    (import "lunatic::message" "read_shared_buffer" (func (param i64 i64 i32 i32) (result i32)))
+    (import "lunatic::message" "drop_shared_buffer" (func (param i64)))
+    (import "lunatic::message" "push_shared_buffer" (func (param i64) (result i64)))
+    (import "lunatic::message" "take_shared_buffer" (func (param i64) (result i64)))
+    (import "lunatic::message" "push_channel_sender" (func (param i64) (result i64)))
+    (import "lunatic::message" "take_channel_sender" (func (param i64) (result i64)))
+    (import "lunatic::message" "push_channel_receiver" (func (param i64) (result i64)))
+    (import "lunatic::message" "take_channel_receiver" (func (param i64) (result i64)))
     (import "lunatic::message" "send" (func (param i64) (result i32)))
+    (import "lunatic::message" "send_priority" (func (param i64) (result i32)))
+    (import "lunatic::message" "send_to_name" (func (param i32 i32) (result i32)))
+    (import "lunatic::message" "broadcast" (func (param i32 i32) (result i32)))
     (import "lunatic::message" "send_receive_skip_search" (func (param i64 i64) (result i32)))
     (import "lunatic::message" "receive" (func (param i32 i32 i64) (result i32)))
+    (import "lunatic::message" "receive_except" (func (param i32 i32 i64) (result i32)))
+    (import "lunatic::message" "receive_link_died" (func (param i32 i32 i64) (result i32)))
+    (import "lunatic::message" "receive_from" (func (param i64 i64 i64) (result i32)))
+
+    (import "lunatic::channel" "create" (func (param i32 i32 i32 i32)))
+    (import "lunatic::channel" "clone_sender" (func (param i64) (result i64)))
+    (import "lunatic::channel" "drop_sender" (func (param i64)))
+    (import "lunatic::channel" "drop_receiver" (func (param i64)))
+    (import "lunatic::channel" "send" (func (param i64 i64) (result i32)))
+    (import "lunatic::channel" "receive" (func (param i64 i64) (result i32)))
 
     (import "lunatic::timer" "send_after" (func (param i64 i64) (result i64)))
     (import "lunatic::timer" "cancel_timer" (func (param i64) (result i32)))
@@ -63,19 +92,41 @@
     (import "lunatic::process" "config_get_max_memory" (func (param i64) (result i64)))
     (import "lunatic::process" "config_set_max_fuel" (func (param i64 i64)))
     (import "lunatic::process" "config_get_max_fuel" (func (param i64) (result i64)))
+    (import "lunatic::process" "config_set_mailbox_capacity" (func (param i64 i64)))
+    (import "lunatic::process" "config_get_mailbox_capacity" (func (param i64) (result i64)))
+    (import "lunatic::process" "config_set_mailbox_overflow" (func (param i64 i32)))
+    (import "lunatic::process" "config_get_mailbox_overflow" (func (param i64) (result i32)))
+    (import "lunatic::process" "config_set_max_message_size" (func (param i64 i64)))
+    (import "lunatic::process" "config_get_max_message_size" (func (param i64) (result i64)))
     (import "lunatic::process" "config_can_compile_modules" (func (param i64) (result i32)))
     (import "lunatic::process" "config_set_can_compile_modules" (func (param i64 i32)))
     (import "lunatic::process" "config_can_create_configs" (func (param i64) (result i32)))
     (import "lunatic::process" "config_set_can_create_configs" (func (param i64 i32)))
     (import "lunatic::process" "config_can_spawn_processes" (func (param i64) (result i32)))
     (import "lunatic::process" "config_set_can_spawn_processes" (func (param i64 i32)))
+    (import "lunatic::process" "config_can_list_processes" (func (param i64) (result i32)))
+    (import "lunatic::process" "config_set_can_list_processes" (func (param i64 i32)))
+    (import "lunatic::process" "config_can_access_environments" (func (param i64) (result i32)))
+    (import "lunatic::process" "config_set_can_access_environments" (func (param i64 i32)))
+    (import "lunatic::process" "config_can_create_environments" (func (param i64) (result i32)))
+    (import "lunatic::process" "config_set_can_create_environments" (func (param i64 i32)))
+    (import "lunatic::process" "create_environment" (func (param i64) (result i64)))
     (import "lunatic::process" "spawn" (func (param i64 i64 i64 i32 i32 i32 i32 i32) (result i32)))
+    (import "lunatic::process" "spawn_in_environment" (func (param i64 i64 i64 i64 i32 i32 i32 i32 i32) (result i32)))
     (import "lunatic::process" "sleep_ms" (func (param i64)))
     (import "lunatic::process" "die_when_link_dies" (func (param i32)))
     (import "lunatic::process" "process_id" (func (result i64)))
+    (import "lunatic::process" "info" (func (param i64 i32) (result i32)))
+    (import "lunatic::process" "list" (func (param i32 i32) (result i32)))
+    (import "lunatic::process" "list_info" (func (param i32 i32) (result i32)))
     (import "lunatic::process" "link" (func (param i64 i64)))
     (import "lunatic::process" "unlink" (func (param i64)))
     (import "lunatic::process" "kill" (func (param i64)))
+    (import "lunatic::process" "shutdown" (func (param i64 i64)))
+    (import "lunatic::process" "exit" (func (param i32 i32)))
+    (import "lunatic::process" "hibernate" (func (param i32 i32 i32 i32)))
+    (import "lunatic::process" "monitor" (func (param i64)))
+    (import "lunatic::process" "demonitor" (func (param i64)))
 
     (import "lunatic::version" "major" (func (result i32)))
     (import "lunatic::version" "minor" (func (result i32)))
@@ -86,8 +137,29 @@
     (import "lunatic::wasi" "config_preopen_dir" (func (param i64 i32 i32)))
 
     (import "lunatic::registry" "put" (func (param i32 i32 i64 i64)))
+    (import "lunatic::registry" "put_permanent" (func (param i32 i32 i64 i64)))
     (import "lunatic::registry" "get" (func (param i32 i32 i32 i32) (result i32)))
     (import "lunatic::registry" "remove" (func (param i32 i32)))
+    (import "lunatic::registry" "get_in_environment" (func (param i64 i32 i32 i32 i32) (result i32)))
+    (import "lunatic::registry" "put_if_absent" (func (param i32 i32 i64 i64) (result i32)))
+    (import "lunatic::registry" "compare_and_swap" (func (param i32 i32 i64 i64 i64 i64) (result i32)))
+    (import "lunatic::registry" "watch" (func (param i32 i32 i64)))
+    (import "lunatic::registry" "unwatch" (func (param i32 i32) (result i32)))
+    (import "lunatic::registry" "put_with_metadata" (func (param i32 i32 i64 i64 i32 i32)))
+    (import "lunatic::registry" "get_metadata" (func (param i32 i32 i32 i32) (result i64)))
+    (import "lunatic::registry" "list" (func (param i32 i32) (result i64)))
+    (import "lunatic::registry" "list_next" (func (param i64 i32 i32 i32 i32) (result i32)))
+    (import "lunatic::registry" "list_current" (func (param i64 i32 i32)))
+    (import "lunatic::registry" "drop_list_iterator" (func (param i64)))
+    (import "lunatic::registry" "group_join" (func (param i32 i32 i64) (result i32)))
+    (import "lunatic::registry" "group_leave" (func (param i32 i32 i64) (result i32)))
+    (import "lunatic::registry" "group_size" (func (param i32 i32) (result i32)))
+    (import "lunatic::registry" "group_members" (func (param i32 i32 i32 i32) (result i32)))
+
+    (import "lunatic::supervisor" "create_spec" (func (param i32 i32 i64) (result i64)))
+    (import "lunatic::supervisor" "drop_spec" (func (param i64)))
+    (import "lunatic::supervisor" "add_child" (func (param i64 i64 i64 i32 i32 i32 i32)))
+    (import "lunatic::supervisor" "start" (func (param i64 i64) (result i64)))
 
     (import "lunatic::distributed" "nodes_count" (func (result i32)))
     (import "lunatic::distributed" "get_nodes" (func (param i32 i32) (result i32)))